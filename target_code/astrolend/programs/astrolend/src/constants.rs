@@ -15,6 +15,21 @@ pub const FEE_VAULT_SEED: &str = "fee_vault";
 pub const EMISSIONS_AUTH_SEED: &str = "emissions_auth_seed";
 pub const EMISSIONS_TOKEN_ACCOUNT_SEED: &str = "emissions_token_account_seed";
 
+pub const PENDING_BANK_CONFIG_SEED: &str = "pending_bank_config";
+
+pub const INSURANCE_SHARE_MINT_SEED: &str = "insurance_share_mint";
+pub const INSURANCE_UNSTAKE_REQUEST_SEED: &str = "insurance_unstake_request";
+pub const INSURANCE_UNSTAKE_ESCROW_SEED: &str = "insurance_unstake_escrow";
+
+pub const GROUP_RESERVE_VAULT_SEED: &str = "group_reserve_vault";
+pub const GROUP_RESERVE_VAULT_AUTHORITY_SEED: &str = "group_reserve_vault_auth";
+
+pub const ACCOUNT_DELEGATE_SEED: &str = "account_delegate";
+
+pub const ASTROLEND_ACCOUNT_SEED: &str = "astrolend_account";
+
+pub const DELEVERAGE_ORDER_SEED: &str = "deleverage_order";
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "devnet")] {
         pub const PYTH_ID: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
@@ -38,6 +53,14 @@ cfg_if::cfg_if! {
 pub const LIQUIDATION_LIQUIDATOR_FEE: I80F48 = I80F48!(0.025);
 pub const LIQUIDATION_INSURANCE_FEE: I80F48 = I80F48!(0.025);
 
+/// Reduced fees of liquidations in the soft liquidation zone, see `lending_account_soft_liquidate`.
+pub const SOFT_LIQUIDATION_LIQUIDATOR_FEE: I80F48 = I80F48!(0.01);
+pub const SOFT_LIQUIDATION_INSURANCE_FEE: I80F48 = I80F48!(0.005);
+/// Length in seconds of the window over which soft liquidations of an account are capped.
+pub const SOFT_LIQUIDATION_WINDOW: i64 = 60 * 60;
+/// Share of the account asset value that can be soft liquidated per window.
+pub const SOFT_LIQUIDATION_MAX_WINDOW_SHARE: I80F48 = I80F48!(0.1);
+
 pub const SECONDS_PER_YEAR: I80F48 = I80F48!(31_536_000);
 
 pub const MAX_PYTH_ORACLE_AGE: u64 = 60;
@@ -72,9 +95,47 @@ pub const ZERO_AMOUNT_THRESHOLD: I80F48 = I80F48!(0.0001);
 pub const EMISSIONS_FLAG_BORROW_ACTIVE: u64 = 1 << 0;
 pub const EMISSIONS_FLAG_LENDING_ACTIVE: u64 = 1 << 1;
 pub const PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG: u64 = 1 << 2;
+/// The emissions rate is a number of emitted tokens per second, shared pro-rata by the shares of
+/// the emitting side (lending or borrowing, not both), instead of an APR.
+pub const EMISSIONS_FLAG_PRO_RATA: u64 = 1 << 3;
+/// Borrowing from the bank is not allowed during a flashloan.
+pub const FLASHLOAN_BORROW_DISABLED_FLAG: u64 = 1 << 4;
+
+pub(crate) const EMISSION_FLAGS: u64 =
+    EMISSIONS_FLAG_BORROW_ACTIVE | EMISSIONS_FLAG_LENDING_ACTIVE | EMISSIONS_FLAG_PRO_RATA;
+pub(crate) const GROUP_FLAGS: u64 =
+    PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG | FLASHLOAN_BORROW_DISABLED_FLAG;
 
-pub(crate) const EMISSION_FLAGS: u64 = EMISSIONS_FLAG_BORROW_ACTIVE | EMISSIONS_FLAG_LENDING_ACTIVE;
-pub(crate) const GROUP_FLAGS: u64 = PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG;
+/// Flashloan fees are in basis points of the repaid amount, capped at 100%.
+pub const MAX_FLASHLOAN_FEE_BPS: u16 = 10_000;
+
+/// Instruction discriminator of the callback invoked by `lending_account_flashloan_with_callback`,
+/// the Anchor sighash of `astrolend_flashloan_callback`.
+pub const FLASHLOAN_CALLBACK_DISCRIMINATOR: [u8; 8] = [124, 148, 61, 122, 253, 24, 201, 245];
+
+/// Maximum bank config delay of a group, 30 days. The delay can't be lowered once set.
+pub const MAX_BANK_CONFIG_DELAY: u64 = 30 * 24 * 60 * 60;
+
+/// Maximum number of swap programs allowed by a group.
+pub const MAX_SWAP_PROGRAMS: usize = 4;
+
+/// The group reserve haircut is a premium over the oracle value of the bad debt it buys down,
+/// capped at 10%.
+pub const MAX_GROUP_RESERVE_HAIRCUT: I80F48 = I80F48!(0.1);
+
+/// Deleverage order keeper fees are in basis points of the withdrawn collateral, capped at 1%.
+pub const MAX_DELEVERAGE_ORDER_KEEPER_FEE_BPS: u16 = 100;
+
+/// Self-deleverage swaps can lose at most 2.5% of the oracle value of the withdrawn collateral,
+/// less than the liquidation discount.
+pub const MAX_SELF_DELEVERAGE_SLIPPAGE: I80F48 = I80F48!(0.025);
+
+/// Maximum number of concurrent emissions streams of a bank, the primary stream included.
+///
+/// Every additional stream takes an outstanding emissions slot in each balance of every account,
+/// see `AstrolendAccount::emissions_outstanding`. Raising it changes the account layout and needs
+/// a migration of all existing accounts, as the expansion to `MAX_LENDING_ACCOUNT_BALANCES` did.
+pub const MAX_EMISSIONS_STREAMS: usize = 2;
 
 /// Cutoff timestamp for balance last_update used in accounting collected emissions.
 /// Any balance updates before this timestamp are ignored, and current_timestamp is used instead.
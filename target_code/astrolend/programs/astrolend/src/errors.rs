@@ -96,6 +96,50 @@ pub enum AstrolendError {
     IllegalAction,
     #[msg("Token22 Banks require mint account as first remaining account")] // 6047
     T22MintRequired,
+    #[msg("Bank config change must be queued through the timelock")] // 6048
+    BankConfigTimelockRequired,
+    #[msg("Pending bank config can't be executed yet")] // 6049
+    BankConfigTimelockNotElapsed,
+    #[msg("Insurance staking already setup")] // 6050
+    InsuranceStakingAlreadySetup,
+    #[msg("Insurance staking is not setup")] // 6051
+    InsuranceStakingNotSetup,
+    #[msg("Insurance fund is owned by stakers")] // 6052
+    InsuranceStakingActive,
+    #[msg("Amount too small to mint insurance shares")] // 6053
+    InsuranceStakeTooSmall,
+    #[msg("Insurance unstake cooldown has not elapsed")] // 6054
+    InsuranceUnstakeCooldownActive,
+    #[msg("Insurance unstake window has expired")] // 6055
+    InsuranceUnstakeWindowExpired,
+    #[msg("Group reserve already setup")] // 6056
+    GroupReserveAlreadySetup,
+    #[msg("Group reserve is not setup")] // 6057
+    GroupReserveNotSetup,
+    #[msg("Emissions stream not found")] // 6058
+    EmissionsStreamNotFound,
+    #[msg("Invalid emissions schedule")] // 6059
+    InvalidEmissionsSchedule,
+    #[msg("Invalid emissions vault or authority")] // 6060
+    InvalidEmissionsAccount,
+    #[msg("Borrowing from this bank is disabled during a flashloan")] // 6061
+    FlashloanBorrowDisabled,
+    #[msg("Flashloan was not repaid")] // 6062
+    FlashloanNotRepaid,
+    #[msg("Swap program is not allowed by the group")] // 6063
+    SwapProgramNotAllowed,
+    #[msg("Swap output is below the minimum amount")] // 6064
+    SwapOutputBelowMinimum,
+    #[msg("Account health is above the deleverage order trigger")] // 6065
+    DeleverageOrderNotTriggered,
+    #[msg("Account health did not improve")] // 6066
+    AccountHealthNotImproved,
+    #[msg("Soft liquidation cap of the window exceeded")] // 6067
+    SoftLiquidationCapExceeded,
+    #[msg("Account is frozen")] // 6068
+    AccountFrozen,
+    #[msg("Bad debt must be bought down by the group reserve before being socialized")] // 6069
+    GroupReserveCoverageRequired,
 }
 
 impl From<AstrolendError> for ProgramError {
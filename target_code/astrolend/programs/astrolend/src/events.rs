@@ -53,6 +53,7 @@ pub struct LendingPoolBankAccrueInterestEvent {
     pub delta: u64,
     pub fees_collected: f64,
     pub insurance_collected: f64,
+    pub deficit_repaid: f64,
 }
 
 #[event]
@@ -74,6 +75,68 @@ pub struct LendingPoolBankHandleBankruptcyEvent {
     pub bad_debt: f64,
     pub covered_amount: f64,
     pub socialized_amount: f64,
+    pub deficit_amount: f64,
+}
+
+/// Emitted once all the bad debt of a bankrupt account has been settled, the values at each
+/// index belong to the bank at the same index of `banks`.
+#[event]
+pub struct LendingPoolAccountHandleBankruptcyEvent {
+    pub header: AccountEventHeader,
+    pub banks: Vec<Pubkey>,
+    pub bad_debts: Vec<f64>,
+    pub covered_amounts: Vec<f64>,
+    pub socialized_amounts: Vec<f64>,
+    pub deficit_amounts: Vec<f64>,
+}
+
+#[event]
+pub struct LendingPoolBankSocializeDeficitEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: f64,
+}
+
+#[event]
+pub struct LendingPoolGroupReserveCoverBadDebtEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub reserve_bank: Pubkey,
+    pub reserve_mint: Pubkey,
+    pub covered_amount: f64,
+    pub reserve_amount_paid: u64,
+}
+
+#[event]
+pub struct LendingPoolBankEmissionsClawbackEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub emissions_remaining: f64,
+    pub emissions_reserved: f64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LendingPoolBankInsuranceStakeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct LendingPoolBankInsuranceUnstakeEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
 }
 
 // astrolend account events
@@ -117,6 +180,19 @@ pub struct LendingAccountWithdrawEvent {
     pub close_balance: bool,
 }
 
+/// Emitted when a balance is moved to another account of the same authority, `amount` is in
+/// native tokens of the bank mint.
+#[event]
+pub struct LendingAccountTransferBalanceEvent {
+    pub header: AccountEventHeader,
+    pub destination_astrolend_account: Pubkey,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: f64,
+    pub liability: bool,
+    pub close_balance: bool,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct LiquidationBalances {
     pub liquidatee_asset_balance: f64,
@@ -146,3 +222,19 @@ pub struct AstrolendAccountTransferAccountAuthorityEvent {
     pub old_account_authority: Pubkey,
     pub new_account_authority: Pubkey,
 }
+
+/// Emitted when the account is frozen or unfrozen.
+#[event]
+pub struct AstrolendAccountFreezeEvent {
+    pub header: AccountEventHeader,
+    pub frozen: bool,
+}
+
+/// Emitted when a delegate is granted, updated or revoked, with zero permissions once revoked.
+#[event]
+pub struct AstrolendAccountDelegateEvent {
+    pub header: AccountEventHeader,
+    pub delegate: Pubkey,
+    pub permissions: u64,
+    pub expiry: i64,
+}
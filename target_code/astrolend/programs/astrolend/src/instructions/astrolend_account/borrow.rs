@@ -4,7 +4,10 @@ use crate::{
     events::{AccountEventHeader, LendingAccountBorrowEvent},
     prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG},
+        astrolend_account::{
+            BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG, FROZEN_FLAG,
+            IN_FLASHLOAN_FLAG,
+        },
         astrolend_group::{Bank, BankVaultType},
     },
     utils,
@@ -47,6 +50,12 @@ pub fn lending_account_borrow<'info>(
         !astrolend_account.get_flag(DISABLED_FLAG),
         AstrolendError::AccountDisabled
     );
+    check!(
+        !astrolend_account.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
+
+    let in_flashloan = astrolend_account.get_flag(IN_FLASHLOAN_FLAG);
 
     bank_loader.load_mut()?.accrue_interest(
         clock.unix_timestamp,
@@ -62,7 +71,7 @@ pub fn lending_account_borrow<'info>(
         let mut bank_account = BankAccountWrapper::find_or_create(
             &bank_loader.key(),
             &mut bank,
-            &mut astrolend_account.lending_account,
+            &mut astrolend_account,
         )?;
 
         // User needs to borrow amount + fee to receive amount
@@ -78,7 +87,11 @@ pub fn lending_account_borrow<'info>(
             .transpose()?
             .unwrap_or(amount);
 
-        bank_account.borrow(I80F48::from_num(amount_pre_fee))?;
+        if in_flashloan {
+            bank_account.flashloan_borrow(I80F48::from_num(amount_pre_fee))?;
+        } else {
+            bank_account.borrow(I80F48::from_num(amount_pre_fee))?;
+        }
         bank_account.withdraw_spl_transfer(
             amount_pre_fee,
             bank_liquidity_vault.to_account_info(),
@@ -120,6 +133,7 @@ pub struct LendingAccountBorrow<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load() ?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
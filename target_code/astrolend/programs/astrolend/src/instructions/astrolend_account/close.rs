@@ -1,6 +1,20 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenInterface};
+use solana_program::{clock::Clock, sysvar::Sysvar};
 
-use crate::{check, state::astrolend_account::AstrolendAccount, AstrolendError, AstrolendResult};
+use crate::{
+    bank_signer, check,
+    constants::ZERO_AMOUNT_THRESHOLD,
+    events::{AccountEventHeader, LendingAccountRepayEvent, LendingAccountWithdrawEvent},
+    prelude::*,
+    state::{
+        astrolend_account::{
+            BankAccountWrapper, AstrolendAccount, DISABLED_FLAG, FROZEN_FLAG, IN_FLASHLOAN_FLAG,
+        },
+        astrolend_group::{Bank, BankVaultType},
+    },
+    utils::{self, NumTraitsWithTolerance},
+};
 
 pub fn close_account(ctx: Context<AstrolendAccountClose>) -> AstrolendResult {
     let astrolend_account = &ctx.accounts.astrolend_account.load()?;
@@ -16,10 +30,217 @@ pub fn close_account(ctx: Context<AstrolendAccountClose>) -> AstrolendResult {
 
 #[derive(Accounts)]
 pub struct AstrolendAccountClose<'info> {
-    #[account(mut, close = fee_payer)]
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        close = fee_payer,
+    )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
     #[account(address = astrolend_account.load()?.authority)]
     pub authority: Signer<'info>,
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 }
+
+/// Number of remaining accounts per balance swept by `astrolend_account_sweep_and_close`,
+/// not counting the transfer hook accounts.
+const SWEEP_GROUP_LEN: usize = 6;
+
+/// Empty every balance of the account and close it, sending the rent to `rent_recipient`.
+///
+/// Remaining accounts, one group per active balance:
+/// - bank
+/// - bank mint
+/// - bank liquidity vault authority
+/// - bank liquidity vault
+/// - authority token account, receiving the remaining assets or repaying the remaining liability
+/// - token program of the bank mint
+/// - transfer hook accounts of the bank mint, `hook_accounts_lens` of the group, Token22 only
+///
+/// Balances with no significant amount are closed without a transfer. Emissions outstanding
+/// below one token unit are forfeited, larger amounts have to be claimed first.
+pub fn astrolend_account_sweep_and_close<'info>(
+    ctx: Context<'_, '_, 'info, 'info, AstrolendAccountSweepAndClose<'info>>,
+    hook_accounts_lens: Vec<u8>,
+) -> AstrolendResult {
+    let AstrolendAccountSweepAndClose {
+        astrolend_account: astrolend_account_loader,
+        authority,
+        ..
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    check!(
+        !astrolend_account.get_flag(DISABLED_FLAG),
+        AstrolendError::AccountDisabled
+    );
+    check!(
+        !astrolend_account.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
+    check!(
+        !astrolend_account.get_flag(IN_FLASHLOAN_FLAG),
+        AstrolendError::AccountInFlashloan
+    );
+
+    let mut remaining_ais = ctx.remaining_accounts;
+
+    for hook_accounts_len in hook_accounts_lens {
+        let hook_accounts_len = hook_accounts_len as usize;
+        check!(
+            remaining_ais.len() >= SWEEP_GROUP_LEN + hook_accounts_len,
+            AstrolendError::MissingBankAccount
+        );
+
+        let (accounts, rest) = remaining_ais.split_at(SWEEP_GROUP_LEN);
+        let (hook_accounts, rest) = rest.split_at(hook_accounts_len);
+        remaining_ais = rest;
+
+        let bank_loader = AccountLoader::<Bank>::try_from(&accounts[0])?;
+        let bank_mint = InterfaceAccount::<Mint>::try_from(&accounts[1])?;
+        let bank_liquidity_vault_authority = &accounts[2];
+        let bank_liquidity_vault = &accounts[3];
+        let token_account = &accounts[4];
+        let token_program = Interface::<TokenInterface>::try_from(&accounts[5])?;
+
+        let is_token_2022 = token_program.key() == anchor_spl::token_2022::ID;
+        check!(
+            is_token_2022 || hook_accounts.is_empty(),
+            AstrolendError::InvalidBankAccount
+        );
+
+        let mut bank = bank_loader.load_mut()?;
+        check!(
+            bank.group == astrolend_account.group
+                && bank.mint == bank_mint.key()
+                && bank.liquidity_vault == bank_liquidity_vault.key(),
+            AstrolendError::InvalidBankAccount
+        );
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            bank_loader.key(),
+        )?;
+
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+        let maybe_bank_mint = is_token_2022.then_some(&bank_mint);
+
+        let mut bank_account =
+            BankAccountWrapper::find(&bank_loader.key(), &mut bank, &mut astrolend_account)?;
+
+        let asset_amount = bank_account
+            .bank
+            .get_asset_amount(bank_account.balance.asset_shares.into())?;
+        let liability_amount = bank_account
+            .bank
+            .get_liability_amount(bank_account.balance.liability_shares.into())?;
+
+        let header = AccountEventHeader {
+            signer: Some(authority.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: authority.key(),
+            astrolend_group: bank_account.bank.group,
+        };
+
+        if liability_amount.is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD) {
+            let repay_amount_post_fee = bank_account.repay_all()?;
+            let repay_amount_pre_fee = maybe_bank_mint
+                .map(|mint| {
+                    utils::calculate_pre_fee_spl_deposit_amount(
+                        mint.to_account_info(),
+                        repay_amount_post_fee,
+                        clock.epoch,
+                    )
+                })
+                .transpose()?
+                .unwrap_or(repay_amount_post_fee);
+
+            bank_account.deposit_spl_transfer(
+                repay_amount_pre_fee,
+                token_account.to_account_info(),
+                bank_liquidity_vault.to_account_info(),
+                authority.to_account_info(),
+                maybe_bank_mint,
+                token_program.to_account_info(),
+                hook_accounts,
+            )?;
+
+            emit!(LendingAccountRepayEvent {
+                header,
+                bank: bank_loader.key(),
+                mint: bank_account.bank.mint,
+                amount: repay_amount_post_fee,
+                close_balance: true,
+            });
+        } else if asset_amount.is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD) {
+            let withdraw_amount = bank_account.withdraw_all()?;
+
+            // Dust below one token unit stays with the bank as insurance fees
+            if withdraw_amount > 0 {
+                bank_account.withdraw_spl_transfer(
+                    withdraw_amount,
+                    bank_liquidity_vault.to_account_info(),
+                    token_account.to_account_info(),
+                    bank_liquidity_vault_authority.to_account_info(),
+                    maybe_bank_mint,
+                    token_program.to_account_info(),
+                    bank_signer!(
+                        BankVaultType::Liquidity,
+                        bank_loader.key(),
+                        liquidity_vault_authority_bump
+                    ),
+                    hook_accounts,
+                )?;
+            }
+
+            emit!(LendingAccountWithdrawEvent {
+                header,
+                bank: bank_loader.key(),
+                mint: bank_account.bank.mint,
+                amount: withdraw_amount,
+                close_balance: true,
+            });
+        } else {
+            bank_account.close_balance()?;
+        }
+    }
+
+    check!(remaining_ais.is_empty(), AstrolendError::InvalidBankAccount);
+
+    check!(
+        astrolend_account
+            .lending_account
+            .balances
+            .iter()
+            .all(|balance| !balance.active),
+        AstrolendError::IllegalAction,
+        "Account has balances left to sweep"
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountSweepAndClose<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        close = rent_recipient,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Any account chosen by the authority can receive the rent
+    #[account(mut)]
+    pub rent_recipient: AccountInfo<'info>,
+}
@@ -30,11 +30,8 @@ pub fn lending_account_close_balance(ctx: Context<LendingAccountCloseBalance>) -
         bank_loader.key(),
     )?;
 
-    let mut bank_account = BankAccountWrapper::find(
-        &bank_loader.key(),
-        &mut bank,
-        &mut astrolend_account.lending_account,
-    )?;
+    let mut bank_account =
+        BankAccountWrapper::find(&bank_loader.key(), &mut bank, &mut astrolend_account)?;
 
     bank_account.close_balance()?;
 
@@ -47,6 +44,7 @@ pub struct LendingAccountCloseBalance<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
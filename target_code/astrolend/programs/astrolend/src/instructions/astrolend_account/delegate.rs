@@ -0,0 +1,166 @@
+use crate::{
+    constants::ACCOUNT_DELEGATE_SEED,
+    events::{AccountEventHeader, AstrolendAccountDelegateEvent},
+    prelude::*,
+    state::{account_delegate::AstrolendAccountDelegate, astrolend_account::AstrolendAccount},
+};
+use anchor_lang::prelude::*;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Allow `delegate` to act on the account with `permissions`, until `expiry` if not zero.
+pub fn astrolend_account_grant_delegate(
+    ctx: Context<AstrolendAccountGrantDelegate>,
+    permissions: u64,
+    expiry: i64,
+) -> AstrolendResult {
+    let mut account_delegate = ctx.accounts.account_delegate.load_init()?;
+
+    account_delegate.astrolend_account = ctx.accounts.astrolend_account.key();
+    account_delegate.delegate = ctx.accounts.delegate.key();
+    account_delegate.authority = ctx.accounts.authority.key();
+    account_delegate.configure(permissions, expiry, Clock::get()?.unix_timestamp)?;
+
+    emit_delegate_event(
+        &ctx.accounts.astrolend_account,
+        ctx.accounts.authority.key(),
+        &account_delegate,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountGrantDelegate<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    /// CHECK: The delegate doesn't need explicit checks
+    pub delegate: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = 8 + AstrolendAccountDelegate::LEN,
+        seeds = [
+            ACCOUNT_DELEGATE_SEED.as_bytes(),
+            astrolend_account.key().as_ref(),
+            delegate.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub account_delegate: AccountLoader<'info, AstrolendAccountDelegate>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Replace the permissions and expiry of an existing delegate, granted again by the current
+/// authority.
+pub fn astrolend_account_update_delegate(
+    ctx: Context<AstrolendAccountUpdateDelegate>,
+    permissions: u64,
+    expiry: i64,
+) -> AstrolendResult {
+    let mut account_delegate = ctx.accounts.account_delegate.load_mut()?;
+
+    account_delegate.authority = ctx.accounts.authority.key();
+    account_delegate.configure(permissions, expiry, Clock::get()?.unix_timestamp)?;
+
+    emit_delegate_event(
+        &ctx.accounts.astrolend_account,
+        ctx.accounts.authority.key(),
+        &account_delegate,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountUpdateDelegate<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = account_delegate.load()?.astrolend_account == astrolend_account.key(),
+    )]
+    pub account_delegate: AccountLoader<'info, AstrolendAccountDelegate>,
+}
+
+/// Remove a delegate, the rent goes back to the authority.
+pub fn astrolend_account_revoke_delegate(
+    ctx: Context<AstrolendAccountRevokeDelegate>,
+) -> AstrolendResult {
+    let mut account_delegate = *ctx.accounts.account_delegate.load()?;
+    account_delegate.permissions = 0;
+
+    emit_delegate_event(
+        &ctx.accounts.astrolend_account,
+        ctx.accounts.authority.key(),
+        &account_delegate,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountRevokeDelegate<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        mut,
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        constraint = account_delegate.load()?.astrolend_account == astrolend_account.key(),
+    )]
+    pub account_delegate: AccountLoader<'info, AstrolendAccountDelegate>,
+}
+
+fn emit_delegate_event(
+    astrolend_account_loader: &AccountLoader<AstrolendAccount>,
+    signer: Pubkey,
+    account_delegate: &AstrolendAccountDelegate,
+) -> AstrolendResult {
+    let astrolend_account = astrolend_account_loader.load()?;
+
+    emit!(AstrolendAccountDelegateEvent {
+        header: AccountEventHeader {
+            signer: Some(signer),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        delegate: account_delegate.delegate,
+        permissions: account_delegate.permissions,
+        expiry: account_delegate.expiry,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,426 @@
+use crate::{
+    bank_signer, check,
+    constants::{DELEVERAGE_ORDER_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
+    events::{AccountEventHeader, LendingAccountRepayEvent, LendingAccountWithdrawEvent},
+    math_error,
+    prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
+    state::{
+        astrolend_account::{
+            AstrolendAccount, BankAccountWrapper, RiskEngine, DISABLED_FLAG, FROZEN_FLAG,
+        },
+        astrolend_group::{Bank, BankVaultType, WrappedI80F48},
+        deleverage_order::DeleverageOrder,
+    },
+    utils,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+use super::swap_collateral::swap_with_min_output;
+
+/// Place an order allowing any keeper to deleverage the account once its maintenance health
+/// drops below `trigger_health`, see `DeleverageOrder`.
+pub fn lending_account_place_deleverage_order(
+    ctx: Context<LendingAccountPlaceDeleverageOrder>,
+    trigger_health: WrappedI80F48,
+    withdraw_amount: u64,
+    min_repay_amount: u64,
+    keeper_fee_bps: u16,
+) -> AstrolendResult {
+    check!(
+        !ctx.accounts.astrolend_account.load()?.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
+
+    let mut deleverage_order = ctx.accounts.deleverage_order.load_init()?;
+
+    deleverage_order.astrolend_account = ctx.accounts.astrolend_account.key();
+    deleverage_order.authority = ctx.accounts.authority.key();
+    deleverage_order.collateral_bank = ctx.accounts.collateral_bank.key();
+    deleverage_order.liability_bank = ctx.accounts.liability_bank.key();
+    deleverage_order.configure(
+        trigger_health,
+        withdraw_amount,
+        min_repay_amount,
+        keeper_fee_bps,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountPlaceDeleverageOrder<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    #[account(
+        constraint = collateral_bank.load()?.group == astrolend_account.load()?.group,
+    )]
+    pub collateral_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        constraint = liability_bank.load()?.group == astrolend_account.load()?.group,
+        constraint = liability_bank.key() != collateral_bank.key() @ AstrolendError::IllegalAction,
+    )]
+    pub liability_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = 8 + DeleverageOrder::LEN,
+        seeds = [
+            DELEVERAGE_ORDER_SEED.as_bytes(),
+            astrolend_account.key().as_ref(),
+            collateral_bank.key().as_ref(),
+            liability_bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub deleverage_order: AccountLoader<'info, DeleverageOrder>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Cancel a deleverage order, the rent goes back to the authority.
+pub fn lending_account_cancel_deleverage_order(
+    _ctx: Context<LendingAccountCancelDeleverageOrder>,
+) -> AstrolendResult {
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountCancelDeleverageOrder<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        mut,
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        constraint = deleverage_order.load()?.astrolend_account == astrolend_account.key(),
+    )]
+    pub deleverage_order: AccountLoader<'info, DeleverageOrder>,
+}
+
+/// Permissionlessly execute a triggered deleverage order:
+///
+/// 1. Accrue interest on both banks
+/// 2. Verify that the maintenance health of the account is below the order trigger
+/// 3. Withdraw the order amount plus the keeper fee from the collateral bank to the keeper's
+///    collateral token account
+/// 4. Invoke the swap program with `swap_data` and the forwarded accounts
+/// 5. Repay the liability with everything the keeper's liability token account received,
+///    failing if it is less than the order minimum. Any excess is deposited.
+/// 6. Verify that the maintenance health of the account improved
+/// 7. Close the order, the rent goes back to the authority
+///
+/// Remaining accounts:
+/// - the collateral bank mint, then the liability bank mint, for Token-2022 banks
+/// - `swap_accounts_count` accounts forwarded to the swap program, signer and writable flags kept
+/// - the oracle accounts used for the health checks
+pub fn lending_account_execute_deleverage_order<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountExecuteDeleverageOrder<'info>>,
+    swap_accounts_count: u8,
+    swap_data: Vec<u8>,
+) -> AstrolendResult {
+    let LendingAccountExecuteDeleverageOrder {
+        astrolend_account: astrolend_account_loader,
+        deleverage_order,
+        keeper,
+        collateral_bank: collateral_bank_loader,
+        keeper_collateral_token_account,
+        collateral_bank_liquidity_vault_authority,
+        collateral_bank_liquidity_vault,
+        collateral_token_program,
+        liability_bank: liability_bank_loader,
+        keeper_liability_token_account,
+        liability_bank_liquidity_vault,
+        liability_token_program,
+        swap_program,
+        ..
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+    let order = *deleverage_order.load()?;
+    let maybe_collateral_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*collateral_bank_loader.load()?,
+        collateral_token_program.key,
+    )?;
+    let maybe_liability_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*liability_bank_loader.load()?,
+        liability_token_program.key,
+    )?;
+
+    check!(
+        (swap_accounts_count as usize) <= ctx.remaining_accounts.len(),
+        AstrolendError::IllegalAction
+    );
+    let (swap_accounts, health_accounts) = ctx
+        .remaining_accounts
+        .split_at(swap_accounts_count as usize);
+
+    for bank_loader in [&collateral_bank_loader, &liability_bank_loader] {
+        bank_loader.load_mut()?.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            bank_loader.key(),
+        )?;
+    }
+
+    let pre_health = {
+        let astrolend_account = astrolend_account_loader.load()?;
+
+        check!(
+            !astrolend_account.get_flag(DISABLED_FLAG),
+            AstrolendError::AccountDisabled
+        );
+        check!(
+            !astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        );
+
+        RiskEngine::new(&astrolend_account, health_accounts)?
+            .check_deleverage_order_condition_and_get_account_health(order.trigger_health.into())?
+    };
+
+    {
+        let mut astrolend_account = astrolend_account_loader.load_mut()?;
+        let mut bank = collateral_bank_loader.load_mut()?;
+
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+
+        let mut bank_account = BankAccountWrapper::find(
+            &collateral_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        let amount = order
+            .withdraw_amount
+            .checked_add(order.get_keeper_fee())
+            .ok_or_else(math_error!())?;
+        let amount_pre_fee = maybe_collateral_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_pre_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    amount,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(amount);
+
+        bank_account.withdraw(I80F48::from_num(amount_pre_fee))?;
+
+        bank_account.withdraw_spl_transfer(
+            amount_pre_fee,
+            collateral_bank_liquidity_vault.to_account_info(),
+            keeper_collateral_token_account.to_account_info(),
+            collateral_bank_liquidity_vault_authority.to_account_info(),
+            maybe_collateral_bank_mint.as_ref(),
+            collateral_token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                collateral_bank_loader.key(),
+                liquidity_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountWithdrawEvent {
+            header: AccountEventHeader {
+                signer: Some(keeper.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: collateral_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_pre_fee,
+            close_balance: false,
+        });
+    }
+
+    let swap_output = swap_with_min_output(
+        swap_program,
+        swap_accounts,
+        swap_data,
+        keeper_liability_token_account,
+        order.min_repay_amount,
+    )?;
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    {
+        let mut bank = liability_bank_loader.load_mut()?;
+
+        let mut bank_account = BankAccountWrapper::find(
+            &liability_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        // The whole output is transferred, the bank receives it net of the transfer fee
+        let amount_post_fee = maybe_liability_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_post_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    swap_output,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(swap_output);
+
+        bank_account.increase_balance(I80F48::from_num(amount_post_fee))?;
+
+        bank_account.deposit_spl_transfer(
+            swap_output,
+            keeper_liability_token_account.to_account_info(),
+            liability_bank_liquidity_vault.to_account_info(),
+            keeper.to_account_info(),
+            maybe_liability_bank_mint.as_ref(),
+            liability_token_program.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountRepayEvent {
+            header: AccountEventHeader {
+                signer: Some(keeper.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: liability_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_post_fee,
+            close_balance: false,
+        });
+    }
+
+    RiskEngine::new(&astrolend_account, health_accounts)?
+        .check_account_health_improved(pre_health)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountExecuteDeleverageOrder<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    /// CHECK: Receives the rent of the executed order
+    #[account(
+        mut,
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        constraint = deleverage_order.load()?.astrolend_account == astrolend_account.key(),
+        constraint = deleverage_order.load()?.authority == authority.key()
+            @ AstrolendError::Unauthorized,
+    )]
+    pub deleverage_order: AccountLoader<'info, DeleverageOrder>,
+
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        address = deleverage_order.load()?.collateral_bank,
+    )]
+    pub collateral_bank: AccountLoader<'info, Bank>,
+
+    /// Receives the withdrawn collateral, which is the swap input, and the keeper fee
+    #[account(mut)]
+    pub keeper_collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            collateral_bank.key().as_ref(),
+        ],
+        bump = collateral_bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub collateral_bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            collateral_bank.key().as_ref(),
+        ],
+        bump = collateral_bank.load()?.liquidity_vault_bump,
+    )]
+    pub collateral_bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+
+    #[account(
+        mut,
+        address = deleverage_order.load()?.liability_bank,
+    )]
+    pub liability_bank: AccountLoader<'info, Bank>,
+
+    /// Receives the swap output, repaid from it, owned by the keeper
+    #[account(
+        mut,
+        token::authority = keeper,
+    )]
+    pub keeper_liability_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            liability_bank.key().as_ref(),
+        ],
+        bump = liability_bank.load()?.liquidity_vault_bump,
+    )]
+    pub liability_bank_liquidity_vault: AccountInfo<'info>,
+
+    pub liability_token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Allow-listed by the group
+    #[account(
+        executable,
+        constraint = astrolend_group.load()?.is_swap_program_allowed(swap_program.key)
+            @ AstrolendError::SwapProgramNotAllowed,
+    )]
+    pub swap_program: AccountInfo<'info>,
+}
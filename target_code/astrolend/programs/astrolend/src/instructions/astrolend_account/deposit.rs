@@ -4,6 +4,7 @@ use crate::{
     events::{AccountEventHeader, LendingAccountDepositEvent},
     prelude::*,
     state::{
+        account_delegate::{self, DELEGATE_DEPOSIT_REPAY_PERMISSION},
         astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG},
         astrolend_group::Bank,
     },
@@ -35,6 +36,14 @@ pub fn lending_account_deposit<'info>(
         ..
     } = ctx.accounts;
     let clock = Clock::get()?;
+    let authority = astrolend_account_loader.load()?.authority;
+    account_delegate::check_signer_permission(
+        &mut ctx.remaining_accounts,
+        &astrolend_account_loader.key(),
+        &authority,
+        signer.key,
+        DELEGATE_DEPOSIT_REPAY_PERMISSION,
+    )?;
     let maybe_bank_mint = utils::maybe_take_bank_mint(
         &mut ctx.remaining_accounts,
         &*bank_loader.load()?,
@@ -55,11 +64,8 @@ pub fn lending_account_deposit<'info>(
         bank_loader.key(),
     )?;
 
-    let mut bank_account = BankAccountWrapper::find_or_create(
-        &bank_loader.key(),
-        &mut bank,
-        &mut astrolend_account.lending_account,
-    )?;
+    let mut bank_account =
+        BankAccountWrapper::find_or_create(&bank_loader.key(), &mut bank, &mut astrolend_account)?;
 
     bank_account.deposit(I80F48::from_num(amount))?;
 
@@ -102,13 +108,12 @@ pub struct LendingAccountDeposit<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
-    #[account(
-        address = astrolend_account.load()?.authority,
-    )]
+    /// The account authority, or a delegate passing its record as first remaining account
     pub signer: Signer<'info>,
 
     #[account(
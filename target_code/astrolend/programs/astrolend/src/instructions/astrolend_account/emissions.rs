@@ -3,37 +3,65 @@ use anchor_spl::token_interface::{
     transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
 };
 
+use fixed::types::I80F48;
+
 use crate::{
     check,
     constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED},
     debug,
+    events::{AccountEventHeader, LendingAccountDepositEvent},
     prelude::{AstrolendError, AstrolendResult},
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG},
+        account_delegate::{self, DELEGATE_WITHDRAW_EMISSIONS_PERMISSION},
+        astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG, FROZEN_FLAG},
         astrolend_group::{Bank, AstrolendGroup},
     },
+    utils,
 };
 
+/// Withdraw the settled emissions of the bank's emissions stream paying out `emissions_mint`.
 pub fn lending_account_withdraw_emissions<'info>(
-    ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawEmissions<'info>>,
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountWithdrawEmissions<'info>>,
 ) -> AstrolendResult {
+    let authority = ctx.accounts.astrolend_account.load()?.authority;
+    let is_delegate = account_delegate::check_signer_permission(
+        &mut ctx.remaining_accounts,
+        &ctx.accounts.astrolend_account.key(),
+        &authority,
+        ctx.accounts.signer.key,
+        DELEGATE_WITHDRAW_EMISSIONS_PERMISSION,
+    )?;
+
+    // Delegates can only withdraw to the authority
+    if is_delegate {
+        check!(
+            ctx.accounts.destination_account.owner == authority,
+            AstrolendError::Unauthorized
+        );
+    }
+
     let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
 
     check!(
         !astrolend_account.get_flag(DISABLED_FLAG),
         AstrolendError::AccountDisabled
     );
+    check!(
+        !astrolend_account.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
 
     let mut bank = ctx.accounts.bank.load_mut()?;
 
     let mut balance = BankAccountWrapper::find(
         ctx.accounts.bank.to_account_info().key,
         &mut bank,
-        &mut astrolend_account.lending_account,
+        &mut astrolend_account,
     )?;
 
     // Settle emissions
-    let emissions_settle_amount = balance.settle_emissions_and_get_transfer_amount()?;
+    let emissions_settle_amount =
+        balance.settle_emissions_and_get_transfer_amount(&ctx.accounts.emissions_mint.key())?;
 
     if emissions_settle_amount > 0 {
         debug!("Transferring {} emissions to user", emissions_settle_amount);
@@ -70,13 +98,12 @@ pub struct LendingAccountWithdrawEmissions<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
-    #[account(
-        address = astrolend_account.load()?.authority,
-    )]
+    /// The account authority, or a delegate passing its record as first remaining account
     pub signer: Signer<'info>,
 
     #[account(
@@ -86,7 +113,8 @@ pub struct LendingAccountWithdrawEmissions<'info> {
     pub bank: AccountLoader<'info, Bank>,
 
     #[account(
-        address = bank.load()?.emissions_mint
+        constraint = bank.load()?.is_emissions_mint(&emissions_mint.key())
+            @ AstrolendError::EmissionsStreamNotFound
     )]
     pub emissions_mint: InterfaceAccount<'info, Mint>,
 
@@ -127,7 +155,7 @@ pub fn lending_account_settle_emissions(
     let mut balance = BankAccountWrapper::find(
         ctx.accounts.bank.to_account_info().key,
         &mut bank,
-        &mut astrolend_account.lending_account,
+        &mut astrolend_account,
     )?;
 
     balance.claim_emissions(Clock::get()?.unix_timestamp.try_into().unwrap())?;
@@ -139,6 +167,7 @@ pub fn lending_account_settle_emissions(
 pub struct LendingAccountSettleEmissions<'info> {
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == bank.load()?.group,
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
@@ -146,3 +175,219 @@ pub struct LendingAccountSettleEmissions<'info> {
     #[account(mut)]
     pub bank: AccountLoader<'info, Bank>,
 }
+
+/// Number of remaining accounts per bank when claimed emissions are sent to a token account.
+const CLAIM_ALL_GROUP_LEN: usize = 5;
+/// Number of remaining accounts per bank when claimed emissions are deposited.
+const CLAIM_ALL_DEPOSIT_GROUP_LEN: usize = 6;
+
+/// Settle and withdraw the emissions of several balances at once.
+///
+/// Remaining accounts, one group per (bank, emissions mint) to claim:
+/// - bank
+/// - emissions mint
+/// - emissions auth
+/// - emissions vault
+/// - destination token account, when `deposit` is false
+///
+/// When `deposit` is true the destination is replaced by:
+/// - deposit bank, a bank of the same group with the emissions mint as its mint
+/// - deposit bank liquidity vault
+///
+/// and the claimed tokens are deposited into the account's balance in the deposit bank.
+///
+/// `emissions_bumps` holds the bumps of the emissions auth and the emissions vault of each group,
+/// so the addresses are checked without searching for the bumps on-chain.
+pub fn lending_account_claim_all_emissions<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountClaimAllEmissions<'info>>,
+    deposit: bool,
+    emissions_bumps: Vec<[u8; 2]>,
+) -> AstrolendResult {
+    let LendingAccountClaimAllEmissions {
+        astrolend_account: astrolend_account_loader,
+        signer,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let group_len = if deposit {
+        CLAIM_ALL_DEPOSIT_GROUP_LEN
+    } else {
+        CLAIM_ALL_GROUP_LEN
+    };
+    check!(
+        !ctx.remaining_accounts.is_empty()
+            && ctx.remaining_accounts.len() % group_len == 0
+            && ctx.remaining_accounts.len() / group_len == emissions_bumps.len(),
+        AstrolendError::InvalidBankAccount
+    );
+
+    let clock = Clock::get()?;
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    check!(
+        !astrolend_account.get_flag(DISABLED_FLAG),
+        AstrolendError::AccountDisabled
+    );
+    check!(
+        !astrolend_account.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
+
+    for (accounts, [emissions_auth_bump, emissions_vault_bump]) in ctx
+        .remaining_accounts
+        .chunks(group_len)
+        .zip(emissions_bumps)
+    {
+        let bank_loader = AccountLoader::<Bank>::try_from(&accounts[0])?;
+        let emissions_mint = InterfaceAccount::<Mint>::try_from(&accounts[1])?;
+        let emissions_auth = &accounts[2];
+        let emissions_vault = &accounts[3];
+
+        let emissions_auth_key = Pubkey::create_program_address(
+            &[
+                EMISSIONS_AUTH_SEED.as_bytes(),
+                bank_loader.key().as_ref(),
+                emissions_mint.key().as_ref(),
+                &[emissions_auth_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| AstrolendError::InvalidEmissionsAccount)?;
+        check!(
+            emissions_auth.key() == emissions_auth_key,
+            AstrolendError::InvalidEmissionsAccount
+        );
+        let emissions_vault_key = Pubkey::create_program_address(
+            &[
+                EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+                bank_loader.key().as_ref(),
+                emissions_mint.key().as_ref(),
+                &[emissions_vault_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| AstrolendError::InvalidEmissionsAccount)?;
+        check!(
+            emissions_vault.key() == emissions_vault_key,
+            AstrolendError::InvalidEmissionsAccount
+        );
+
+        let emissions_settle_amount = {
+            let mut bank = bank_loader.load_mut()?;
+            check!(
+                bank.group == astrolend_account.group,
+                AstrolendError::InvalidBankAccount
+            );
+            check!(
+                bank.is_emissions_mint(&emissions_mint.key()),
+                AstrolendError::EmissionsStreamNotFound
+            );
+
+            BankAccountWrapper::find(&bank_loader.key(), &mut bank, &mut astrolend_account)?
+                .settle_emissions_and_get_transfer_amount(&emissions_mint.key())?
+        };
+
+        if emissions_settle_amount == 0 {
+            continue;
+        }
+
+        let destination = if deposit {
+            let deposit_bank_loader = AccountLoader::<Bank>::try_from(&accounts[4])?;
+            let deposit_bank_liquidity_vault = &accounts[5];
+
+            // The deposit bank may be the bank the emissions were claimed from, the borrow
+            // of that bank is released above.
+            let mut deposit_bank = deposit_bank_loader.load_mut()?;
+            check!(
+                deposit_bank.group == astrolend_account.group
+                    && deposit_bank.mint == emissions_mint.key(),
+                AstrolendError::InvalidBankAccount
+            );
+            check!(
+                deposit_bank.liquidity_vault == deposit_bank_liquidity_vault.key(),
+                AstrolendError::InvalidBankAccount
+            );
+
+            deposit_bank.accrue_interest(
+                clock.unix_timestamp,
+                #[cfg(not(feature = "client"))]
+                deposit_bank_loader.key(),
+            )?;
+
+            let deposit_amount = utils::calculate_post_fee_spl_deposit_amount(
+                emissions_mint.to_account_info(),
+                emissions_settle_amount,
+                clock.epoch,
+            )?;
+
+            BankAccountWrapper::find_or_create(
+                &deposit_bank_loader.key(),
+                &mut deposit_bank,
+                &mut astrolend_account,
+            )?
+            .deposit(I80F48::from_num(deposit_amount))?;
+
+            emit!(LendingAccountDepositEvent {
+                header: AccountEventHeader {
+                    signer: Some(signer.key()),
+                    astrolend_account: astrolend_account_loader.key(),
+                    astrolend_account_authority: astrolend_account.authority,
+                    astrolend_group: astrolend_account.group,
+                },
+                bank: deposit_bank_loader.key(),
+                mint: deposit_bank.mint,
+                amount: deposit_amount,
+            });
+
+            deposit_bank_liquidity_vault
+        } else {
+            &accounts[4]
+        };
+
+        debug!("Transferring {} emissions to user", emissions_settle_amount);
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            &bank_loader.key().to_bytes(),
+            &emissions_mint.key().to_bytes(),
+            &[emissions_auth_bump],
+        ]];
+
+        transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: emissions_vault.to_account_info(),
+                    to: destination.to_account_info(),
+                    authority: emissions_auth.to_account_info(),
+                    mint: emissions_mint.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            emissions_settle_amount,
+            emissions_mint.decimals,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountClaimAllEmissions<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
@@ -7,7 +7,9 @@ use solana_program::{
 use crate::{
     check,
     prelude::*,
-    state::astrolend_account::{AstrolendAccount, RiskEngine, DISABLED_FLAG, IN_FLASHLOAN_FLAG},
+    state::astrolend_account::{
+        AstrolendAccount, RiskEngine, DISABLED_FLAG, FROZEN_FLAG, IN_FLASHLOAN_FLAG,
+    },
 };
 
 pub fn lending_account_start_flashloan(
@@ -28,7 +30,10 @@ pub fn lending_account_start_flashloan(
 
 #[derive(Accounts)]
 pub struct LendingAccountStartFlashloan<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
     #[account(address = astrolend_account.load()?.authority)]
     pub signer: Signer<'info>,
@@ -59,6 +64,10 @@ pub fn check_flashloan_can_start(
         !astrolend_account.load()?.get_flag(DISABLED_FLAG),
         AstrolendError::AccountDisabled
     );
+    check!(
+        !astrolend_account.load()?.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
 
     let current_ix_idx: usize = instructions::load_current_index_checked(sysvar_ixs)?.into();
 
@@ -132,6 +141,7 @@ pub fn lending_account_end_flashloan<'info>(
     let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
 
     astrolend_account.unset_flag(IN_FLASHLOAN_FLAG);
+    astrolend_account.lending_account.clear_flashloan_borrows();
 
     RiskEngine::check_account_init_health(&astrolend_account, ctx.remaining_accounts)?;
 
@@ -140,7 +150,10 @@ pub fn lending_account_end_flashloan<'info>(
 
 #[derive(Accounts)]
 pub struct LendingAccountEndFlashloan<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
     #[account(address = astrolend_account.load()?.authority)]
     pub signer: Signer<'info>,
@@ -0,0 +1,297 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        FLASHLOAN_CALLBACK_DISCRIMINATOR, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
+    },
+    debug,
+    events::{AccountEventHeader, LendingAccountBorrowEvent, LendingAccountRepayEvent},
+    math_error,
+    prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
+    state::{
+        astrolend_account::{
+            BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG, FROZEN_FLAG,
+            IN_FLASHLOAN_FLAG,
+        },
+        astrolend_group::{Bank, BankVaultType},
+    },
+    utils,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Arguments of the `astrolend_flashloan_callback` instruction invoked on the callback program,
+/// serialized after `FLASHLOAN_CALLBACK_DISCRIMINATOR`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FlashloanCallbackArgs {
+    pub astrolend_account: Pubkey,
+    pub bank: Pubkey,
+    /// Amount sent to the destination token account
+    pub amount: u64,
+    /// Amount that must reach the bank liquidity vault before the callback returns, fee included
+    pub repay_amount: u64,
+    pub data: Vec<u8>,
+}
+
+/// 1. Accrue interest
+/// 2. Borrow `amount` and transfer it to the destination token account
+/// 3. Invoke `astrolend_flashloan_callback` on the callback program with the forwarded accounts
+/// 4. Verify the liquidity vault received the borrowed amount plus the flashloan fee, any excess
+///    is deposited
+/// 5. Verify that the user account is in a healthy state
+///
+/// Remaining accounts:
+/// - the bank mint for Token-2022 banks
+/// - `callback_accounts_count` accounts forwarded to the callback, signer and writable flags kept
+/// - the oracle accounts used for the health check
+///
+/// The callback can't re-enter astrolend, so the account is not touched until repayment.
+pub fn lending_account_flashloan_with_callback<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountFlashloanWithCallback<'info>>,
+    amount: u64,
+    callback_accounts_count: u8,
+    callback_data: Vec<u8>,
+) -> AstrolendResult {
+    let LendingAccountFlashloanWithCallback {
+        astrolend_account: astrolend_account_loader,
+        signer,
+        destination_token_account,
+        bank_liquidity_vault,
+        token_program,
+        bank_liquidity_vault_authority,
+        bank: bank_loader,
+        callback_program,
+        ..
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+    let maybe_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*bank_loader.load()?,
+        token_program.key,
+    )?;
+
+    check!(
+        (callback_accounts_count as usize) <= ctx.remaining_accounts.len(),
+        AstrolendError::IllegalFlashloan
+    );
+    let (callback_accounts, health_accounts) = ctx
+        .remaining_accounts
+        .split_at(callback_accounts_count as usize);
+
+    let (amount_pre_fee, repay_amount) = {
+        let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+        check!(
+            !astrolend_account.get_flag(DISABLED_FLAG),
+            AstrolendError::AccountDisabled
+        );
+        check!(
+            !astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        );
+        check!(
+            !astrolend_account.get_flag(IN_FLASHLOAN_FLAG),
+            AstrolendError::IllegalFlashloan
+        );
+
+        let mut bank = bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            bank_loader.key(),
+        )?;
+
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+
+        let mut bank_account = BankAccountWrapper::find_or_create(
+            &bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        // User needs to borrow amount + fee to receive amount
+        let amount_pre_fee = maybe_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_pre_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    amount,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(amount);
+
+        bank_account.flashloan_borrow(I80F48::from_num(amount_pre_fee))?;
+
+        let flashloan_borrowed = bank_account.balance.flashloan_borrowed;
+        let repay_amount = flashloan_borrowed
+            .checked_add(bank_account.bank.get_flashloan_fee(flashloan_borrowed))
+            .ok_or_else(math_error!())?;
+
+        bank_account.withdraw_spl_transfer(
+            amount_pre_fee,
+            bank_liquidity_vault.to_account_info(),
+            destination_token_account.to_account_info(),
+            bank_liquidity_vault_authority.to_account_info(),
+            maybe_bank_mint.as_ref(),
+            token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                bank_loader.key(),
+                liquidity_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountBorrowEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_pre_fee,
+        });
+
+        (amount_pre_fee, repay_amount)
+    };
+
+    bank_liquidity_vault.reload()?;
+    let vault_balance_before = bank_liquidity_vault.amount;
+
+    invoke_flashloan_callback(
+        callback_program,
+        callback_accounts,
+        FlashloanCallbackArgs {
+            astrolend_account: astrolend_account_loader.key(),
+            bank: bank_loader.key(),
+            amount,
+            repay_amount,
+            data: callback_data,
+        },
+    )?;
+
+    bank_liquidity_vault.reload()?;
+    let repaid_amount = bank_liquidity_vault
+        .amount
+        .saturating_sub(vault_balance_before);
+
+    debug!(
+        "Flashloan of {} repaid with {} (expected {})",
+        amount_pre_fee, repaid_amount, repay_amount
+    );
+
+    check!(
+        repaid_amount >= repay_amount,
+        AstrolendError::FlashloanNotRepaid
+    );
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    {
+        let mut bank = bank_loader.load_mut()?;
+        let mut bank_account =
+            BankAccountWrapper::find(&bank_loader.key(), &mut bank, &mut astrolend_account)?;
+
+        let flashloan_borrowed = bank_account.balance.flashloan_borrowed;
+        let flashloan_fee =
+            bank_account.charge_flashloan_fee(flashloan_borrowed, flashloan_borrowed)?;
+
+        let repaid_amount_net = repaid_amount
+            .checked_sub(flashloan_fee)
+            .ok_or_else(math_error!())?;
+        bank_account.increase_balance(I80F48::from_num(repaid_amount_net))?;
+
+        emit!(LendingAccountRepayEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            amount: repaid_amount_net,
+            close_balance: false,
+        });
+    }
+
+    astrolend_account.lending_account.clear_flashloan_borrows();
+
+    // Check account health, if below threshold fail transaction
+    RiskEngine::check_account_init_health(&astrolend_account, health_accounts)?;
+
+    Ok(())
+}
+
+fn invoke_flashloan_callback<'info>(
+    callback_program: &AccountInfo<'info>,
+    callback_accounts: &[AccountInfo<'info>],
+    args: FlashloanCallbackArgs,
+) -> AstrolendResult {
+    let mut data = FLASHLOAN_CALLBACK_DISCRIMINATOR.to_vec();
+    args.serialize(&mut data)?;
+
+    utils::invoke_with_forwarded_accounts(callback_program, callback_accounts, data)
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountFlashloanWithCallback<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load() ?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load() ?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load() ?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(mut)]
+    pub destination_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load() ?.liquidity_vault_authority_bump,
+    )]
+    pub bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load() ?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Any executable program but astrolend, which could otherwise re-enter
+    #[account(
+        executable,
+        constraint = callback_program.key() != crate::ID @ AstrolendError::IllegalFlashloan,
+    )]
+    pub callback_program: AccountInfo<'info>,
+}
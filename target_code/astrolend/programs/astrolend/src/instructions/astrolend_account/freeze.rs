@@ -0,0 +1,131 @@
+use crate::{
+    events::{
+        AccountEventHeader, AstrolendAccountFreezeEvent,
+        AstrolendAccountTransferAccountAuthorityEvent,
+    },
+    prelude::*,
+    state::astrolend_account::AstrolendAccount,
+};
+use anchor_lang::prelude::*;
+
+/// Block withdrawals and borrows of the account, deposits and repayments are still allowed.
+/// Can be signed by the authority or the recovery authority.
+pub fn astrolend_account_freeze(ctx: Context<AstrolendAccountFreeze>) -> AstrolendResult {
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    astrolend_account.freeze(ctx.accounts.signer.key)?;
+
+    emit!(AstrolendAccountFreezeEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: ctx.accounts.astrolend_account.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        frozen: true,
+    });
+
+    Ok(())
+}
+
+/// Lift a freeze, signed by the recovery authority if one is set, otherwise by the authority.
+pub fn astrolend_account_unfreeze(ctx: Context<AstrolendAccountFreeze>) -> AstrolendResult {
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    astrolend_account.unfreeze(ctx.accounts.signer.key)?;
+
+    emit!(AstrolendAccountFreezeEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: ctx.accounts.astrolend_account.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        frozen: false,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountFreeze<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    /// The authority or the recovery authority, checked against the account state
+    pub signer: Signer<'info>,
+}
+
+pub fn astrolend_account_set_recovery_authority(
+    ctx: Context<AstrolendAccountSetRecoveryAuthority>,
+    recovery_authority: Pubkey,
+) -> AstrolendResult {
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    astrolend_account.set_recovery_authority(recovery_authority)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountSetRecoveryAuthority<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+/// Replace the authority of the account, signed by the recovery authority. The account stays
+/// frozen until the recovery authority unfreezes it.
+pub fn astrolend_account_recover_authority(
+    ctx: Context<AstrolendAccountRecoverAuthority>,
+) -> AstrolendResult {
+    let new_account_authority = ctx.accounts.new_authority.key();
+
+    // Ensure astrolend_account is dropped out of scope to not exceed stack frame limits
+    let (old_account_authority, astrolend_group) = {
+        let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+        let old_account_authority = astrolend_account.recover_authority(new_account_authority)?;
+
+        (old_account_authority, astrolend_account.group)
+    };
+
+    emit!(AstrolendAccountTransferAccountAuthorityEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.recovery_authority.key()),
+            astrolend_account: ctx.accounts.astrolend_account.key(),
+            astrolend_account_authority: new_account_authority,
+            astrolend_group,
+        },
+        old_account_authority,
+        new_account_authority,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountRecoverAuthority<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.recovery_authority,
+    )]
+    pub recovery_authority: Signer<'info>,
+
+    /// CHECK: The new account authority doesn't need explicit checks
+    pub new_authority: AccountInfo<'info>,
+}
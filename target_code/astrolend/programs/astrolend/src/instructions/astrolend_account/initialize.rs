@@ -1,4 +1,5 @@
 use crate::{
+    constants::ASTROLEND_ACCOUNT_SEED,
     events::{AccountEventHeader, AstrolendAccountCreateEvent},
     prelude::*,
     state::astrolend_account::AstrolendAccount,
@@ -14,6 +15,30 @@ pub fn initialize_account(ctx: Context<AstrolendAccountInitialize>) -> Astrolend
         ..
     } = ctx.accounts;
 
+    init_account(astrolend_group, authority, astrolend_account_loader)
+}
+
+/// Initialize an account at the address derived from the group, the authority and
+/// `account_index`, so an authority can find its accounts without an indexer.
+pub fn initialize_account_pda(
+    ctx: Context<AstrolendAccountInitializePda>,
+    _account_index: u16,
+) -> AstrolendResult {
+    let AstrolendAccountInitializePda {
+        authority,
+        astrolend_group,
+        astrolend_account: astrolend_account_loader,
+        ..
+    } = ctx.accounts;
+
+    init_account(astrolend_group, authority, astrolend_account_loader)
+}
+
+fn init_account(
+    astrolend_group: &AccountLoader<AstrolendGroup>,
+    authority: &Signer,
+    astrolend_account_loader: &AccountLoader<AstrolendAccount>,
+) -> AstrolendResult {
     let mut astrolend_account = astrolend_account_loader.load_init()?;
 
     astrolend_account.initialize(astrolend_group.key(), authority.key());
@@ -48,3 +73,30 @@ pub struct AstrolendAccountInitialize<'info> {
 
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+#[instruction(account_index: u16)]
+pub struct AstrolendAccountInitializePda<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = 8 + std::mem::size_of::<AstrolendAccount>(),
+        seeds = [
+            ASTROLEND_ACCOUNT_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+            authority.key().as_ref(),
+            &account_index.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -1,8 +1,10 @@
 use crate::constants::{
     INSURANCE_VAULT_SEED, LIQUIDATION_INSURANCE_FEE, LIQUIDATION_LIQUIDATOR_FEE,
+    SOFT_LIQUIDATION_INSURANCE_FEE, SOFT_LIQUIDATION_LIQUIDATOR_FEE,
+    SOFT_LIQUIDATION_MAX_WINDOW_SHARE,
 };
 use crate::events::{AccountEventHeader, LendingAccountLiquidateEvent, LiquidationBalances};
-use crate::state::astrolend_account::{calc_amount, calc_value, RiskEngine};
+use crate::state::astrolend_account::{calc_amount, calc_value, RiskEngine, RiskRequirementType};
 use crate::state::astrolend_group::{Bank, BankVaultType};
 use crate::state::price::{OraclePriceFeedAdapter, OraclePriceType, PriceAdapter, PriceBias};
 use crate::{
@@ -10,7 +12,7 @@ use crate::{
     constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
     state::astrolend_account::{BankAccountWrapper, AstrolendAccount},
 };
-use crate::{check, debug, prelude::*, utils};
+use crate::{check, debug, math_error, prelude::*, utils};
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{TokenAccount, TokenInterface};
 use fixed::types::I80F48;
@@ -76,8 +78,32 @@ use solana_program::sysvar::Sysvar;
 ///  ]
 
 pub fn lending_account_liquidate<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountLiquidate<'info>>,
+    asset_amount: u64,
+) -> AstrolendResult {
+    liquidate(ctx, asset_amount, false)
+}
+
+/// Partially liquidate an account in the soft liquidation zone, failing the soft liquidation
+/// requirement of its banks (see `BankConfig::soft_liquidation_threshold`) but not the
+/// maintenance requirement.
+///
+/// Works like `lending_account_liquidate`, with the reduced `SOFT_LIQUIDATION_LIQUIDATOR_FEE` and
+/// `SOFT_LIQUIDATION_INSURANCE_FEE`, and the post liquidation checks made against the soft
+/// liquidation requirement. The value of the collateral soft liquidated from an account within a
+/// `SOFT_LIQUIDATION_WINDOW` is capped to `SOFT_LIQUIDATION_MAX_WINDOW_SHARE` of its assets, so
+/// positions unwind gradually.
+pub fn lending_account_soft_liquidate<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountLiquidate<'info>>,
+    asset_amount: u64,
+) -> AstrolendResult {
+    liquidate(ctx, asset_amount, true)
+}
+
+fn liquidate<'info>(
     mut ctx: Context<'_, '_, 'info, 'info, LendingAccountLiquidate<'info>>,
     asset_amount: u64,
+    soft_liquidation: bool,
 ) -> AstrolendResult {
     check!(
         asset_amount > 0,
@@ -120,14 +146,37 @@ pub fn lending_account_liquidate<'info>(
         )?;
     }
     let init_liquidatee_remaining_len = liquidatee_astrolend_account.get_remaining_accounts_len();
-    let pre_liquidation_health = {
+    let (pre_liquidation_health, maybe_max_soft_liquidation_value) = {
         let liquidatee_accounts_starting_pos =
             ctx.remaining_accounts.len() - init_liquidatee_remaining_len;
         let liquidatee_remaining_accounts =
             &ctx.remaining_accounts[liquidatee_accounts_starting_pos..];
 
-        RiskEngine::new(&liquidatee_astrolend_account, liquidatee_remaining_accounts)?
-            .check_pre_liquidation_condition_and_get_account_health(&ctx.accounts.liab_bank.key())?
+        let risk_engine =
+            RiskEngine::new(&liquidatee_astrolend_account, liquidatee_remaining_accounts)?;
+
+        if soft_liquidation {
+            let (total_assets, _) =
+                risk_engine.get_account_health_components(RiskRequirementType::Equity)?;
+
+            (
+                risk_engine.check_pre_soft_liquidation_condition_and_get_account_health(
+                    &ctx.accounts.liab_bank.key(),
+                )?,
+                Some(
+                    total_assets
+                        .checked_mul(SOFT_LIQUIDATION_MAX_WINDOW_SHARE)
+                        .ok_or_else(math_error!())?,
+                ),
+            )
+        } else {
+            (
+                risk_engine.check_pre_liquidation_condition_and_get_account_health(
+                    &ctx.accounts.liab_bank.key(),
+                )?,
+                None,
+            )
+        }
     };
 
     // ##Accounting changes##
@@ -157,8 +206,25 @@ pub fn lending_account_liquidate<'info>(
             liab_pf.get_price_of_type(OraclePriceType::RealTime, Some(PriceBias::High))?
         };
 
-        let final_discount = I80F48::ONE - (LIQUIDATION_INSURANCE_FEE + LIQUIDATION_LIQUIDATOR_FEE);
-        let liquidator_discount = I80F48::ONE - LIQUIDATION_LIQUIDATOR_FEE;
+        if let Some(max_soft_liquidation_value) = maybe_max_soft_liquidation_value {
+            liquidatee_astrolend_account.record_soft_liquidation(
+                calc_value(asset_amount, asset_price, asset_bank.mint_decimals, None)?,
+                max_soft_liquidation_value,
+                current_timestamp,
+            )?;
+        }
+
+        let (liquidator_fee, insurance_fee) = if soft_liquidation {
+            (
+                SOFT_LIQUIDATION_LIQUIDATOR_FEE,
+                SOFT_LIQUIDATION_INSURANCE_FEE,
+            )
+        } else {
+            (LIQUIDATION_LIQUIDATOR_FEE, LIQUIDATION_INSURANCE_FEE)
+        };
+
+        let final_discount = I80F48::ONE - (insurance_fee + liquidator_fee);
+        let liquidator_discount = I80F48::ONE - liquidator_fee;
 
         // Quantity of liability to be paid off by liquidator
         let liab_amount_liquidator = calc_amount(
@@ -202,7 +268,7 @@ pub fn lending_account_liquidate<'info>(
             let mut bank_account = BankAccountWrapper::find_or_create(
                 &ctx.accounts.liab_bank.key(),
                 &mut liab_bank,
-                &mut liquidator_astrolend_account.lending_account,
+                &mut liquidator_astrolend_account,
             )?;
 
             let pre_balance = bank_account
@@ -223,7 +289,7 @@ pub fn lending_account_liquidate<'info>(
             let mut bank_account = BankAccountWrapper::find(
                 &ctx.accounts.asset_bank.key(),
                 &mut asset_bank,
-                &mut liquidatee_astrolend_account.lending_account,
+                &mut liquidatee_astrolend_account,
             )?;
 
             let pre_balance = bank_account
@@ -231,7 +297,7 @@ pub fn lending_account_liquidate<'info>(
                 .get_asset_amount(bank_account.balance.asset_shares.into())?;
 
             bank_account
-                .withdraw(asset_amount)
+                .withdraw_nominal(asset_amount)
                 .map_err(|_| AstrolendError::IllegalLiquidation)?;
 
             let post_balance = bank_account
@@ -246,7 +312,7 @@ pub fn lending_account_liquidate<'info>(
             let mut bank_account = BankAccountWrapper::find_or_create(
                 &ctx.accounts.asset_bank.key(),
                 &mut asset_bank,
-                &mut liquidator_astrolend_account.lending_account,
+                &mut liquidator_astrolend_account,
             )?;
 
             let pre_balance = bank_account
@@ -276,7 +342,7 @@ pub fn lending_account_liquidate<'info>(
             let mut liquidatee_liab_bank_account = BankAccountWrapper::find_or_create(
                 &ctx.accounts.liab_bank.key(),
                 &mut liab_bank,
-                &mut liquidatee_astrolend_account.lending_account,
+                &mut liquidatee_astrolend_account,
             )?;
 
             let liquidatee_liability_pre_balance =
@@ -350,12 +416,19 @@ pub fn lending_account_liquidate<'info>(
         &ctx.remaining_accounts[liquidator_accounts_starting_pos..liquidatee_accounts_starting_pos];
 
     // Verify liquidatee liquidation post health
-    let post_liquidation_health =
-        RiskEngine::new(&liquidatee_astrolend_account, liquidatee_remaining_accounts)?
-            .check_post_liquidation_condition_and_get_account_health(
-                &ctx.accounts.liab_bank.key(),
-                pre_liquidation_health,
-            )?;
+    let liquidatee_risk_engine =
+        RiskEngine::new(&liquidatee_astrolend_account, liquidatee_remaining_accounts)?;
+    let post_liquidation_health = if soft_liquidation {
+        liquidatee_risk_engine.check_post_soft_liquidation_condition_and_get_account_health(
+            &ctx.accounts.liab_bank.key(),
+            pre_liquidation_health,
+        )?
+    } else {
+        liquidatee_risk_engine.check_post_liquidation_condition_and_get_account_health(
+            &ctx.accounts.liab_bank.key(),
+            pre_liquidation_health,
+        )?
+    };
 
     // Verify liquidator account health
     RiskEngine::check_account_init_health(
@@ -403,6 +476,7 @@ pub struct LendingAccountLiquidate<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(liquidator_astrolend_account.as_ref())?,
         constraint = liquidator_astrolend_account.load()?.group == astrolend_group.key()
     )]
     pub liquidator_astrolend_account: AccountLoader<'info, AstrolendAccount>,
@@ -414,6 +488,7 @@ pub struct LendingAccountLiquidate<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(liquidatee_astrolend_account.as_ref())?,
         constraint = liquidatee_astrolend_account.load()?.group == astrolend_group.key()
     )]
     pub liquidatee_astrolend_account: AccountLoader<'info, AstrolendAccount>,
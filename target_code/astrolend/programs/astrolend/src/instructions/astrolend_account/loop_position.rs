@@ -0,0 +1,681 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED, MAX_SELF_DELEVERAGE_SLIPPAGE,
+    },
+    events::{
+        AccountEventHeader, LendingAccountBorrowEvent, LendingAccountDepositEvent,
+        LendingAccountRepayEvent, LendingAccountWithdrawEvent,
+    },
+    math_error,
+    prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
+    state::{
+        astrolend_account::{
+            calc_value, BankAccountWithPriceFeed, BankAccountWrapper, AstrolendAccount,
+            RiskEngine, RiskRequirementType, DISABLED_FLAG, FROZEN_FLAG,
+        },
+        astrolend_group::{Bank, BankVaultType},
+        price::OraclePriceType,
+    },
+    utils,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+use super::swap_collateral::swap_with_min_output;
+
+/// Open or increase a leveraged position in one instruction:
+///
+/// 1. Accrue interest on both banks
+/// 2. Borrow `borrow_amount` from the liability bank to the signer's liability token account
+/// 3. Invoke the swap program with `swap_data` and the forwarded accounts
+/// 4. Deposit everything the signer's collateral token account received into the collateral bank,
+///    failing if it is less than `min_deposit_amount`
+/// 5. Verify that the user account is in a healthy state
+///
+/// The health check only runs at the end, so the borrow is effectively flash-borrowed and the
+/// position reaches the leverage that repeated deposit and borrow calls would, as long as it stays
+/// within the initial weights.
+///
+/// The instruction takes no leverage target, the caller sizes `borrow_amount` and builds the swap
+/// for it. With an account of asset value `A` and liability value `L`, reaching leverage
+/// `A' / (A' - L')` of `x` takes a borrow worth `x * (A - L) - A`, before swap slippage. The
+/// initial weights cap the reachable leverage, a larger borrow fails the health check with
+/// `RiskEngineInitRejected` instead of being reduced.
+///
+/// Remaining accounts:
+/// - the collateral bank mint, then the liability bank mint, for Token-2022 banks
+/// - `swap_accounts_count` accounts forwarded to the swap program, signer and writable flags kept
+/// - the oracle accounts used for the health check
+pub fn lending_account_loop<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+    borrow_amount: u64,
+    min_deposit_amount: u64,
+    swap_accounts_count: u8,
+    swap_data: Vec<u8>,
+) -> AstrolendResult {
+    let LendingAccountLoopPosition {
+        astrolend_account: astrolend_account_loader,
+        signer,
+        collateral_bank: collateral_bank_loader,
+        collateral_token_account,
+        collateral_bank_liquidity_vault,
+        collateral_token_program,
+        liability_bank: liability_bank_loader,
+        liability_token_account,
+        liability_bank_liquidity_vault_authority,
+        liability_bank_liquidity_vault,
+        liability_token_program,
+        swap_program,
+        ..
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+    let maybe_collateral_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*collateral_bank_loader.load()?,
+        collateral_token_program.key,
+    )?;
+    let maybe_liability_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*liability_bank_loader.load()?,
+        liability_token_program.key,
+    )?;
+
+    check!(
+        (swap_accounts_count as usize) <= ctx.remaining_accounts.len(),
+        AstrolendError::IllegalAction
+    );
+    let (swap_accounts, health_accounts) = ctx
+        .remaining_accounts
+        .split_at(swap_accounts_count as usize);
+
+    {
+        let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+        check!(
+            !astrolend_account.get_flag(DISABLED_FLAG),
+            AstrolendError::AccountDisabled
+        );
+        check!(
+            !astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        );
+
+        let mut bank = liability_bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            liability_bank_loader.key(),
+        )?;
+
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+
+        let mut bank_account = BankAccountWrapper::find_or_create(
+            &liability_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        // User needs to borrow amount + fee to receive amount
+        let amount_pre_fee = maybe_liability_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_pre_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    borrow_amount,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(borrow_amount);
+
+        bank_account.borrow(I80F48::from_num(amount_pre_fee))?;
+
+        bank_account.withdraw_spl_transfer(
+            amount_pre_fee,
+            liability_bank_liquidity_vault.to_account_info(),
+            liability_token_account.to_account_info(),
+            liability_bank_liquidity_vault_authority.to_account_info(),
+            maybe_liability_bank_mint.as_ref(),
+            liability_token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                liability_bank_loader.key(),
+                liquidity_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountBorrowEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: liability_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_pre_fee,
+        });
+    }
+
+    let swap_output = swap_with_min_output(
+        swap_program,
+        swap_accounts,
+        swap_data,
+        collateral_token_account,
+        min_deposit_amount,
+    )?;
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    {
+        let mut bank = collateral_bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            collateral_bank_loader.key(),
+        )?;
+
+        let mut bank_account = BankAccountWrapper::find_or_create(
+            &collateral_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        // The whole output is transferred, the bank receives it net of the transfer fee
+        let amount_post_fee = maybe_collateral_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_post_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    swap_output,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(swap_output);
+
+        bank_account.deposit(I80F48::from_num(amount_post_fee))?;
+
+        bank_account.deposit_spl_transfer(
+            swap_output,
+            collateral_token_account.to_account_info(),
+            collateral_bank_liquidity_vault.to_account_info(),
+            signer.to_account_info(),
+            maybe_collateral_bank_mint.as_ref(),
+            collateral_token_program.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountDepositEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: collateral_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_post_fee,
+        });
+    }
+
+    // Check account health, if below threshold fail transaction
+    RiskEngine::check_account_init_health(&astrolend_account, health_accounts)?;
+
+    Ok(())
+}
+
+/// Reduce or close a leveraged position in one instruction:
+///
+/// 1. Accrue interest on both banks
+/// 2. Withdraw `withdraw_amount` (everything if `close_position`) from the collateral bank to the
+///    signer's collateral token account
+/// 3. Invoke the swap program with `swap_data` and the forwarded accounts
+/// 4. Repay the liability with everything the signer's liability token account received, failing
+///    if it is less than `min_repay_amount`. The liability is closed once covered, the leftover
+///    stays in the liability token account.
+/// 5. Verify that the user account is in a healthy state
+///
+/// With `close_position` the output must cover the whole liability.
+///
+/// Remaining accounts are laid out as for `lending_account_loop`.
+pub fn lending_account_deleverage<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+    withdraw_amount: u64,
+    min_repay_amount: u64,
+    close_position: bool,
+    swap_accounts_count: u8,
+    swap_data: Vec<u8>,
+) -> AstrolendResult {
+    deleverage_position(
+        ctx,
+        withdraw_amount,
+        min_repay_amount,
+        close_position,
+        swap_accounts_count,
+        swap_data,
+        false,
+    )
+}
+
+/// Same as `lending_account_deleverage`, for accounts that no longer meet the initial
+/// requirement. Instead of the initial health check, the maintenance health of the account must
+/// improve, so users can pay down their debt with their collateral at the swap price rather than
+/// wait to be liquidated at a discount.
+///
+/// The swap output must be worth at least the oracle value of the withdrawn collateral, less
+/// `MAX_SELF_DELEVERAGE_SLIPPAGE`. The health accounts must include both banks.
+pub fn lending_account_self_deleverage<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+    withdraw_amount: u64,
+    min_repay_amount: u64,
+    close_position: bool,
+    swap_accounts_count: u8,
+    swap_data: Vec<u8>,
+) -> AstrolendResult {
+    deleverage_position(
+        ctx,
+        withdraw_amount,
+        min_repay_amount,
+        close_position,
+        swap_accounts_count,
+        swap_data,
+        true,
+    )
+}
+
+fn deleverage_position<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+    withdraw_amount: u64,
+    min_repay_amount: u64,
+    close_position: bool,
+    swap_accounts_count: u8,
+    swap_data: Vec<u8>,
+    self_deleverage: bool,
+) -> AstrolendResult {
+    let LendingAccountLoopPosition {
+        astrolend_account: astrolend_account_loader,
+        signer,
+        collateral_bank: collateral_bank_loader,
+        collateral_token_account,
+        collateral_bank_liquidity_vault_authority,
+        collateral_bank_liquidity_vault,
+        collateral_token_program,
+        liability_bank: liability_bank_loader,
+        liability_token_account,
+        liability_bank_liquidity_vault,
+        liability_token_program,
+        swap_program,
+        ..
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+    let maybe_collateral_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*collateral_bank_loader.load()?,
+        collateral_token_program.key,
+    )?;
+    let maybe_liability_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*liability_bank_loader.load()?,
+        liability_token_program.key,
+    )?;
+
+    check!(
+        (swap_accounts_count as usize) <= ctx.remaining_accounts.len(),
+        AstrolendError::IllegalAction
+    );
+    let (swap_accounts, health_accounts) = ctx
+        .remaining_accounts
+        .split_at(swap_accounts_count as usize);
+
+    let maybe_self_deleverage_check = if self_deleverage {
+        for bank_loader in [&collateral_bank_loader, &liability_bank_loader] {
+            bank_loader.load_mut()?.accrue_interest(
+                clock.unix_timestamp,
+                #[cfg(not(feature = "client"))]
+                bank_loader.key(),
+            )?;
+        }
+
+        let astrolend_account = astrolend_account_loader.load()?;
+        let (assets, liabs) = RiskEngine::new(&astrolend_account, health_accounts)?
+            .get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        let collateral_price = BankAccountWithPriceFeed::load_bank_price(
+            health_accounts,
+            &collateral_bank_loader.key(),
+            OraclePriceType::RealTime,
+            None,
+        )?;
+        let liability_price = BankAccountWithPriceFeed::load_bank_price(
+            health_accounts,
+            &liability_bank_loader.key(),
+            OraclePriceType::RealTime,
+            None,
+        )?;
+
+        Some((
+            assets.checked_sub(liabs).ok_or_else(math_error!())?,
+            collateral_price,
+            liability_price,
+        ))
+    } else {
+        None
+    };
+
+    let withdrawn_amount = {
+        let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+        check!(
+            !astrolend_account.get_flag(DISABLED_FLAG),
+            AstrolendError::AccountDisabled
+        );
+        check!(
+            !astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        );
+
+        let mut bank = collateral_bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            collateral_bank_loader.key(),
+        )?;
+
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+
+        let mut bank_account = BankAccountWrapper::find(
+            &collateral_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        let amount_pre_fee = if close_position {
+            bank_account.withdraw_all()?
+        } else {
+            let amount_pre_fee = maybe_collateral_bank_mint
+                .as_ref()
+                .map(|mint| {
+                    utils::calculate_pre_fee_spl_deposit_amount(
+                        mint.to_account_info(),
+                        withdraw_amount,
+                        clock.epoch,
+                    )
+                })
+                .transpose()?
+                .unwrap_or(withdraw_amount);
+
+            bank_account.withdraw(I80F48::from_num(amount_pre_fee))?;
+
+            amount_pre_fee
+        };
+
+        bank_account.withdraw_spl_transfer(
+            amount_pre_fee,
+            collateral_bank_liquidity_vault.to_account_info(),
+            collateral_token_account.to_account_info(),
+            collateral_bank_liquidity_vault_authority.to_account_info(),
+            maybe_collateral_bank_mint.as_ref(),
+            collateral_token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                collateral_bank_loader.key(),
+                liquidity_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountWithdrawEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: collateral_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_pre_fee,
+            close_balance: close_position,
+        });
+
+        amount_pre_fee
+    };
+
+    let swap_output = swap_with_min_output(
+        swap_program,
+        swap_accounts,
+        swap_data,
+        liability_token_account,
+        min_repay_amount,
+    )?;
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    let swap_output_post_fee = {
+        let mut bank = liability_bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            liability_bank_loader.key(),
+        )?;
+
+        let mut bank_account = BankAccountWrapper::find(
+            &liability_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        let liability_amount = bank_account
+            .bank
+            .get_liability_amount(bank_account.balance.liability_shares.into())?
+            .checked_ceil()
+            .ok_or_else(math_error!())?
+            .checked_to_num::<u64>()
+            .ok_or_else(math_error!())?;
+
+        let output_post_fee = maybe_liability_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_post_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    swap_output,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(swap_output);
+
+        let repay_all = output_post_fee >= liability_amount;
+
+        check!(
+            repay_all || !close_position,
+            AstrolendError::SwapOutputBelowMinimum,
+            "Swap output does not cover the liability"
+        );
+
+        let (repay_amount_post_fee, repay_amount_pre_fee) = if repay_all {
+            let repay_amount_post_fee = bank_account.repay_all()?;
+            let repay_amount_pre_fee = maybe_liability_bank_mint
+                .as_ref()
+                .map(|mint| {
+                    utils::calculate_pre_fee_spl_deposit_amount(
+                        mint.to_account_info(),
+                        repay_amount_post_fee,
+                        clock.epoch,
+                    )
+                })
+                .transpose()?
+                .unwrap_or(repay_amount_post_fee);
+
+            (repay_amount_post_fee, repay_amount_pre_fee)
+        } else {
+            bank_account.repay(I80F48::from_num(output_post_fee))?;
+
+            (output_post_fee, swap_output)
+        };
+
+        bank_account.deposit_spl_transfer(
+            repay_amount_pre_fee,
+            liability_token_account.to_account_info(),
+            liability_bank_liquidity_vault.to_account_info(),
+            signer.to_account_info(),
+            maybe_liability_bank_mint.as_ref(),
+            liability_token_program.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountRepayEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: liability_bank_loader.key(),
+            mint: bank.mint,
+            amount: repay_amount_post_fee,
+            close_balance: repay_all,
+        });
+
+        output_post_fee
+    };
+
+    match maybe_self_deleverage_check {
+        Some((pre_health, collateral_price, liability_price)) => {
+            let withdrawn_value = calc_value(
+                I80F48::from_num(withdrawn_amount),
+                collateral_price,
+                collateral_bank_loader.load()?.mint_decimals,
+                None,
+            )?;
+            let swap_output_value = calc_value(
+                I80F48::from_num(swap_output_post_fee),
+                liability_price,
+                liability_bank_loader.load()?.mint_decimals,
+                None,
+            )?;
+
+            check!(
+                swap_output_value
+                    >= withdrawn_value
+                        .checked_mul(I80F48::ONE - MAX_SELF_DELEVERAGE_SLIPPAGE)
+                        .ok_or_else(math_error!())?,
+                AstrolendError::SwapOutputBelowMinimum,
+                "Swap output is below the oracle value of the withdrawn collateral"
+            );
+
+            RiskEngine::new(&astrolend_account, health_accounts)?
+                .check_account_health_improved(pre_health)?;
+        }
+        // Check account health, if below threshold fail transaction
+        None => RiskEngine::check_account_init_health(&astrolend_account, health_accounts)?,
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountLoopPosition<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = collateral_bank.load()?.group == astrolend_group.key(),
+    )]
+    pub collateral_bank: AccountLoader<'info, Bank>,
+
+    /// Swap output when looping, swap input when deleveraging, owned by the signer
+    #[account(
+        mut,
+        token::authority = signer,
+    )]
+    pub collateral_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            collateral_bank.key().as_ref(),
+        ],
+        bump = collateral_bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub collateral_bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            collateral_bank.key().as_ref(),
+        ],
+        bump = collateral_bank.load()?.liquidity_vault_bump,
+    )]
+    pub collateral_bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+
+    #[account(
+        mut,
+        constraint = liability_bank.load()?.group == astrolend_group.key(),
+        constraint = liability_bank.key() != collateral_bank.key() @ AstrolendError::IllegalAction,
+    )]
+    pub liability_bank: AccountLoader<'info, Bank>,
+
+    /// Swap input when looping, swap output when deleveraging, owned by the signer
+    #[account(
+        mut,
+        token::authority = signer,
+    )]
+    pub liability_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            liability_bank.key().as_ref(),
+        ],
+        bump = liability_bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub liability_bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            liability_bank.key().as_ref(),
+        ],
+        bump = liability_bank.load()?.liquidity_vault_bump,
+    )]
+    pub liability_bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub liability_token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Allow-listed by the group
+    #[account(
+        executable,
+        constraint = astrolend_group.load()?.is_swap_program_allowed(swap_program.key)
+            @ AstrolendError::SwapProgramNotAllowed,
+    )]
+    pub swap_program: AccountInfo<'info>,
+}
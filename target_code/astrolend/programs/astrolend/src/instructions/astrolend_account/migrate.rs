@@ -0,0 +1,71 @@
+use crate::{
+    check,
+    prelude::*,
+    state::astrolend_account::{AstrolendAccount, LEGACY_ASTROLEND_ACCOUNT_SIZE},
+};
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+    Discriminator,
+};
+use solana_program::sysvar::Sysvar;
+
+/// Reallocate an account created before the lending account was expanded and move its fields to
+/// the current layout, see `AstrolendAccount::migrate_legacy_layout`.
+///
+/// Permissionless, the layout change doesn't affect the account state. The fee payer funds the
+/// additional rent.
+pub fn migrate_account(ctx: Context<AstrolendAccountMigrate>) -> AstrolendResult {
+    let AstrolendAccountMigrate {
+        astrolend_account,
+        fee_payer,
+        system_program,
+    } = ctx.accounts;
+
+    check!(
+        astrolend_account.data_len() == 8 + LEGACY_ASTROLEND_ACCOUNT_SIZE,
+        AstrolendError::IllegalAction,
+        "Account is not in the legacy layout"
+    );
+    check!(
+        astrolend_account.try_borrow_data()?[..8] == AstrolendAccount::DISCRIMINATOR,
+        AstrolendError::IllegalAction
+    );
+
+    let new_len = 8 + std::mem::size_of::<AstrolendAccount>();
+    let missing_rent = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(astrolend_account.lamports());
+
+    if missing_rent > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: fee_payer.to_account_info(),
+                    to: astrolend_account.to_account_info(),
+                },
+            ),
+            missing_rent,
+        )?;
+    }
+
+    AstrolendAccount::migrate_if_legacy(astrolend_account)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountMigrate<'info> {
+    /// CHECK: Legacy layout, can't be loaded as `AstrolendAccount`, checked in the handler
+    #[account(
+        mut,
+        owner = crate::ID,
+    )]
+    pub astrolend_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -1,23 +1,39 @@
 mod borrow;
 mod close;
 mod close_balance;
+mod delegate;
+mod deleverage_order;
 mod deposit;
 mod emissions;
 mod flashloan;
+mod flashloan_callback;
+mod freeze;
 mod initialize;
 mod liquidate;
+mod loop_position;
+mod migrate;
 mod repay;
+mod swap_collateral;
 mod transfer_authority;
+mod transfer_balance;
 mod withdraw;
 
 pub use borrow::*;
 pub use close::*;
 pub use close_balance::*;
+pub use delegate::*;
+pub use deleverage_order::*;
 pub use deposit::*;
 pub use emissions::*;
 pub use flashloan::*;
+pub use flashloan_callback::*;
+pub use freeze::*;
 pub use initialize::*;
 pub use liquidate::*;
+pub use loop_position::*;
+pub use migrate::*;
 pub use repay::*;
+pub use swap_collateral::*;
 pub use transfer_authority::*;
+pub use transfer_balance::*;
 pub use withdraw::*;
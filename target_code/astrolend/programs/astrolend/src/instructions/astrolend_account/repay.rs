@@ -2,9 +2,13 @@ use crate::{
     check,
     constants::LIQUIDITY_VAULT_SEED,
     events::{AccountEventHeader, LendingAccountRepayEvent},
+    math_error,
     prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, DISABLED_FLAG},
+        account_delegate::{self, DELEGATE_DEPOSIT_REPAY_PERMISSION},
+        astrolend_account::{
+            BankAccountWrapper, AstrolendAccount, DISABLED_FLAG, IN_FLASHLOAN_FLAG,
+        },
         astrolend_group::Bank,
     },
     utils,
@@ -35,6 +39,14 @@ pub fn lending_account_repay<'info>(
         ..
     } = ctx.accounts;
     let clock = Clock::get()?;
+    let authority = astrolend_account_loader.load()?.authority;
+    account_delegate::check_signer_permission(
+        &mut ctx.remaining_accounts,
+        &astrolend_account_loader.key(),
+        &authority,
+        signer.key,
+        DELEGATE_DEPOSIT_REPAY_PERMISSION,
+    )?;
     let maybe_bank_mint = utils::maybe_take_bank_mint(
         &mut ctx.remaining_accounts,
         &*bank_loader.load()?,
@@ -50,17 +62,18 @@ pub fn lending_account_repay<'info>(
         AstrolendError::AccountDisabled
     );
 
+    let in_flashloan = astrolend_account.get_flag(IN_FLASHLOAN_FLAG);
+
     bank.accrue_interest(
         clock.unix_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
 
-    let mut bank_account = BankAccountWrapper::find(
-        &bank_loader.key(),
-        &mut bank,
-        &mut astrolend_account.lending_account,
-    )?;
+    let mut bank_account =
+        BankAccountWrapper::find(&bank_loader.key(), &mut bank, &mut astrolend_account)?;
+
+    let flashloan_borrowed = bank_account.balance.flashloan_borrowed;
 
     let repay_amount_post_fee = if repay_all {
         bank_account.repay_all()?
@@ -70,17 +83,27 @@ pub fn lending_account_repay<'info>(
         amount
     };
 
+    let flashloan_fee = if in_flashloan {
+        bank_account.charge_flashloan_fee(flashloan_borrowed, repay_amount_post_fee)?
+    } else {
+        0
+    };
+
+    let transfer_amount_post_fee = repay_amount_post_fee
+        .checked_add(flashloan_fee)
+        .ok_or_else(math_error!())?;
+
     let repay_amount_pre_fee = maybe_bank_mint
         .as_ref()
         .map(|mint| {
             utils::calculate_pre_fee_spl_deposit_amount(
                 mint.to_account_info(),
-                repay_amount_post_fee,
+                transfer_amount_post_fee,
                 clock.epoch,
             )
         })
         .transpose()?
-        .unwrap_or(repay_amount_post_fee);
+        .unwrap_or(transfer_amount_post_fee);
 
     bank_account.deposit_spl_transfer(
         repay_amount_pre_fee,
@@ -114,13 +137,12 @@ pub struct LendingAccountRepay<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
-    #[account(
-        address = astrolend_account.load()?.authority,
-    )]
+    /// The account authority, or a delegate passing its record as first remaining account
     pub signer: Signer<'info>,
 
     #[account(
@@ -0,0 +1,323 @@
+use crate::{
+    bank_signer, check,
+    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
+    debug,
+    events::{AccountEventHeader, LendingAccountDepositEvent, LendingAccountWithdrawEvent},
+    prelude::{AstrolendError, AstrolendGroup, AstrolendResult},
+    state::{
+        astrolend_account::{
+            BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG, FROZEN_FLAG,
+        },
+        astrolend_group::{Bank, BankVaultType},
+    },
+    utils,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// 1. Accrue interest on both banks
+/// 2. Withdraw `withdraw_amount` from the withdraw bank to the signer's withdraw token account
+/// 3. Invoke the swap program with `swap_data` and the forwarded accounts
+/// 4. Deposit everything the signer's deposit token account received into the deposit bank,
+///    failing if it is less than `min_deposit_amount`
+/// 5. Verify that the user account is in a healthy state
+///
+/// Remaining accounts:
+/// - the withdraw bank mint, then the deposit bank mint, for Token-2022 banks
+/// - `swap_accounts_count` accounts forwarded to the swap program, signer and writable flags kept
+/// - the oracle accounts used for the health check
+///
+/// Will error if there is no existing asset in the withdraw bank. An existing liability in the
+/// deposit bank is repaid first, only the rest of the swap output is deposited.
+pub fn lending_account_swap_collateral<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingAccountSwapCollateral<'info>>,
+    withdraw_amount: u64,
+    min_deposit_amount: u64,
+    swap_accounts_count: u8,
+    swap_data: Vec<u8>,
+) -> AstrolendResult {
+    let LendingAccountSwapCollateral {
+        astrolend_account: astrolend_account_loader,
+        signer,
+        withdraw_bank: withdraw_bank_loader,
+        withdraw_token_account,
+        withdraw_bank_liquidity_vault_authority,
+        withdraw_bank_liquidity_vault,
+        withdraw_token_program,
+        deposit_bank: deposit_bank_loader,
+        deposit_token_account,
+        deposit_bank_liquidity_vault,
+        deposit_token_program,
+        swap_program,
+        ..
+    } = ctx.accounts;
+    let clock = Clock::get()?;
+    let maybe_withdraw_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*withdraw_bank_loader.load()?,
+        withdraw_token_program.key,
+    )?;
+    let maybe_deposit_bank_mint = utils::maybe_take_bank_mint(
+        &mut ctx.remaining_accounts,
+        &*deposit_bank_loader.load()?,
+        deposit_token_program.key,
+    )?;
+
+    check!(
+        (swap_accounts_count as usize) <= ctx.remaining_accounts.len(),
+        AstrolendError::IllegalAction
+    );
+    let (swap_accounts, health_accounts) = ctx
+        .remaining_accounts
+        .split_at(swap_accounts_count as usize);
+
+    {
+        let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+        check!(
+            !astrolend_account.get_flag(DISABLED_FLAG),
+            AstrolendError::AccountDisabled
+        );
+        check!(
+            !astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        );
+
+        let mut bank = withdraw_bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            withdraw_bank_loader.key(),
+        )?;
+
+        let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
+
+        let mut bank_account = BankAccountWrapper::find(
+            &withdraw_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        let amount_pre_fee = maybe_withdraw_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_pre_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    withdraw_amount,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(withdraw_amount);
+
+        bank_account.withdraw(I80F48::from_num(amount_pre_fee))?;
+
+        bank_account.withdraw_spl_transfer(
+            amount_pre_fee,
+            withdraw_bank_liquidity_vault.to_account_info(),
+            withdraw_token_account.to_account_info(),
+            withdraw_bank_liquidity_vault_authority.to_account_info(),
+            maybe_withdraw_bank_mint.as_ref(),
+            withdraw_token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                withdraw_bank_loader.key(),
+                liquidity_vault_authority_bump
+            ),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountWithdrawEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: withdraw_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_pre_fee,
+            close_balance: false,
+        });
+    }
+
+    let swap_output = swap_with_min_output(
+        swap_program,
+        swap_accounts,
+        swap_data,
+        deposit_token_account,
+        min_deposit_amount,
+    )?;
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    {
+        let mut bank = deposit_bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            deposit_bank_loader.key(),
+        )?;
+
+        let mut bank_account = BankAccountWrapper::find_or_create(
+            &deposit_bank_loader.key(),
+            &mut bank,
+            &mut astrolend_account,
+        )?;
+
+        // The whole output is transferred, the bank receives it net of the transfer fee
+        let amount_post_fee = maybe_deposit_bank_mint
+            .as_ref()
+            .map(|mint| {
+                utils::calculate_post_fee_spl_deposit_amount(
+                    mint.to_account_info(),
+                    swap_output,
+                    clock.epoch,
+                )
+            })
+            .transpose()?
+            .unwrap_or(swap_output);
+
+        bank_account.deposit(I80F48::from_num(amount_post_fee))?;
+
+        bank_account.deposit_spl_transfer(
+            swap_output,
+            deposit_token_account.to_account_info(),
+            deposit_bank_liquidity_vault.to_account_info(),
+            signer.to_account_info(),
+            maybe_deposit_bank_mint.as_ref(),
+            deposit_token_program.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+
+        emit!(LendingAccountDepositEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: deposit_bank_loader.key(),
+            mint: bank.mint,
+            amount: amount_post_fee,
+        });
+    }
+
+    // Check account health, if below threshold fail transaction
+    RiskEngine::check_account_init_health(&astrolend_account, health_accounts)?;
+
+    Ok(())
+}
+
+/// Invoke the swap program and return the amount `output_token_account` received, failing if it
+/// is less than `min_output`.
+pub(crate) fn swap_with_min_output<'info>(
+    swap_program: &AccountInfo<'info>,
+    swap_accounts: &[AccountInfo<'info>],
+    swap_data: Vec<u8>,
+    output_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    min_output: u64,
+) -> AstrolendResult<u64> {
+    output_token_account.reload()?;
+    let balance_before = output_token_account.amount;
+
+    utils::invoke_with_forwarded_accounts(swap_program, swap_accounts, swap_data)?;
+
+    output_token_account.reload()?;
+    let output = output_token_account.amount.saturating_sub(balance_before);
+
+    debug!("Swap output {} (min {})", output, min_output);
+
+    check!(output >= min_output, AstrolendError::SwapOutputBelowMinimum);
+
+    Ok(output)
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountSwapCollateral<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = withdraw_bank.load()?.group == astrolend_group.key(),
+    )]
+    pub withdraw_bank: AccountLoader<'info, Bank>,
+
+    /// Receives the withdrawn tokens, which are the swap input
+    #[account(mut)]
+    pub withdraw_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            withdraw_bank.key().as_ref(),
+        ],
+        bump = withdraw_bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub withdraw_bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            withdraw_bank.key().as_ref(),
+        ],
+        bump = withdraw_bank.load()?.liquidity_vault_bump,
+    )]
+    pub withdraw_bank_liquidity_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub withdraw_token_program: Interface<'info, TokenInterface>,
+
+    #[account(
+        mut,
+        constraint = deposit_bank.load()?.group == astrolend_group.key(),
+        constraint = deposit_bank.key() != withdraw_bank.key() @ AstrolendError::IllegalAction,
+    )]
+    pub deposit_bank: AccountLoader<'info, Bank>,
+
+    /// Receives the swap output, deposited from it, owned by the signer
+    #[account(
+        mut,
+        token::authority = signer,
+    )]
+    pub deposit_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            deposit_bank.key().as_ref(),
+        ],
+        bump = deposit_bank.load()?.liquidity_vault_bump,
+    )]
+    pub deposit_bank_liquidity_vault: AccountInfo<'info>,
+
+    pub deposit_token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Allow-listed by the group
+    #[account(
+        executable,
+        constraint = astrolend_group.load()?.is_swap_program_allowed(swap_program.key)
+            @ AstrolendError::SwapProgramNotAllowed,
+    )]
+    pub swap_program: AccountInfo<'info>,
+}
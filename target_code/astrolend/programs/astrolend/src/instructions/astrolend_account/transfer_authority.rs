@@ -1,24 +1,40 @@
-use crate::{prelude::*, state::astrolend_account::AstrolendAccount};
+use crate::{
+    events::{AccountEventHeader, AstrolendAccountTransferAccountAuthorityEvent},
+    prelude::*,
+    state::astrolend_account::AstrolendAccount,
+};
 use anchor_lang::prelude::*;
 
 pub fn set_account_transfer_authority(
     ctx: Context<AstrolendAccountSetAccountAuthority>,
 ) -> AstrolendResult {
     // Ensure astrolend_account is dropped out of scope to not exceed stack frame limits
-    {
+    let (old_account_authority, astrolend_group) = {
         let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+        let old_account_authority = astrolend_account.authority;
         let new_account_authority = ctx.accounts.new_authority.key();
         astrolend_account.set_new_account_authority_checked(new_account_authority)?;
-    }
 
-    // TODO: add back event (dropped for memory reasons)
+        (old_account_authority, astrolend_account.group)
+    };
+
+    emit_transfer_account_authority_event(
+        ctx.accounts.astrolend_account.key(),
+        ctx.accounts.signer.key(),
+        astrolend_group,
+        old_account_authority,
+        ctx.accounts.new_authority.key(),
+    );
 
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct AstrolendAccountSetAccountAuthority<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
     /// CHECK: The group is confirmed by the address macro
@@ -38,3 +54,116 @@ pub struct AstrolendAccountSetAccountAuthority<'info> {
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 }
+
+/// Offer the account to `new_authority`, replacing any pending offer.
+/// No admin approval is needed, the transfer completes once `new_authority` accepts it.
+pub fn astrolend_account_offer_authority_transfer(
+    ctx: Context<AstrolendAccountOfferAuthorityTransfer>,
+) -> AstrolendResult {
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    astrolend_account.offer_authority_transfer(ctx.accounts.new_authority.key())?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountOfferAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+
+    /// CHECK: The new account authority has to sign to accept the offer
+    pub new_authority: AccountInfo<'info>,
+}
+
+/// Withdraw a pending authority transfer offer.
+pub fn astrolend_account_cancel_authority_transfer(
+    ctx: Context<AstrolendAccountCancelAuthorityTransfer>,
+) -> AstrolendResult {
+    let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+
+    astrolend_account.cancel_authority_transfer();
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountCancelAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.authority,
+    )]
+    pub authority: Signer<'info>,
+}
+
+/// Take over an account offered with `astrolend_account_offer_authority_transfer`.
+pub fn astrolend_account_accept_authority_transfer(
+    ctx: Context<AstrolendAccountAcceptAuthorityTransfer>,
+) -> AstrolendResult {
+    let new_account_authority = ctx.accounts.new_authority.key();
+
+    // Ensure astrolend_account is dropped out of scope to not exceed stack frame limits
+    let (old_account_authority, astrolend_group) = {
+        let mut astrolend_account = ctx.accounts.astrolend_account.load_mut()?;
+        let old_account_authority =
+            astrolend_account.accept_authority_transfer(new_account_authority)?;
+
+        (old_account_authority, astrolend_account.group)
+    };
+
+    emit_transfer_account_authority_event(
+        ctx.accounts.astrolend_account.key(),
+        new_account_authority,
+        astrolend_group,
+        old_account_authority,
+        new_account_authority,
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AstrolendAccountAcceptAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = astrolend_account.load()?.pending_authority,
+    )]
+    pub new_authority: Signer<'info>,
+}
+
+fn emit_transfer_account_authority_event(
+    astrolend_account: Pubkey,
+    signer: Pubkey,
+    astrolend_group: Pubkey,
+    old_account_authority: Pubkey,
+    new_account_authority: Pubkey,
+) {
+    emit!(AstrolendAccountTransferAccountAuthorityEvent {
+        header: AccountEventHeader {
+            signer: Some(signer),
+            astrolend_account,
+            astrolend_account_authority: new_account_authority,
+            astrolend_group,
+        },
+        old_account_authority,
+        new_account_authority,
+    });
+}
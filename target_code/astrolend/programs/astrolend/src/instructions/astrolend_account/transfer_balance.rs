@@ -0,0 +1,200 @@
+use crate::{
+    check,
+    events::{AccountEventHeader, LendingAccountTransferBalanceEvent},
+    prelude::*,
+    state::{
+        astrolend_account::{
+            BalanceSide, BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG,
+            FROZEN_FLAG, IN_FLASHLOAN_FLAG,
+        },
+        astrolend_group::Bank,
+    },
+};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Move a balance of `bank` to another account of the same authority, only the shares move and
+/// no tokens are transferred.
+///
+/// The side of the source balance is moved: assets are withdrawn from the source and deposited
+/// into the destination, or a liability is repaid on the source and borrowed by the destination.
+/// The authority signs for both accounts, which is the destination's consent to take over a
+/// liability. With `transfer_all` the whole balance is moved and the source balance is closed.
+///
+/// Neither account can be in a flashloan, flashloan liabilities are only repaid with `repay`,
+/// which charges the flashloan fee.
+///
+/// Remaining accounts: the health accounts of the source account followed by the ones of the
+/// destination account, both for their balances after the transfer.
+pub fn lending_account_transfer_balance<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingAccountTransferBalance<'info>>,
+    amount: u64,
+    transfer_all: Option<bool>,
+) -> AstrolendResult {
+    let LendingAccountTransferBalance {
+        source_astrolend_account: source_loader,
+        destination_astrolend_account: destination_loader,
+        signer,
+        bank: bank_loader,
+        ..
+    } = ctx.accounts;
+
+    let transfer_all = transfer_all.unwrap_or(false);
+    let mut source_astrolend_account = source_loader.load_mut()?;
+    let mut destination_astrolend_account = destination_loader.load_mut()?;
+
+    check!(
+        !source_astrolend_account.get_flag(DISABLED_FLAG)
+            && !destination_astrolend_account.get_flag(DISABLED_FLAG),
+        AstrolendError::AccountDisabled
+    );
+
+    check!(
+        !source_astrolend_account.get_flag(IN_FLASHLOAN_FLAG)
+            && !destination_astrolend_account.get_flag(IN_FLASHLOAN_FLAG),
+        AstrolendError::AccountInFlashloan
+    );
+
+    let side = source_astrolend_account
+        .lending_account
+        .get_balance(&bank_loader.key())
+        .and_then(|balance| balance.get_side())
+        .ok_or(AstrolendError::LendingAccountBalanceNotFound)?;
+
+    // Moving assets out is a withdrawal for the source, taking over a liability is a borrow for
+    // the destination
+    match side {
+        BalanceSide::Assets => check!(
+            !source_astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        ),
+        BalanceSide::Liabilities => check!(
+            !destination_astrolend_account.get_flag(FROZEN_FLAG),
+            AstrolendError::AccountFrozen
+        ),
+    }
+
+    {
+        let mut bank = bank_loader.load_mut()?;
+
+        bank.accrue_interest(
+            Clock::get()?.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            bank_loader.key(),
+        )?;
+
+        let transfer_amount = {
+            let mut source_bank_account = BankAccountWrapper::find(
+                &bank_loader.key(),
+                &mut bank,
+                &mut source_astrolend_account,
+            )?;
+
+            let transfer_amount = if transfer_all {
+                match side {
+                    BalanceSide::Assets => source_bank_account
+                        .bank
+                        .get_asset_amount(source_bank_account.balance.asset_shares.into())?,
+                    BalanceSide::Liabilities => source_bank_account.bank.get_liability_amount(
+                        source_bank_account.balance.liability_shares.into(),
+                    )?,
+                }
+            } else {
+                I80F48::from_num(amount)
+            };
+
+            match side {
+                BalanceSide::Assets => source_bank_account.withdraw_nominal(transfer_amount)?,
+                BalanceSide::Liabilities => source_bank_account.repay(transfer_amount)?,
+            }
+
+            if transfer_all {
+                source_bank_account.close_balance()?;
+            }
+
+            transfer_amount
+        };
+
+        // The bank totals are unchanged, so deposit and borrow limits don't apply
+        let mut destination_bank_account = BankAccountWrapper::find_or_create(
+            &bank_loader.key(),
+            &mut bank,
+            &mut destination_astrolend_account,
+        )?;
+
+        match side {
+            BalanceSide::Assets => {
+                destination_bank_account.increase_balance_in_liquidation(transfer_amount)?
+            }
+            BalanceSide::Liabilities => {
+                destination_bank_account.decrease_balance_in_liquidation(transfer_amount)?
+            }
+        }
+
+        emit!(LendingAccountTransferBalanceEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: source_loader.key(),
+                astrolend_account_authority: source_astrolend_account.authority,
+                astrolend_group: source_astrolend_account.group,
+            },
+            destination_astrolend_account: destination_loader.key(),
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            amount: transfer_amount.to_num::<f64>(),
+            liability: matches!(side, BalanceSide::Liabilities),
+            close_balance: transfer_all,
+        });
+    }
+
+    let source_remaining_len = source_astrolend_account.get_remaining_accounts_len();
+    check!(
+        ctx.remaining_accounts.len() >= source_remaining_len,
+        AstrolendError::MissingPythOrBankAccount
+    );
+    let (source_remaining_accounts, destination_remaining_accounts) =
+        ctx.remaining_accounts.split_at(source_remaining_len);
+
+    RiskEngine::check_account_init_health(&source_astrolend_account, source_remaining_accounts)?;
+    RiskEngine::check_account_init_health(
+        &destination_astrolend_account,
+        destination_remaining_accounts,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountTransferBalance<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(source_astrolend_account.as_ref())?,
+        constraint = source_astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub source_astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(destination_astrolend_account.as_ref())?,
+        constraint = destination_astrolend_account.key() != source_astrolend_account.key()
+            @ AstrolendError::IllegalAction,
+        constraint = destination_astrolend_account.load()?.group == astrolend_group.key(),
+        constraint = destination_astrolend_account.load()?.authority
+            == source_astrolend_account.load()?.authority @ AstrolendError::Unauthorized,
+    )]
+    pub destination_astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    #[account(
+        address = source_astrolend_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+}
@@ -4,7 +4,10 @@ use crate::{
     events::{AccountEventHeader, LendingAccountWithdrawEvent},
     prelude::*,
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG},
+        account_delegate::{self, DELEGATE_WITHDRAW_PERMISSION},
+        astrolend_account::{
+            BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG, FROZEN_FLAG,
+        },
         astrolend_group::{Bank, BankVaultType},
     },
     utils,
@@ -28,6 +31,7 @@ pub fn lending_account_withdraw<'info>(
 ) -> AstrolendResult {
     let LendingAccountWithdraw {
         astrolend_account: astrolend_account_loader,
+        signer,
         destination_token_account,
         bank_liquidity_vault,
         token_program,
@@ -36,6 +40,22 @@ pub fn lending_account_withdraw<'info>(
         ..
     } = ctx.accounts;
     let clock = Clock::get()?;
+    let authority = astrolend_account_loader.load()?.authority;
+    let is_delegate = account_delegate::check_signer_permission(
+        &mut ctx.remaining_accounts,
+        &astrolend_account_loader.key(),
+        &authority,
+        signer.key,
+        DELEGATE_WITHDRAW_PERMISSION,
+    )?;
+
+    // Delegates can only withdraw to the authority
+    if is_delegate {
+        check!(
+            destination_token_account.owner == authority,
+            AstrolendError::Unauthorized
+        );
+    }
 
     let withdraw_all = withdraw_all.unwrap_or(false);
     let mut astrolend_account = astrolend_account_loader.load_mut()?;
@@ -44,6 +64,10 @@ pub fn lending_account_withdraw<'info>(
         !astrolend_account.get_flag(DISABLED_FLAG),
         AstrolendError::AccountDisabled
     );
+    check!(
+        !astrolend_account.get_flag(FROZEN_FLAG),
+        AstrolendError::AccountFrozen
+    );
 
     let maybe_bank_mint = utils::maybe_take_bank_mint(
         &mut ctx.remaining_accounts,
@@ -62,11 +86,8 @@ pub fn lending_account_withdraw<'info>(
 
         let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
 
-        let mut bank_account = BankAccountWrapper::find(
-            &bank_loader.key(),
-            &mut bank,
-            &mut astrolend_account.lending_account,
-        )?;
+        let mut bank_account =
+            BankAccountWrapper::find(&bank_loader.key(), &mut bank, &mut astrolend_account)?;
 
         let amount_pre_fee = if withdraw_all {
             bank_account.withdraw_all()?
@@ -105,7 +126,7 @@ pub fn lending_account_withdraw<'info>(
 
         emit!(LendingAccountWithdrawEvent {
             header: AccountEventHeader {
-                signer: Some(ctx.accounts.signer.key()),
+                signer: Some(signer.key()),
                 astrolend_account: astrolend_account_loader.key(),
                 astrolend_account_authority: astrolend_account.authority,
                 astrolend_group: astrolend_account.group,
@@ -130,13 +151,12 @@ pub struct LendingAccountWithdraw<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 
-    #[account(
-        address = astrolend_account.load()?.authority,
-    )]
+    /// The account authority, or a delegate passing its record as first remaining account
     pub signer: Signer<'info>,
 
     #[account(
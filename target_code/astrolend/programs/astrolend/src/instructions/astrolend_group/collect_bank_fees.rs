@@ -2,11 +2,12 @@ use crate::constants::{FEE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_AUTHORITY_SEED}
 use crate::events::{GroupEventHeader, LendingPoolBankCollectFeesEvent};
 use crate::utils;
 use crate::{
-    bank_signer,
+    bank_signer, check,
     constants::{
         FEE_VAULT_SEED, INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
     },
     math_error,
+    prelude::AstrolendError,
     state::astrolend_group::{Bank, BankVaultType, AstrolendGroup},
     AstrolendResult,
 };
@@ -268,6 +269,13 @@ pub fn lending_pool_withdraw_insurance<'info>(
     } = ctx.accounts;
 
     let bank = bank_loader.load()?;
+
+    // Once stakers own the insurance fund, it can only be drained through bankruptcies.
+    check!(
+        bank.insurance_share_mint == Pubkey::default(),
+        AstrolendError::InsuranceStakingActive
+    );
+
     let maybe_bank_mint =
         utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
 
@@ -70,12 +70,15 @@ pub fn set_account_flag(ctx: Context<SetAccountFlag>, flag: u64) -> AstrolendRes
 
 #[derive(Accounts)]
 pub struct SetAccountFlag<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
     #[account(address = astrolend_account.load()?.group)]
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
-    #[account(mut)]
-    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
-
     /// Admin only
     #[account(address = astrolend_group.load()?.admin)]
     pub admin: Signer<'info>,
@@ -93,12 +96,15 @@ pub fn unset_account_flag(ctx: Context<UnsetAccountFlag>, flag: u64) -> Astrolen
 
 #[derive(Accounts)]
 pub struct UnsetAccountFlag<'info> {
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
     #[account(address = astrolend_account.load()?.group)]
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
-    #[account(mut)]
-    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
-
     /// Admin only
     #[account(address = astrolend_group.load()?.admin)]
     pub admin: Signer<'info>,
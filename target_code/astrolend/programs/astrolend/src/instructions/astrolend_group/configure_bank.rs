@@ -1,9 +1,21 @@
-use crate::constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED};
-use crate::events::{GroupEventHeader, LendingPoolBankConfigureEvent};
+use crate::constants::{
+    EMISSIONS_AUTH_SEED, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
+    EMISSIONS_FLAG_PRO_RATA, EMISSIONS_TOKEN_ACCOUNT_SEED, EMISSION_FLAGS,
+    MIN_EMISSIONS_START_TIME,
+};
+use crate::events::{
+    GroupEventHeader, LendingPoolBankConfigureEvent, LendingPoolBankEmissionsClawbackEvent,
+};
 use crate::prelude::AstrolendError;
 use crate::{check, math_error, utils};
 use crate::{
-    state::astrolend_group::{Bank, BankConfigOpt, AstrolendGroup},
+    state::{
+        astrolend_account::calc_emissions,
+        astrolend_group::{
+            Bank, BankConfigOpt, EmissionsIndex, EmissionsSchedule, EmissionsScheduleConfig,
+            EmissionsStream, AstrolendGroup,
+        },
+    },
     AstrolendResult,
 };
 use anchor_lang::prelude::*;
@@ -17,6 +29,13 @@ pub fn lending_pool_configure_bank(
 ) -> AstrolendResult {
     let mut bank = ctx.accounts.bank.load_mut()?;
 
+    // With a bank config delay set, only risk reducing changes skip the timelock.
+    let bank_config_delay = ctx.accounts.astrolend_group.load()?.bank_config_delay;
+    check!(
+        bank_config_delay == 0 || bank_config.is_risk_reducing(&bank),
+        AstrolendError::BankConfigTimelockRequired
+    );
+
     bank.configure(&bank_config)?;
 
     if bank_config.oracle.is_some() {
@@ -52,6 +71,13 @@ pub struct LendingPoolConfigureBank<'info> {
     pub bank: AccountLoader<'info, Bank>,
 }
 
+/// Setup an emissions stream paying out `emissions_mint`.
+///
+/// The primary stream is setup first, every later call sets up one of the additional streams of
+/// the bank, up to `MAX_EMISSIONS_STREAMS` streams with different mints.
+///
+/// With `EMISSIONS_FLAG_PRO_RATA`, `emissions_rate` is a number of tokens per second shared by
+/// all shares of the emitting side. The mode of a stream can't be changed after setup.
 pub fn lending_pool_setup_emissions(
     ctx: Context<LendingPoolSetupEmissions>,
     emissions_flags: u64,
@@ -59,18 +85,52 @@ pub fn lending_pool_setup_emissions(
     total_emissions: u64,
 ) -> AstrolendResult {
     let mut bank = ctx.accounts.bank.load_mut()?;
+    let emissions_mint = ctx.accounts.emissions_mint.key();
+
+    let current_timestamp = Clock::get()?.unix_timestamp;
 
     check!(
-        bank.emissions_mint.eq(&Pubkey::default()),
+        !bank.is_emissions_mint(&emissions_mint),
         AstrolendError::EmissionsAlreadySetup
     );
 
-    bank.emissions_mint = ctx.accounts.emissions_mint.key();
-
-    bank.override_emissions_flag(emissions_flags);
+    check!(
+        Bank::verify_emissions_flags(emissions_flags),
+        AstrolendError::EmissionsUpdateError
+    );
 
-    bank.emissions_rate = emissions_rate;
-    bank.emissions_remaining = I80F48::from_num(total_emissions).into();
+    if bank.emissions_mint.eq(&Pubkey::default()) {
+        bank.emissions_mint = emissions_mint;
+
+        bank.override_emissions_flag(emissions_flags);
+
+        bank.emissions_rate = emissions_rate;
+        bank.emissions_remaining = I80F48::from_num(total_emissions).into();
+        bank.emissions_index = EmissionsIndex {
+            last_update: current_timestamp,
+            ..Default::default()
+        };
+        bank.emissions_setup_time = current_timestamp;
+    } else {
+        let stream = bank
+            .emissions_streams
+            .iter_mut()
+            .find(|stream| !stream.is_active())
+            .ok_or_else(|| error!(AstrolendError::EmissionsAlreadySetup))?;
+
+        *stream = EmissionsStream {
+            mint: emissions_mint,
+            flags: emissions_flags,
+            rate: emissions_rate,
+            remaining: I80F48::from_num(total_emissions).into(),
+            index: EmissionsIndex {
+                last_update: current_timestamp,
+                ..Default::default()
+            },
+            setup_time: current_timestamp,
+            ..Default::default()
+        };
+    }
 
     let initial_emissions_amount_pre_fee = utils::calculate_pre_fee_spl_deposit_amount(
         ctx.accounts.emissions_mint.to_account_info(),
@@ -153,29 +213,62 @@ pub fn lending_pool_update_emissions_parameters(
     additional_emissions: Option<u64>,
 ) -> AstrolendResult {
     let mut bank = ctx.accounts.bank.load_mut()?;
+    let emissions_mint = ctx.accounts.emissions_mint.key();
 
     check!(
-        bank.emissions_mint.ne(&Pubkey::default()),
-        AstrolendError::EmissionsUpdateError
-    );
-
-    check!(
-        bank.emissions_mint.eq(&ctx.accounts.emissions_mint.key()),
+        bank.is_emissions_mint(&emissions_mint),
         AstrolendError::EmissionsUpdateError
     );
 
-    if let Some(flags) = emissions_flags {
-        msg!("Updating emissions flags to {:#010b}", flags);
-        bank.flags = flags;
-    }
-
-    if let Some(rate) = emissions_rate {
-        msg!("Updating emissions rate to {}", rate);
-        bank.emissions_rate = rate;
-    }
+    // Accrue pro-rata streams under the current parameters before changing them
+    bank.accrue_pro_rata_emissions(Clock::get()?.unix_timestamp)?;
+
+    let emissions_remaining = match bank.get_emissions_stream_index(&emissions_mint) {
+        None => {
+            if let Some(flags) = emissions_flags {
+                check!(
+                    can_update_emissions_flags(bank.flags, flags),
+                    AstrolendError::EmissionsUpdateError
+                );
+                msg!("Updating emissions flags to {:#010b}", flags);
+                bank.flags = flags;
+            }
+
+            if let Some(rate) = emissions_rate {
+                msg!("Updating emissions rate to {}", rate);
+                bank.emissions_rate = rate;
+            }
+
+            &mut bank.emissions_remaining
+        }
+        Some(index) => {
+            let stream = &mut bank.emissions_streams[index];
+
+            if let Some(flags) = emissions_flags {
+                check!(
+                    Bank::verify_emissions_flags(flags)
+                        && can_update_emissions_flags(stream.flags, flags),
+                    AstrolendError::EmissionsUpdateError
+                );
+                msg!(
+                    "Updating stream {} emissions flags to {:#010b}",
+                    index,
+                    flags
+                );
+                stream.flags = flags;
+            }
+
+            if let Some(rate) = emissions_rate {
+                msg!("Updating stream {} emissions rate to {}", index, rate);
+                stream.rate = rate;
+            }
+
+            &mut stream.remaining
+        }
+    };
 
     if let Some(additional_emissions) = additional_emissions {
-        bank.emissions_remaining = I80F48::from(bank.emissions_remaining)
+        *emissions_remaining = I80F48::from(*emissions_remaining)
             .checked_add(I80F48::from_num(additional_emissions))
             .ok_or_else(math_error!())?
             .into();
@@ -183,7 +276,7 @@ pub fn lending_pool_update_emissions_parameters(
         msg!(
             "Adding {} emissions, total {}",
             additional_emissions,
-            I80F48::from(bank.emissions_remaining)
+            I80F48::from(*emissions_remaining)
         );
 
         let additional_emissions_amount_pre_fee = utils::calculate_pre_fee_spl_deposit_amount(
@@ -210,6 +303,14 @@ pub fn lending_pool_update_emissions_parameters(
     Ok(())
 }
 
+/// The emissions flags of pro-rata streams are fixed, balance checkpoints depend on them.
+fn can_update_emissions_flags(current_flags: u64, new_flags: u64) -> bool {
+    let is_pro_rata = |flags: u64| flags & EMISSIONS_FLAG_PRO_RATA != 0;
+
+    !(is_pro_rata(current_flags) || is_pro_rata(new_flags))
+        || current_flags & EMISSION_FLAGS == new_flags & EMISSION_FLAGS
+}
+
 #[derive(Accounts)]
 pub struct LendingPoolUpdateEmissionsParameters<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
@@ -245,3 +346,269 @@ pub struct LendingPoolUpdateEmissionsParameters<'info> {
 
     pub token_program: Interface<'info, TokenInterface>,
 }
+
+/// Set the schedule of the emissions stream paying out `emissions_mint`: when emissions start and
+/// end, and how the rate decays in between.
+///
+/// Emissions accrued before the update are not affected: pro-rata streams are accrued first,
+/// time-based streams can only change the part of the schedule after the update, balances settle
+/// their unclaimed period under it.
+pub fn lending_pool_update_emissions_schedule(
+    ctx: Context<LendingPoolUpdateEmissionsSchedule>,
+    schedule: EmissionsScheduleConfig,
+) -> AstrolendResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let emissions_mint = ctx.accounts.emissions_mint.key();
+
+    check!(
+        bank.is_emissions_mint(&emissions_mint),
+        AstrolendError::EmissionsUpdateError
+    );
+
+    let schedule: EmissionsSchedule = schedule.into();
+    schedule.validate()?;
+
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    bank.accrue_pro_rata_emissions(current_timestamp)?;
+
+    let (emissions_flags, setup_time, current_schedule) =
+        match bank.get_emissions_stream_index(&emissions_mint) {
+            None => (
+                bank.flags,
+                bank.emissions_setup_time,
+                &mut bank.emissions_schedule,
+            ),
+            Some(index) => {
+                let stream = &mut bank.emissions_streams[index];
+                (stream.flags, stream.setup_time, &mut stream.schedule)
+            }
+        };
+
+    check!(
+        emissions_flags & EMISSIONS_FLAG_PRO_RATA != 0
+            || current_schedule.can_update_to(&schedule, setup_time, current_timestamp),
+        AstrolendError::InvalidEmissionsSchedule,
+        "Time-based emissions schedules can't change before the current time"
+    );
+    // A clawed back stream stays ended
+    *current_schedule = EmissionsSchedule {
+        clawback_time: current_schedule.clawback_time,
+        ..schedule
+    };
+
+    msg!(
+        "Updated emissions schedule: {} - {}, decay {:?}",
+        schedule.start_time,
+        schedule.end_time,
+        schedule.decay
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolUpdateEmissionsSchedule<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub emissions_mint: InterfaceAccount<'info, Mint>,
+}
+
+/// End the emissions stream paying out `emissions_mint` and return the undistributed emissions
+/// to `destination_account`.
+///
+/// The stream stops emitting at the clawback, balances keep claiming the emissions accrued
+/// before it. The emissions vault keeps what is owed to balances:
+/// - the settled emissions not withdrawn yet, held in the vault on top of the remaining budget
+/// - the time-based emissions not settled yet, bounded by the emissions of all the deposits or
+///   borrows of the bank since the stream was set up, pro-rata emissions are settled to the
+///   stream index at the clawback
+///
+/// Everything else of the remaining budget is returned.
+pub fn lending_pool_clawback_emissions(
+    ctx: Context<LendingPoolClawbackEmissions>,
+) -> AstrolendResult {
+    let clock = Clock::get()?;
+    let mut bank = ctx.accounts.bank.load_mut()?;
+    let bank = &mut *bank;
+    let emissions_mint = ctx.accounts.emissions_mint.key();
+
+    check!(
+        bank.is_emissions_mint(&emissions_mint),
+        AstrolendError::EmissionsStreamNotFound
+    );
+
+    // Settle the emissions of pro-rata streams accrued up to now
+    bank.accrue_pro_rata_emissions(clock.unix_timestamp)?;
+
+    let total_asset_amount = bank.get_asset_amount(bank.total_asset_shares.into())?;
+    let total_liability_amount = bank.get_liability_amount(bank.total_liability_shares.into())?;
+    let mint_decimals = bank.mint_decimals;
+
+    let (emissions_flags, emissions_rate, setup_time, emissions_schedule, emissions_remaining) =
+        match bank.get_emissions_stream_index(&emissions_mint) {
+            None => (
+                bank.flags,
+                bank.emissions_rate,
+                bank.emissions_setup_time,
+                &mut bank.emissions_schedule,
+                &mut bank.emissions_remaining,
+            ),
+            Some(index) => {
+                let stream = &mut bank.emissions_streams[index];
+                (
+                    stream.flags,
+                    stream.rate,
+                    stream.setup_time,
+                    &mut stream.schedule,
+                    &mut stream.remaining,
+                )
+            }
+        };
+
+    // Time-based emissions are only settled when a balance claims them, keep enough of the
+    // remaining budget for every balance that could have accrued since the stream was set up.
+    // Streams set up before the setup time was recorded fall back to the emissions cutoff.
+    let emissions_unsettled = if emissions_flags & EMISSIONS_FLAG_PRO_RATA != 0 {
+        I80F48::ZERO
+    } else {
+        let mut balance_amount = I80F48::ZERO;
+        if emissions_flags & EMISSIONS_FLAG_LENDING_ACTIVE != 0 {
+            balance_amount = balance_amount
+                .checked_add(total_asset_amount)
+                .ok_or_else(math_error!())?;
+        }
+        if emissions_flags & EMISSIONS_FLAG_BORROW_ACTIVE != 0 {
+            balance_amount = balance_amount
+                .checked_add(total_liability_amount)
+                .ok_or_else(math_error!())?;
+        }
+
+        let (period, rate) = emissions_schedule.get_period_and_rate(
+            emissions_rate,
+            setup_time.max(MIN_EMISSIONS_START_TIME as i64),
+            clock.unix_timestamp,
+        )?;
+
+        calc_emissions(period, balance_amount, mint_decimals as usize, rate)?
+    };
+
+    if emissions_schedule.clawback_time == 0 {
+        emissions_schedule.clawback_time = clock.unix_timestamp;
+    }
+
+    let remaining = I80F48::from(*emissions_remaining);
+    let remaining_kept = emissions_unsettled.min(remaining);
+    *emissions_remaining = remaining_kept.into();
+
+    let clawback_amount = remaining
+        .checked_sub(remaining_kept)
+        .ok_or_else(math_error!())?
+        .checked_floor()
+        .ok_or_else(math_error!())?
+        .checked_to_num::<u64>()
+        .ok_or_else(math_error!())?
+        .min(ctx.accounts.emissions_vault.amount);
+    let emissions_reserved = ctx.accounts.emissions_vault.amount - clawback_amount;
+
+    msg!(
+        "Clawing back {} emissions, {} remaining, {} reserved",
+        clawback_amount,
+        remaining,
+        emissions_reserved
+    );
+
+    if clawback_amount > 0 {
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            &ctx.accounts.bank.key().to_bytes(),
+            &emissions_mint.to_bytes(),
+            &[ctx.bumps.emissions_auth],
+        ]];
+
+        transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.emissions_vault.to_account_info(),
+                    to: ctx.accounts.destination_account.to_account_info(),
+                    authority: ctx.accounts.emissions_auth.to_account_info(),
+                    mint: ctx.accounts.emissions_mint.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            clawback_amount,
+            ctx.accounts.emissions_mint.decimals,
+        )?;
+    }
+
+    emit!(LendingPoolBankEmissionsClawbackEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        emissions_mint,
+        emissions_remaining: remaining.to_num::<f64>(),
+        emissions_reserved: emissions_reserved as f64,
+        amount: clawback_amount,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolClawbackEmissions<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub emissions_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            bank.key().as_ref(),
+            emissions_mint.key().as_ref(),
+        ],
+        bump
+    )]
+    /// CHECK: Asserted by PDA constraints
+    pub emissions_auth: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+            bank.key().as_ref(),
+            emissions_mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub emissions_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Token program checks the destination, in the emissions mint
+    #[account(mut)]
+    pub destination_account: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
@@ -0,0 +1,189 @@
+use crate::constants::PENDING_BANK_CONFIG_SEED;
+use crate::events::{GroupEventHeader, LendingPoolBankConfigureEvent};
+use crate::prelude::AstrolendError;
+use crate::state::astrolend_group::PendingBankConfig;
+use crate::{check, math_error};
+use crate::{
+    state::astrolend_group::{AstrolendGroup, Bank, BankConfigOpt},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+
+/// Queue a bank config change, it can be executed by anyone once the group's
+/// bank config delay has passed.
+///
+/// Admin only
+pub fn lending_pool_queue_bank_config(
+    ctx: Context<LendingPoolQueueBankConfig>,
+    bank_config: BankConfigOpt,
+) -> AstrolendResult {
+    let bank_config_delay = ctx.accounts.astrolend_group.load()?.bank_config_delay;
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    let earliest_execution_time = current_timestamp
+        .checked_add_unsigned(bank_config_delay)
+        .ok_or_else(math_error!())?;
+
+    let pending_bank_config = &mut ctx.accounts.pending_bank_config;
+    pending_bank_config.bank = ctx.accounts.bank.key();
+    pending_bank_config.rent_payer = ctx.accounts.admin.key();
+    pending_bank_config.earliest_execution_time = earliest_execution_time;
+    pending_bank_config.config = bank_config.clone();
+
+    msg!(
+        "Queued bank config change, executable after {}",
+        earliest_execution_time
+    );
+
+    emit!(LendingPoolBankConfigureEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: ctx.accounts.bank.load()?.mint,
+        config: bank_config,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolQueueBankConfig<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PendingBankConfig::LEN,
+        seeds = [
+            PENDING_BANK_CONFIG_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_bank_config: Account<'info, PendingBankConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Apply a queued bank config change once its timelock has elapsed.
+///
+/// Permissionless, the oracle accounts must be passed as remaining accounts if the
+/// queued config changes the oracle.
+pub fn lending_pool_execute_bank_config(
+    ctx: Context<LendingPoolExecuteBankConfig>,
+) -> AstrolendResult {
+    let pending_bank_config = &ctx.accounts.pending_bank_config;
+
+    check!(
+        Clock::get()?.unix_timestamp >= pending_bank_config.earliest_execution_time,
+        AstrolendError::BankConfigTimelockNotElapsed
+    );
+
+    let bank_config = pending_bank_config.config.clone();
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    bank.configure(&bank_config)?;
+
+    if bank_config.oracle.is_some() {
+        bank.config.validate_oracle_setup(ctx.remaining_accounts)?;
+    }
+
+    emit!(LendingPoolBankConfigureEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: None
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        config: bank_config,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolExecuteBankConfig<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        has_one = bank,
+        has_one = rent_payer,
+        close = rent_payer,
+        seeds = [
+            PENDING_BANK_CONFIG_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_bank_config: Account<'info, PendingBankConfig>,
+
+    /// CHECK: Asserted by the pending bank config `has_one` constraint
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+}
+
+/// Drop a queued bank config change without applying it.
+///
+/// Admin only
+pub fn lending_pool_cancel_bank_config(
+    ctx: Context<LendingPoolCancelBankConfig>,
+) -> AstrolendResult {
+    msg!(
+        "Cancelled pending bank config for bank {}",
+        ctx.accounts.bank.key()
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolCancelBankConfig<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        has_one = bank,
+        has_one = rent_payer,
+        close = rent_payer,
+        seeds = [
+            PENDING_BANK_CONFIG_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_bank_config: Account<'info, PendingBankConfig>,
+
+    /// CHECK: Asserted by the pending bank config `has_one` constraint
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+}
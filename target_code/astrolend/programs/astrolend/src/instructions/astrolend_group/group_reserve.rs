@@ -0,0 +1,164 @@
+use crate::{
+    check,
+    constants::{GROUP_RESERVE_VAULT_AUTHORITY_SEED, GROUP_RESERVE_VAULT_SEED},
+    prelude::AstrolendError,
+    state::astrolend_group::{AstrolendGroup, Bank},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+/// Create the group reserve vault, in the mint of `reserve_bank`.
+///
+/// The group reserve buys down bad debt of any other bank of the group before it is socialized,
+/// see `lending_pool_handle_bankruptcy_with_reserve`. The reserve is priced with the oracle of
+/// `reserve_bank` and funded by plain token transfers into the reserve vault.
+///
+/// Token22 reserve mints are not supported.
+///
+/// Admin only
+pub fn lending_pool_setup_group_reserve(
+    ctx: Context<LendingPoolSetupGroupReserve>,
+) -> AstrolendResult {
+    let mut astrolend_group = ctx.accounts.astrolend_group.load_mut()?;
+
+    check!(
+        !astrolend_group.is_reserve_enabled(),
+        AstrolendError::GroupReserveAlreadySetup
+    );
+
+    check!(
+        ctx.accounts.token_program.key() == anchor_spl::token::ID,
+        AstrolendError::InvalidConfig,
+        "Group reserve mint must be owned by the token program"
+    );
+
+    astrolend_group.reserve_bank = ctx.accounts.reserve_bank.key();
+    astrolend_group.reserve_vault_bump = ctx.bumps.reserve_vault;
+    astrolend_group.reserve_vault_authority_bump = ctx.bumps.reserve_vault_authority;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSetupGroupReserve<'info> {
+    #[account(mut)]
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = reserve_bank.load()?.group == astrolend_group.key(),
+    )]
+    pub reserve_bank: AccountLoader<'info, Bank>,
+
+    #[account(address = reserve_bank.load()?.mint)]
+    pub reserve_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            GROUP_RESERVE_VAULT_AUTHORITY_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+        ],
+        bump
+    )]
+    pub reserve_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        token::mint = reserve_mint,
+        token::authority = reserve_vault_authority,
+        seeds = [
+            GROUP_RESERVE_VAULT_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub reserve_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Withdraw `amount` from the group reserve vault.
+///
+/// Admin only
+pub fn lending_pool_withdraw_group_reserve(
+    ctx: Context<LendingPoolWithdrawGroupReserve>,
+    amount: u64,
+) -> AstrolendResult {
+    let astrolend_group = ctx.accounts.astrolend_group.load()?;
+
+    check!(
+        astrolend_group.is_reserve_enabled(),
+        AstrolendError::GroupReserveNotSetup
+    );
+
+    let group_key = ctx.accounts.astrolend_group.key();
+
+    transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.reserve_vault.to_account_info(),
+                mint: ctx.accounts.reserve_mint.to_account_info(),
+                to: ctx.accounts.dst_token_account.to_account_info(),
+                authority: ctx.accounts.reserve_vault_authority.to_account_info(),
+            },
+            &[&[
+                GROUP_RESERVE_VAULT_AUTHORITY_SEED.as_bytes(),
+                group_key.as_ref(),
+                &[astrolend_group.reserve_vault_authority_bump],
+            ]],
+        ),
+        amount,
+        ctx.accounts.reserve_mint.decimals,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolWithdrawGroupReserve<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(address = astrolend_group.load()?.admin)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            GROUP_RESERVE_VAULT_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+        ],
+        bump = astrolend_group.load()?.reserve_vault_bump
+    )]
+    pub reserve_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = reserve_vault.mint)]
+    pub reserve_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            GROUP_RESERVE_VAULT_AUTHORITY_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+        ],
+        bump = astrolend_group.load()?.reserve_vault_authority_bump
+    )]
+    pub reserve_vault_authority: AccountInfo<'info>,
+
+    /// CHECK: Token program checks the destination
+    #[account(mut)]
+    pub dst_token_account: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
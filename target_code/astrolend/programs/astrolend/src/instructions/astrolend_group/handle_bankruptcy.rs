@@ -1,21 +1,31 @@
 use crate::{
     bank_signer, check,
     constants::{
+        GROUP_RESERVE_VAULT_AUTHORITY_SEED, GROUP_RESERVE_VAULT_SEED,
         INSURANCE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_SEED,
         PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG, ZERO_AMOUNT_THRESHOLD,
     },
     debug,
-    events::{AccountEventHeader, LendingPoolBankHandleBankruptcyEvent},
+    events::{
+        AccountEventHeader, LendingPoolAccountHandleBankruptcyEvent,
+        LendingPoolBankHandleBankruptcyEvent, LendingPoolGroupReserveCoverBadDebtEvent,
+    },
     math_error,
     prelude::AstrolendError,
     state::{
-        astrolend_account::{BankAccountWrapper, AstrolendAccount, RiskEngine, DISABLED_FLAG},
+        astrolend_account::{
+            calc_amount, calc_value, BalanceSide, BankAccountWithPriceFeed, BankAccountWrapper,
+            AstrolendAccount, RiskEngine, DISABLED_FLAG,
+        },
         astrolend_group::{Bank, BankVaultType, AstrolendGroup},
+        price::{OraclePriceType, PriceBias},
     },
     utils, AstrolendResult,
 };
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 use fixed::types::I80F48;
 use std::cmp::{max, min};
 
@@ -24,7 +34,17 @@ use std::cmp::{max, min};
 /// 2. Determine the amount of bad debt covered by the insurance fund and the amount socialized between depositors.
 /// 3. Cover the bad debt of the bankrupt account.
 /// 4. Transfer the insured amount from the insurance fund.
-/// 5. Socialize the loss between lenders if any.
+/// 5. Socialize the loss between lenders if any, or record it as a deficit of the bank.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+///    reserve_vault_ai (if the group reserve is setup),
+///    astrolend_account_observation_ais...,
+/// ]
+///
+/// Losses can't be socialized or deferred while the group reserve holds tokens, the bad debt must
+/// be bought down with `lending_pool_handle_bankruptcy_with_reserve` first.
 pub fn lending_pool_handle_bankruptcy<'info>(
     mut ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleBankruptcy<'info>>,
 ) -> AstrolendResult {
@@ -41,12 +61,17 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
 
     let clock = Clock::get()?;
+    let astrolend_group = astrolend_group_loader.load()?;
+    let signer_is_admin = ctx.accounts.signer.key() == astrolend_group.admin;
+
+    let maybe_reserve_vault_amount = maybe_take_reserve_vault_amount(
+        &mut ctx.remaining_accounts,
+        &astrolend_group,
+        &astrolend_group_loader.key(),
+    )?;
 
     if !bank.get_flag(PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG) {
-        check!(
-            ctx.accounts.signer.key() == astrolend_group_loader.load()?.admin,
-            AstrolendError::Unauthorized
-        );
+        check!(signer_is_admin, AstrolendError::Unauthorized);
     }
 
     drop(bank);
@@ -57,17 +82,123 @@ pub fn lending_pool_handle_bankruptcy<'info>(
 
     let mut bank = bank_loader.load_mut()?;
 
+    let BadDebtSettlement {
+        bad_debt,
+        covered_by_insurance,
+        socialized_loss,
+        deficit_recorded,
+        ..
+    } = settle_bad_debt(
+        &mut bank,
+        bank_loader.key(),
+        &mut astrolend_account,
+        BadDebtSettlementAccounts {
+            liquidity_vault: ctx.accounts.liquidity_vault.to_account_info(),
+            insurance_vault: ctx.accounts.insurance_vault.to_account_info(),
+            insurance_vault_amount: insurance_vault.amount,
+            insurance_vault_authority: ctx.accounts.insurance_vault_authority.to_account_info(),
+            maybe_bank_mint: maybe_bank_mint.as_ref(),
+            token_program: token_program.to_account_info(),
+        },
+        None,
+        ctx.remaining_accounts,
+        &clock,
+    )?;
+
+    check_reserve_exhausted(
+        &astrolend_group,
+        bank_loader.key(),
+        socialized_loss + deficit_recorded,
+        maybe_reserve_vault_amount,
+    )?;
+
+    astrolend_account.set_flag(DISABLED_FLAG);
+
+    emit!(LendingPoolBankHandleBankruptcyEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        bad_debt: bad_debt.to_num::<f64>(),
+        covered_amount: covered_by_insurance.to_num::<f64>(),
+        socialized_amount: socialized_loss.to_num::<f64>(),
+        deficit_amount: deficit_recorded.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+/// Token accounts of a bank involved in settling bad debt.
+pub struct BadDebtSettlementAccounts<'a, 'info> {
+    pub liquidity_vault: AccountInfo<'info>,
+    pub insurance_vault: AccountInfo<'info>,
+    pub insurance_vault_amount: u64,
+    pub insurance_vault_authority: AccountInfo<'info>,
+    pub maybe_bank_mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+/// Group reserve accounts and prices used to buy down bad debt before it is socialized.
+///
+/// The buyer repays bad debt into the bank's liquidity vault and receives reserve tokens for
+/// the oracle value of the repaid tokens plus the haircut.
+pub struct ReserveCoverage<'info> {
+    pub astrolend_group: Pubkey,
+    pub reserve_haircut: I80F48,
+    pub reserve_mint: AccountInfo<'info>,
+    pub reserve_vault: AccountInfo<'info>,
+    pub reserve_vault_amount: u64,
+    pub reserve_vault_authority: AccountInfo<'info>,
+    pub reserve_vault_authority_bump: u8,
+    pub reserve_mint_decimals: u8,
+    pub reserve_price: I80F48,
+    pub reserve_token_program: AccountInfo<'info>,
+    pub bank_price: I80F48,
+    pub buyer: AccountInfo<'info>,
+    pub buyer_token_account: AccountInfo<'info>,
+    pub buyer_reserve_token_account: AccountInfo<'info>,
+}
+
+/// Outcome of settling the bad debt of a single bank.
+pub struct BadDebtSettlement {
+    pub bad_debt: I80F48,
+    pub covered_by_insurance: I80F48,
+    pub covered_by_reserve: I80F48,
+    /// Reserve tokens paid to the buyer of the bad debt.
+    pub reserve_amount_paid: u64,
+    pub socialized_loss: I80F48,
+    pub deficit_recorded: I80F48,
+}
+
+/// Settle the bad debt of a bankrupt account in a single bank.
+///
+/// The bad debt is covered by the bank's insurance fund first, then bought down with the group
+/// reserve if provided, the rest is socialized among the bank's depositors, or recorded as a
+/// deficit of the bank if it defers loss socialization.
+pub fn settle_bad_debt<'info>(
+    bank: &mut Bank,
+    bank_pk: Pubkey,
+    astrolend_account: &mut AstrolendAccount,
+    accounts: BadDebtSettlementAccounts<'_, 'info>,
+    maybe_reserve: Option<ReserveCoverage<'info>>,
+    remaining_accounts: &[AccountInfo<'info>],
+    clock: &Clock,
+) -> AstrolendResult<BadDebtSettlement> {
     bank.accrue_interest(
         clock.unix_timestamp,
         #[cfg(not(feature = "client"))]
-        bank_loader.key(),
+        bank_pk,
     )?;
 
     let lending_account_balance = astrolend_account
         .lending_account
         .balances
         .iter_mut()
-        .find(|balance| balance.active && balance.bank_pk == bank_loader.key());
+        .find(|balance| balance.active && balance.bank_pk == bank_pk);
 
     check!(
         lending_account_balance.is_some(),
@@ -83,24 +214,24 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         AstrolendError::BalanceNotBadDebt
     );
 
-    let (covered_by_insurance, socialized_loss) = {
-        let available_insurance_fund: I80F48 = maybe_bank_mint
-            .as_ref()
+    let (covered_by_insurance, uncovered_bad_debt) = {
+        let available_insurance_fund: I80F48 = accounts
+            .maybe_bank_mint
             .map(|mint| {
                 utils::calculate_post_fee_spl_deposit_amount(
                     mint.to_account_info(),
-                    insurance_vault.amount,
+                    accounts.insurance_vault_amount,
                     clock.epoch,
                 )
             })
             .transpose()?
-            .unwrap_or(insurance_vault.amount)
+            .unwrap_or(accounts.insurance_vault_amount)
             .into();
 
         let covered_by_insurance = min(bad_debt, available_insurance_fund);
-        let socialized_loss = max(bad_debt - covered_by_insurance, I80F48::ZERO);
+        let uncovered_bad_debt = max(bad_debt - covered_by_insurance, I80F48::ZERO);
 
-        (covered_by_insurance, socialized_loss)
+        (covered_by_insurance, uncovered_bad_debt)
     };
 
     // Cover bad debt with insurance funds.
@@ -110,12 +241,12 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         .checked_to_num()
         .ok_or_else(math_error!())?;
     debug!(
-        "covered_by_insurance_rounded_up: {}; socialized loss {}",
-        covered_by_insurance_rounded_up, socialized_loss
+        "covered_by_insurance_rounded_up: {}; uncovered bad debt {}",
+        covered_by_insurance_rounded_up, uncovered_bad_debt
     );
 
-    let insurance_coverage_deposit_pre_fee = maybe_bank_mint
-        .as_ref()
+    let insurance_coverage_deposit_pre_fee = accounts
+        .maybe_bank_mint
         .map(|mint| {
             utils::calculate_pre_fee_spl_deposit_amount(
                 mint.to_account_info(),
@@ -128,36 +259,435 @@ pub fn lending_pool_handle_bankruptcy<'info>(
 
     bank.withdraw_spl_transfer(
         insurance_coverage_deposit_pre_fee,
-        ctx.accounts.insurance_vault.to_account_info(),
-        ctx.accounts.liquidity_vault.to_account_info(),
-        ctx.accounts.insurance_vault_authority.to_account_info(),
-        maybe_bank_mint.as_ref(),
-        token_program.to_account_info(),
+        accounts.insurance_vault,
+        accounts.liquidity_vault.clone(),
+        accounts.insurance_vault_authority,
+        accounts.maybe_bank_mint,
+        accounts.token_program.clone(),
         bank_signer!(
             BankVaultType::Insurance,
-            bank_loader.key(),
+            bank_pk,
             bank.insurance_vault_authority_bump
         ),
-        ctx.remaining_accounts,
+        remaining_accounts,
     )?;
 
-    // Socialize bad debt among depositors.
-    bank.socialize_loss(socialized_loss)?;
+    // Buy down the rest of the bad debt with the group reserve.
+    let (covered_by_reserve, reserve_amount_paid) = match maybe_reserve {
+        Some(reserve) => buy_down_bad_debt_with_reserve(
+            bank,
+            uncovered_bad_debt,
+            reserve,
+            accounts.liquidity_vault,
+            accounts.maybe_bank_mint,
+            accounts.token_program,
+            remaining_accounts,
+            clock,
+        )?,
+        None => (I80F48::ZERO, 0),
+    };
+
+    let uncovered_loss = max(uncovered_bad_debt - covered_by_reserve, I80F48::ZERO);
+
+    // Socialize bad debt among depositors, or defer it until repaid from interest.
+    let (socialized_loss, deficit_recorded) = if bank.defers_loss_socialization() {
+        bank.record_deficit(uncovered_loss)?;
+        (I80F48::ZERO, uncovered_loss)
+    } else {
+        bank.socialize_loss(uncovered_loss)?;
+        (uncovered_loss, I80F48::ZERO)
+    };
 
     // Settle bad debt.
     // The liabilities of this account and global total liabilities are reduced by `bad_debt`
-    BankAccountWrapper::find_or_create(
+    BankAccountWrapper::find_or_create(&bank_pk, bank, astrolend_account)?.repay(bad_debt)?;
+
+    Ok(BadDebtSettlement {
+        bad_debt,
+        covered_by_insurance,
+        covered_by_reserve,
+        reserve_amount_paid,
+        socialized_loss,
+        deficit_recorded,
+    })
+}
+
+/// Repay up to `uncovered_bad_debt` with tokens of the buyer, and pay the buyer out of the group
+/// reserve, limited by the reserve balance.
+///
+/// The repaid tokens are valued low and the reserve tokens high, so the reserve never pays
+/// more than the haircut as a premium over the oracle value.
+///
+/// Returns `(covered_by_reserve, reserve_amount_paid)`.
+#[allow(clippy::too_many_arguments)]
+fn buy_down_bad_debt_with_reserve<'info>(
+    bank: &Bank,
+    uncovered_bad_debt: I80F48,
+    reserve: ReserveCoverage<'info>,
+    liquidity_vault: AccountInfo<'info>,
+    maybe_bank_mint: Option<&InterfaceAccount<'info, Mint>>,
+    token_program: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    clock: &Clock,
+) -> AstrolendResult<(I80F48, u64)> {
+    let reserve_premium = I80F48::ONE
+        .checked_add(reserve.reserve_haircut)
+        .ok_or_else(math_error!())?;
+
+    // Bank tokens that can be bought with the whole reserve.
+    let reserve_capacity = calc_amount(
+        calc_value(
+            I80F48::from_num(reserve.reserve_vault_amount),
+            reserve.reserve_price,
+            reserve.reserve_mint_decimals,
+            None,
+        )?
+        .checked_div(reserve_premium)
+        .ok_or_else(math_error!())?,
+        reserve.bank_price,
+        bank.mint_decimals,
+    )?;
+
+    let covered_amount: u64 = min(
+        uncovered_bad_debt
+            .checked_ceil()
+            .ok_or_else(math_error!())?,
+        reserve_capacity.checked_floor().ok_or_else(math_error!())?,
+    )
+    .checked_to_num()
+    .ok_or_else(math_error!())?;
+
+    if covered_amount == 0 {
+        return Ok((I80F48::ZERO, 0));
+    }
+
+    let reserve_amount_paid: u64 = calc_amount(
+        calc_value(
+            I80F48::from_num(covered_amount),
+            reserve.bank_price,
+            bank.mint_decimals,
+            None,
+        )?
+        .checked_mul(reserve_premium)
+        .ok_or_else(math_error!())?,
+        reserve.reserve_price,
+        reserve.reserve_mint_decimals,
+    )?
+    .checked_floor()
+    .ok_or_else(math_error!())?
+    .checked_to_num::<u64>()
+    .ok_or_else(math_error!())?
+    .min(reserve.reserve_vault_amount);
+
+    debug!(
+        "reserve covered amount: {}; reserve amount paid {}",
+        covered_amount, reserve_amount_paid
+    );
+
+    let covered_amount_pre_fee = maybe_bank_mint
+        .map(|mint| {
+            utils::calculate_pre_fee_spl_deposit_amount(
+                mint.to_account_info(),
+                covered_amount,
+                clock.epoch,
+            )
+        })
+        .transpose()?
+        .unwrap_or(covered_amount);
+
+    bank.deposit_spl_transfer(
+        covered_amount_pre_fee,
+        reserve.buyer_token_account,
+        liquidity_vault,
+        reserve.buyer,
+        maybe_bank_mint,
+        token_program,
+        remaining_accounts,
+    )?;
+
+    transfer_checked(
+        CpiContext::new_with_signer(
+            reserve.reserve_token_program,
+            TransferChecked {
+                from: reserve.reserve_vault,
+                mint: reserve.reserve_mint,
+                to: reserve.buyer_reserve_token_account,
+                authority: reserve.reserve_vault_authority,
+            },
+            &[&[
+                GROUP_RESERVE_VAULT_AUTHORITY_SEED.as_bytes(),
+                reserve.astrolend_group.as_ref(),
+                &[reserve.reserve_vault_authority_bump],
+            ]],
+        ),
+        reserve_amount_paid,
+        reserve.reserve_mint_decimals,
+    )?;
+
+    Ok((
+        min(uncovered_bad_debt, I80F48::from_num(covered_amount)),
+        reserve_amount_paid,
+    ))
+}
+
+/// Take the group reserve vault off the front of the remaining accounts if the group reserve is
+/// setup, and return its balance.
+fn maybe_take_reserve_vault_amount<'info>(
+    remaining_ais: &mut &'info [AccountInfo<'info>],
+    astrolend_group: &AstrolendGroup,
+    astrolend_group_pk: &Pubkey,
+) -> AstrolendResult<Option<u64>> {
+    if !astrolend_group.is_reserve_enabled() {
+        return Ok(None);
+    }
+
+    let (reserve_vault_ai, rest) = remaining_ais
+        .split_first()
+        .ok_or(AstrolendError::GroupReserveCoverageRequired)?;
+    *remaining_ais = rest;
+
+    let reserve_vault_pk = Pubkey::create_program_address(
+        &[
+            GROUP_RESERVE_VAULT_SEED.as_bytes(),
+            astrolend_group_pk.as_ref(),
+            &[astrolend_group.reserve_vault_bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| AstrolendError::GroupReserveNotSetup)?;
+
+    check!(
+        reserve_vault_ai.key() == reserve_vault_pk,
+        AstrolendError::GroupReserveCoverageRequired
+    );
+
+    Ok(Some(
+        InterfaceAccount::<TokenAccount>::try_from(reserve_vault_ai)?.amount,
+    ))
+}
+
+/// Losses in banks other than the reserve bank can only be socialized or deferred once the group
+/// reserve is exhausted.
+fn check_reserve_exhausted(
+    astrolend_group: &AstrolendGroup,
+    bank_pk: Pubkey,
+    uncovered_loss: I80F48,
+    maybe_reserve_vault_amount: Option<u64>,
+) -> AstrolendResult {
+    if astrolend_group.is_reserve_enabled()
+        && astrolend_group.reserve_bank != bank_pk
+        && uncovered_loss > I80F48::ZERO
+    {
+        check!(
+            maybe_reserve_vault_amount == Some(0),
+            AstrolendError::GroupReserveCoverageRequired
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolHandleBankruptcy<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    // #[account(address = astrolend_group.load()?.admin)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump
+    )]
+    pub liquidity_vault: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Handle a bankrupt astrolend account for a given bank, buying down the bad debt left after the
+/// bank's insurance fund with the group reserve before socializing the rest.
+///
+/// The signer repays as much of the remaining bad debt as the reserve can buy from
+/// `signer_token_account`, and receives reserve tokens for its oracle value plus the group's
+/// reserve haircut into `signer_reserve_token_account`. The group reserve does not cover bad debt
+/// of the reserve bank itself.
+///
+/// Expected remaining account schema
+/// [
+///    bank_mint_ai (if token2022 mint),
+///    reserve_bank_ai,
+///    reserve_oracle_ai,
+///    astrolend_account_observation_ais...,
+/// ]
+///
+/// The bank's price is read from its [bank, oracle] pair in the observation accounts.
+pub fn lending_pool_handle_bankruptcy_with_reserve<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleBankruptcyWithReserve<'info>>,
+) -> AstrolendResult {
+    check!(
+        ctx.accounts.bank.key() != ctx.accounts.reserve_bank.key(),
+        AstrolendError::IllegalAction,
+        "The group reserve only covers bad debt of other banks"
+    );
+
+    let LendingPoolHandleBankruptcyWithReserve {
+        astrolend_group: astrolend_group_loader,
+        signer,
+        bank: bank_loader,
+        astrolend_account: astrolend_account_loader,
+        liquidity_vault,
+        insurance_vault,
+        insurance_vault_authority,
+        reserve_bank: reserve_bank_loader,
+        reserve_mint,
+        reserve_vault,
+        reserve_vault_authority,
+        signer_token_account,
+        signer_reserve_token_account,
+        token_program,
+        reserve_token_program,
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let astrolend_group = astrolend_group_loader.load()?;
+
+    let bank = bank_loader.load()?;
+    let maybe_bank_mint =
+        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+
+    if !bank.get_flag(PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG) {
+        check!(
+            signer.key() == astrolend_group.admin,
+            AstrolendError::Unauthorized
+        );
+    }
+
+    check!(
+        ctx.remaining_accounts.len() >= 2,
+        AstrolendError::MissingPythOrBankAccount
+    );
+
+    let (reserve_ais, observation_ais) = ctx.remaining_accounts.split_at(2);
+
+    let bank_price = BankAccountWithPriceFeed::load_bank_price(
+        observation_ais,
         &bank_loader.key(),
+        OraclePriceType::RealTime,
+        Some(PriceBias::Low),
+    )?;
+
+    let reserve_bank = reserve_bank_loader.load()?;
+    let reserve_price = BankAccountWithPriceFeed::load_bank_price(
+        reserve_ais,
+        &reserve_bank_loader.key(),
+        OraclePriceType::RealTime,
+        Some(PriceBias::High),
+    )?;
+
+    drop(bank);
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    RiskEngine::new(&astrolend_account, observation_ais)?.check_account_bankrupt()?;
+
+    let mut bank = bank_loader.load_mut()?;
+
+    let BadDebtSettlement {
+        bad_debt,
+        covered_by_insurance,
+        covered_by_reserve,
+        reserve_amount_paid,
+        socialized_loss,
+        deficit_recorded,
+    } = settle_bad_debt(
         &mut bank,
-        &mut astrolend_account.lending_account,
-    )?
-    .repay(bad_debt)?;
+        bank_loader.key(),
+        &mut astrolend_account,
+        BadDebtSettlementAccounts {
+            liquidity_vault: liquidity_vault.to_account_info(),
+            insurance_vault: insurance_vault.to_account_info(),
+            insurance_vault_amount: insurance_vault.amount,
+            insurance_vault_authority: insurance_vault_authority.to_account_info(),
+            maybe_bank_mint: maybe_bank_mint.as_ref(),
+            token_program: token_program.to_account_info(),
+        },
+        Some(ReserveCoverage {
+            astrolend_group: astrolend_group_loader.key(),
+            reserve_haircut: astrolend_group.reserve_haircut.into(),
+            reserve_mint: reserve_mint.to_account_info(),
+            reserve_vault: reserve_vault.to_account_info(),
+            reserve_vault_amount: reserve_vault.amount,
+            reserve_vault_authority: reserve_vault_authority.to_account_info(),
+            reserve_vault_authority_bump: astrolend_group.reserve_vault_authority_bump,
+            reserve_mint_decimals: reserve_bank.mint_decimals,
+            reserve_price,
+            reserve_token_program: reserve_token_program.to_account_info(),
+            bank_price,
+            buyer: signer.to_account_info(),
+            buyer_token_account: signer_token_account.to_account_info(),
+            buyer_reserve_token_account: signer_reserve_token_account.to_account_info(),
+        }),
+        observation_ais,
+        &clock,
+    )?;
 
     astrolend_account.set_flag(DISABLED_FLAG);
 
+    emit!(LendingPoolGroupReserveCoverBadDebtEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        reserve_bank: reserve_bank_loader.key(),
+        reserve_mint: reserve_bank.mint,
+        covered_amount: covered_by_reserve.to_num::<f64>(),
+        reserve_amount_paid,
+    });
+
     emit!(LendingPoolBankHandleBankruptcyEvent {
         header: AccountEventHeader {
-            signer: Some(ctx.accounts.signer.key()),
+            signer: Some(signer.key()),
             astrolend_account: astrolend_account_loader.key(),
             astrolend_account_authority: astrolend_account.authority,
             astrolend_group: astrolend_account.group,
@@ -165,18 +695,18 @@ pub fn lending_pool_handle_bankruptcy<'info>(
         bank: bank_loader.key(),
         mint: bank.mint,
         bad_debt: bad_debt.to_num::<f64>(),
-        covered_amount: covered_by_insurance.to_num::<f64>(),
+        covered_amount: (covered_by_insurance + covered_by_reserve).to_num::<f64>(),
         socialized_amount: socialized_loss.to_num::<f64>(),
+        deficit_amount: deficit_recorded.to_num::<f64>(),
     });
 
     Ok(())
 }
 
 #[derive(Accounts)]
-pub struct LendingPoolHandleBankruptcy<'info> {
+pub struct LendingPoolHandleBankruptcyWithReserve<'info> {
     pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
 
-    // #[account(address = astrolend_group.load()?.admin)]
     pub signer: Signer<'info>,
 
     #[account(
@@ -187,6 +717,7 @@ pub struct LendingPoolHandleBankruptcy<'info> {
 
     #[account(
         mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
         constraint = astrolend_account.load()?.group == astrolend_group.key(),
     )]
     pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
@@ -222,5 +753,278 @@ pub struct LendingPoolHandleBankruptcy<'info> {
     )]
     pub insurance_vault_authority: AccountInfo<'info>,
 
+    #[account(
+        address = astrolend_group.load()?.reserve_bank @ AstrolendError::GroupReserveNotSetup,
+    )]
+    pub reserve_bank: AccountLoader<'info, Bank>,
+
+    #[account(address = reserve_bank.load()?.mint)]
+    pub reserve_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [
+            GROUP_RESERVE_VAULT_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+        ],
+        bump = astrolend_group.load()?.reserve_vault_bump
+    )]
+    pub reserve_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            GROUP_RESERVE_VAULT_AUTHORITY_SEED.as_bytes(),
+            astrolend_group.key().as_ref(),
+        ],
+        bump = astrolend_group.load()?.reserve_vault_authority_bump
+    )]
+    pub reserve_vault_authority: AccountInfo<'info>,
+
+    /// CHECK: Token program checks the source, in the bank mint
+    #[account(mut)]
+    pub signer_token_account: AccountInfo<'info>,
+
+    /// CHECK: Token program checks the destination, in the reserve mint
+    #[account(mut)]
+    pub signer_reserve_token_account: AccountInfo<'info>,
+
     pub token_program: Interface<'info, TokenInterface>,
+    pub reserve_token_program: Interface<'info, TokenInterface>,
+}
+
+/// Handle a bankrupt astrolend account across all of its liability banks at once.
+///
+/// Remaining accounts, starting with the group reserve vault if the group reserve is setup, then
+/// one group per liability balance of the account in any order:
+/// - bank
+/// - liquidity vault
+/// - insurance vault
+/// - insurance vault authority
+/// - token program
+/// - bank mint, for Token22 banks only
+/// - transfer hook accounts of the bank mint, `hook_accounts_lens` of the group, Token22 only
+///
+/// followed by the [bank, oracle] pairs of every active balance, as for a health check.
+///
+/// The bankruptcy check runs once, then the bad debt of every bank is settled as in
+/// `lending_pool_handle_bankruptcy`. Every liability balance must be settled.
+pub fn lending_pool_handle_account_bankruptcy<'info>(
+    ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleAccountBankruptcy<'info>>,
+    hook_accounts_lens: Vec<u8>,
+) -> AstrolendResult {
+    let LendingPoolHandleAccountBankruptcy {
+        astrolend_account: astrolend_account_loader,
+        astrolend_group: astrolend_group_loader,
+        signer,
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let astrolend_group = astrolend_group_loader.load()?;
+    let signer_is_admin = signer.key() == astrolend_group.admin;
+
+    let mut remaining_ais: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+    let maybe_reserve_vault_amount = maybe_take_reserve_vault_amount(
+        &mut remaining_ais,
+        &astrolend_group,
+        &astrolend_group_loader.key(),
+    )?;
+
+    let mut astrolend_account = astrolend_account_loader.load_mut()?;
+
+    let liability_balance_count = astrolend_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| balance.active && !balance.is_empty(BalanceSide::Liabilities))
+        .count();
+
+    check!(
+        liability_balance_count > 0,
+        AstrolendError::BalanceNotBadDebt
+    );
+    check!(
+        hook_accounts_lens.len() == liability_balance_count,
+        AstrolendError::MissingBankAccount
+    );
+
+    let mut bank_groups = Vec::with_capacity(liability_balance_count);
+
+    for hook_accounts_len in hook_accounts_lens {
+        let (bank_ais, hook_ais, rest) =
+            take_bankruptcy_bank_accounts(remaining_ais, hook_accounts_len as usize)?;
+        bank_groups.push((bank_ais, hook_ais));
+        remaining_ais = rest;
+    }
+
+    RiskEngine::new(&astrolend_account, remaining_ais)?.check_account_bankrupt()?;
+
+    let mut banks = Vec::with_capacity(liability_balance_count);
+    let mut bad_debts = Vec::with_capacity(liability_balance_count);
+    let mut covered_amounts = Vec::with_capacity(liability_balance_count);
+    let mut socialized_amounts = Vec::with_capacity(liability_balance_count);
+    let mut deficit_amounts = Vec::with_capacity(liability_balance_count);
+
+    for (bank_ais, hook_ais) in bank_groups {
+        let bank_ai = &bank_ais[0];
+
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_ai)?;
+        let mut bank = bank_loader.load_mut()?;
+
+        check!(
+            bank.group == astrolend_group_loader.key(),
+            AstrolendError::InvalidBankAccount
+        );
+
+        if !bank.get_flag(PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG) {
+            check!(signer_is_admin, AstrolendError::Unauthorized);
+        }
+
+        let liquidity_vault = &bank_ais[1];
+        let insurance_vault = InterfaceAccount::<TokenAccount>::try_from(&bank_ais[2])?;
+        let insurance_vault_authority = &bank_ais[3];
+        let token_program = Interface::<TokenInterface>::try_from(&bank_ais[4])?;
+
+        check!(
+            liquidity_vault.key() == bank.liquidity_vault
+                && insurance_vault.key() == bank.insurance_vault,
+            AstrolendError::InvalidTransfer
+        );
+
+        let mut mint_ais = &bank_ais[5..];
+        let maybe_bank_mint = utils::maybe_take_bank_mint(&mut mint_ais, &bank, token_program.key)?;
+
+        let BadDebtSettlement {
+            bad_debt,
+            covered_by_insurance,
+            socialized_loss,
+            deficit_recorded,
+            ..
+        } = settle_bad_debt(
+            &mut bank,
+            bank_loader.key(),
+            &mut astrolend_account,
+            BadDebtSettlementAccounts {
+                liquidity_vault: liquidity_vault.clone(),
+                insurance_vault: insurance_vault.to_account_info(),
+                insurance_vault_amount: insurance_vault.amount,
+                insurance_vault_authority: insurance_vault_authority.clone(),
+                maybe_bank_mint: maybe_bank_mint.as_ref(),
+                token_program: token_program.to_account_info(),
+            },
+            None,
+            hook_ais,
+            &clock,
+        )?;
+
+        check_reserve_exhausted(
+            &astrolend_group,
+            bank_loader.key(),
+            socialized_loss + deficit_recorded,
+            maybe_reserve_vault_amount,
+        )?;
+
+        emit!(LendingPoolBankHandleBankruptcyEvent {
+            header: AccountEventHeader {
+                signer: Some(signer.key()),
+                astrolend_account: astrolend_account_loader.key(),
+                astrolend_account_authority: astrolend_account.authority,
+                astrolend_group: astrolend_account.group,
+            },
+            bank: bank_loader.key(),
+            mint: bank.mint,
+            bad_debt: bad_debt.to_num::<f64>(),
+            covered_amount: covered_by_insurance.to_num::<f64>(),
+            socialized_amount: socialized_loss.to_num::<f64>(),
+            deficit_amount: deficit_recorded.to_num::<f64>(),
+        });
+
+        banks.push(bank_loader.key());
+        bad_debts.push(bad_debt.to_num::<f64>());
+        covered_amounts.push(covered_by_insurance.to_num::<f64>());
+        socialized_amounts.push(socialized_loss.to_num::<f64>());
+        deficit_amounts.push(deficit_recorded.to_num::<f64>());
+    }
+
+    check!(
+        astrolend_account
+            .lending_account
+            .balances
+            .iter()
+            .all(|balance| !balance.active || balance.is_empty(BalanceSide::Liabilities)),
+        AstrolendError::IllegalBalanceState,
+        "All liability balances must be settled"
+    );
+
+    astrolend_account.set_flag(DISABLED_FLAG);
+
+    emit!(LendingPoolAccountHandleBankruptcyEvent {
+        header: AccountEventHeader {
+            signer: Some(signer.key()),
+            astrolend_account: astrolend_account_loader.key(),
+            astrolend_account_authority: astrolend_account.authority,
+            astrolend_group: astrolend_account.group,
+        },
+        banks,
+        bad_debts,
+        covered_amounts,
+        socialized_amounts,
+        deficit_amounts,
+    });
+
+    Ok(())
+}
+
+/// Split the accounts of one bank group off the front of the remaining accounts, the bank mint
+/// and its `hook_accounts_len` transfer hook accounts are only part of the group for Token22
+/// banks.
+///
+/// Returns `(bank_ais, hook_ais, rest)`.
+#[allow(clippy::type_complexity)]
+fn take_bankruptcy_bank_accounts<'info>(
+    remaining_ais: &'info [AccountInfo<'info>],
+    hook_accounts_len: usize,
+) -> AstrolendResult<(
+    &'info [AccountInfo<'info>],
+    &'info [AccountInfo<'info>],
+    &'info [AccountInfo<'info>],
+)> {
+    check!(remaining_ais.len() >= 5, AstrolendError::MissingBankAccount);
+
+    let group_len = if remaining_ais[4].key() == anchor_spl::token_2022::ID {
+        6
+    } else {
+        check!(hook_accounts_len == 0, AstrolendError::InvalidBankAccount);
+        5
+    };
+
+    check!(
+        remaining_ais.len() >= group_len,
+        AstrolendError::T22MintRequired
+    );
+
+    let (bank_ais, rest) = remaining_ais.split_at(group_len);
+
+    check!(
+        rest.len() >= hook_accounts_len,
+        AstrolendError::MissingBankAccount
+    );
+
+    let (hook_ais, rest) = rest.split_at(hook_accounts_len);
+
+    Ok((bank_ais, hook_ais, rest))
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolHandleAccountBankruptcy<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = AstrolendAccount::migrate_if_legacy(astrolend_account.as_ref())?,
+        constraint = astrolend_account.load()?.group == astrolend_group.key(),
+    )]
+    pub astrolend_account: AccountLoader<'info, AstrolendAccount>,
 }
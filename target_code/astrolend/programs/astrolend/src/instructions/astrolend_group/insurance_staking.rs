@@ -0,0 +1,807 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        INSURANCE_SHARE_MINT_SEED, INSURANCE_UNSTAKE_ESCROW_SEED, INSURANCE_UNSTAKE_REQUEST_SEED,
+        INSURANCE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED,
+        LIQUIDITY_VAULT_SEED,
+    },
+    events::{
+        GroupEventHeader, LendingPoolBankInsuranceStakeEvent, LendingPoolBankInsuranceUnstakeEvent,
+    },
+    math_error,
+    prelude::AstrolendError,
+    state::{
+        astrolend_group::{AstrolendGroup, Bank, BankVaultType},
+        insurance_fund::{
+            calc_insurance_shares_to_mint, calc_insurance_shares_value, InsuranceUnstakeRequest,
+        },
+    },
+    utils, AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, close_account, mint_to, transfer_checked, Burn, CloseAccount, Mint, MintTo, TokenAccount,
+    TokenInterface, TransferChecked,
+};
+use fixed::types::I80F48;
+use std::cmp::min;
+
+/// Turn the bank's insurance vault into a staking vault.
+///
+/// Stakers receive share tokens for their stake, earn the insurance fees flowing into the
+/// vault, and absorb bad debt before it is socialized among depositors. Once enabled, the
+/// admin can no longer withdraw from the insurance vault.
+///
+/// The insurance vault must be empty, otherwise its balance would be captured by the first staker.
+///
+/// Admin only
+pub fn lending_pool_setup_insurance_staking(
+    ctx: Context<LendingPoolSetupInsuranceStaking>,
+    unstake_cooldown: u64,
+    unstake_window: u64,
+) -> AstrolendResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    check!(
+        bank.insurance_share_mint == Pubkey::default(),
+        AstrolendError::InsuranceStakingAlreadySetup
+    );
+
+    check!(
+        ctx.accounts.insurance_vault.amount == 0,
+        AstrolendError::IllegalAction,
+        "Insurance vault must be emptied before enabling staking"
+    );
+
+    check!(unstake_window > 0, AstrolendError::InvalidConfig);
+
+    bank.insurance_share_mint = ctx.accounts.insurance_share_mint.key();
+    bank.insurance_unstake_cooldown = unstake_cooldown;
+    bank.insurance_unstake_window = unstake_window;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSetupInsuranceStaking<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        address = astrolend_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = bank.load()?.mint_decimals,
+        mint::authority = insurance_vault_authority,
+        mint::token_program = token_program,
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_share_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Stake `amount` into the bank's insurance fund and receive insurance share tokens.
+///
+/// The outstanding insurance fees of the bank are collected first, so the shares are priced
+/// with the fees earned by the current stakers.
+///
+/// Token22 banks require the bank mint as first remaining account.
+pub fn lending_pool_stake_insurance<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolStakeInsurance<'info>>,
+    amount: u64,
+) -> AstrolendResult {
+    let LendingPoolStakeInsurance {
+        bank: bank_loader,
+        staker,
+        staker_token_account,
+        liquidity_vault_authority,
+        liquidity_vault,
+        insurance_vault,
+        insurance_vault_authority,
+        insurance_share_mint,
+        staker_share_token_account,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let mut bank = bank_loader.load_mut()?;
+    let maybe_bank_mint =
+        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+
+    check!(
+        bank.insurance_share_mint != Pubkey::default(),
+        AstrolendError::InsuranceStakingNotSetup
+    );
+
+    bank.accrue_interest(
+        clock.unix_timestamp,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+    )?;
+
+    collect_insurance_fees(
+        &mut bank,
+        bank_loader.key(),
+        liquidity_vault,
+        liquidity_vault_authority.to_account_info(),
+        insurance_vault.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+    insurance_vault.reload()?;
+
+    let shares =
+        calc_insurance_shares_to_mint(amount, insurance_vault.amount, insurance_share_mint.supply)
+            .ok_or_else(math_error!())?;
+
+    check!(shares > 0, AstrolendError::InsuranceStakeTooSmall);
+
+    let amount_pre_fee = maybe_bank_mint
+        .as_ref()
+        .map(|mint| {
+            utils::calculate_pre_fee_spl_deposit_amount(mint.to_account_info(), amount, clock.epoch)
+        })
+        .transpose()?
+        .unwrap_or(amount);
+
+    bank.insurance_deposit_spl_transfer(
+        amount_pre_fee,
+        staker_token_account.to_account_info(),
+        insurance_vault.to_account_info(),
+        staker.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+
+    mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: insurance_share_mint.to_account_info(),
+                to: staker_share_token_account.to_account_info(),
+                authority: insurance_vault_authority.to_account_info(),
+            },
+            bank_signer!(
+                BankVaultType::Insurance,
+                bank_loader.key(),
+                bank.insurance_vault_authority_bump
+            ),
+        ),
+        shares,
+    )?;
+
+    emit!(LendingPoolBankInsuranceStakeEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(staker.key())
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        staker: staker.key(),
+        amount,
+        shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolStakeInsurance<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub staker: Signer<'info>,
+
+    /// CHECK: Token mint/authority are checked at transfer
+    #[account(mut)]
+    pub staker_token_account: AccountInfo<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump
+    )]
+    pub liquidity_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_share_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Token mint is checked at mint
+    #[account(mut)]
+    pub staker_share_token_account: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Move the outstanding insurance fees of the bank from the liquidity vault into the insurance
+/// vault, as far as the liquidity allows, as `lending_pool_collect_bank_fees` does.
+#[allow(clippy::too_many_arguments)]
+fn collect_insurance_fees<'info>(
+    bank: &mut Bank,
+    bank_pk: Pubkey,
+    liquidity_vault: &InterfaceAccount<'info, TokenAccount>,
+    liquidity_vault_authority: AccountInfo<'info>,
+    insurance_vault: AccountInfo<'info>,
+    maybe_bank_mint: Option<&InterfaceAccount<'info, Mint>>,
+    token_program: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> AstrolendResult {
+    let outstanding = I80F48::from(bank.collected_insurance_fees_outstanding);
+    let transfer_amount = min(outstanding, I80F48::from_num(liquidity_vault.amount)).int();
+
+    if transfer_amount == I80F48::ZERO {
+        return Ok(());
+    }
+
+    bank.collected_insurance_fees_outstanding = outstanding
+        .checked_sub(transfer_amount)
+        .ok_or_else(math_error!())?
+        .into();
+
+    bank.withdraw_spl_transfer(
+        transfer_amount.checked_to_num().ok_or_else(math_error!())?,
+        liquidity_vault.to_account_info(),
+        insurance_vault,
+        liquidity_vault_authority,
+        maybe_bank_mint,
+        token_program,
+        bank_signer!(
+            BankVaultType::Liquidity,
+            bank_pk,
+            bank.liquidity_vault_authority_bump
+        ),
+        remaining_accounts,
+    )
+}
+
+/// Start the unstake cooldown for `shares` insurance shares.
+///
+/// The shares are moved into an escrow owned by the program, so they can't be moved or restaked
+/// during the cooldown, and keep absorbing bad debt until the unstake is completed.
+pub fn lending_pool_request_insurance_unstake(
+    ctx: Context<LendingPoolRequestInsuranceUnstake>,
+    shares: u64,
+) -> AstrolendResult {
+    let bank = ctx.accounts.bank.load()?;
+
+    check!(
+        bank.insurance_share_mint != Pubkey::default(),
+        AstrolendError::InsuranceStakingNotSetup
+    );
+
+    check!(
+        shares > 0 && shares <= ctx.accounts.staker_share_token_account.amount,
+        AstrolendError::IllegalAction,
+        "Invalid amount of shares to unstake"
+    );
+
+    let cooldown_end = Clock::get()?
+        .unix_timestamp
+        .checked_add_unsigned(bank.insurance_unstake_cooldown)
+        .ok_or_else(math_error!())?;
+
+    transfer_checked(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.staker_share_token_account.to_account_info(),
+                to: ctx.accounts.unstake_escrow.to_account_info(),
+                authority: ctx.accounts.staker.to_account_info(),
+                mint: ctx.accounts.insurance_share_mint.to_account_info(),
+            },
+        ),
+        shares,
+        ctx.accounts.insurance_share_mint.decimals,
+    )?;
+
+    let mut unstake_request = ctx.accounts.unstake_request.load_init()?;
+
+    *unstake_request = InsuranceUnstakeRequest {
+        bank: ctx.accounts.bank.key(),
+        staker: ctx.accounts.staker.key(),
+        shares,
+        cooldown_end,
+        ..Default::default()
+    };
+
+    msg!(
+        "Requested unstake of {} insurance shares, executable after {}",
+        shares,
+        cooldown_end
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolRequestInsuranceUnstake<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = insurance_share_mint,
+        token::authority = staker,
+    )]
+    pub staker_share_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_share_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = staker,
+        space = 8 + InsuranceUnstakeRequest::LEN,
+        seeds = [
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_request: AccountLoader<'info, InsuranceUnstakeRequest>,
+
+    #[account(
+        init,
+        payer = staker,
+        token::mint = insurance_share_mint,
+        token::authority = insurance_vault_authority,
+        token::token_program = token_program,
+        seeds = [
+            INSURANCE_UNSTAKE_ESCROW_SEED.as_bytes(),
+            unstake_request.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Complete an unstake request within its window, burning the escrowed shares for their
+/// share of the insurance vault at the time of completion, the outstanding insurance fees of the
+/// bank collected.
+///
+/// Token22 banks require the bank mint as first remaining account.
+pub fn lending_pool_unstake_insurance<'info>(
+    mut ctx: Context<'_, '_, 'info, 'info, LendingPoolUnstakeInsurance<'info>>,
+) -> AstrolendResult {
+    let LendingPoolUnstakeInsurance {
+        bank: bank_loader,
+        staker,
+        liquidity_vault_authority,
+        liquidity_vault,
+        insurance_vault,
+        insurance_vault_authority,
+        insurance_share_mint,
+        destination_token_account,
+        unstake_request: unstake_request_loader,
+        unstake_escrow,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let clock = Clock::get()?;
+    let mut bank = bank_loader.load_mut()?;
+    let maybe_bank_mint =
+        utils::maybe_take_bank_mint(&mut ctx.remaining_accounts, &bank, token_program.key)?;
+
+    let shares = {
+        let unstake_request = unstake_request_loader.load()?;
+        let current_timestamp = clock.unix_timestamp;
+
+        check!(
+            current_timestamp >= unstake_request.cooldown_end,
+            AstrolendError::InsuranceUnstakeCooldownActive
+        );
+
+        let window_end = unstake_request
+            .cooldown_end
+            .checked_add_unsigned(bank.insurance_unstake_window)
+            .ok_or_else(math_error!())?;
+
+        check!(
+            current_timestamp <= window_end,
+            AstrolendError::InsuranceUnstakeWindowExpired
+        );
+
+        unstake_request.shares
+    };
+
+    bank.accrue_interest(
+        clock.unix_timestamp,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+    )?;
+
+    collect_insurance_fees(
+        &mut bank,
+        bank_loader.key(),
+        liquidity_vault,
+        liquidity_vault_authority.to_account_info(),
+        insurance_vault.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+    )?;
+    insurance_vault.reload()?;
+
+    let amount =
+        calc_insurance_shares_value(shares, insurance_vault.amount, insurance_share_mint.supply)
+            .ok_or_else(math_error!())?;
+
+    burn(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Burn {
+                mint: insurance_share_mint.to_account_info(),
+                from: unstake_escrow.to_account_info(),
+                authority: insurance_vault_authority.to_account_info(),
+            },
+            bank_signer!(
+                BankVaultType::Insurance,
+                bank_loader.key(),
+                bank.insurance_vault_authority_bump
+            ),
+        ),
+        shares,
+    )?;
+
+    close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: unstake_escrow.to_account_info(),
+            destination: staker.to_account_info(),
+            authority: insurance_vault_authority.to_account_info(),
+        },
+        bank_signer!(
+            BankVaultType::Insurance,
+            bank_loader.key(),
+            bank.insurance_vault_authority_bump
+        ),
+    ))?;
+
+    bank.withdraw_spl_transfer(
+        amount,
+        insurance_vault.to_account_info(),
+        destination_token_account.to_account_info(),
+        insurance_vault_authority.to_account_info(),
+        maybe_bank_mint.as_ref(),
+        token_program.to_account_info(),
+        bank_signer!(
+            BankVaultType::Insurance,
+            bank_loader.key(),
+            bank.insurance_vault_authority_bump
+        ),
+        ctx.remaining_accounts,
+    )?;
+
+    emit!(LendingPoolBankInsuranceUnstakeEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(staker.key())
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        staker: staker.key(),
+        amount,
+        shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolUnstakeInsurance<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump
+    )]
+    pub liquidity_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_bump
+    )]
+    pub insurance_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_share_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Token mint is checked at transfer
+    #[account(mut)]
+    pub destination_token_account: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        has_one = bank,
+        has_one = staker,
+        close = staker,
+        seeds = [
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_request: AccountLoader<'info, InsuranceUnstakeRequest>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_UNSTAKE_ESCROW_SEED.as_bytes(),
+            unstake_request.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Drop a pending unstake request, e.g. once its window has expired, returning the escrowed
+/// shares to the staker.
+pub fn lending_pool_cancel_insurance_unstake(
+    ctx: Context<LendingPoolCancelInsuranceUnstake>,
+) -> AstrolendResult {
+    let LendingPoolCancelInsuranceUnstake {
+        bank: bank_loader,
+        staker,
+        staker_share_token_account,
+        insurance_vault_authority,
+        insurance_share_mint,
+        unstake_escrow,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let bank = bank_loader.load()?;
+
+    transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: unstake_escrow.to_account_info(),
+                to: staker_share_token_account.to_account_info(),
+                authority: insurance_vault_authority.to_account_info(),
+                mint: insurance_share_mint.to_account_info(),
+            },
+            bank_signer!(
+                BankVaultType::Insurance,
+                bank_loader.key(),
+                bank.insurance_vault_authority_bump
+            ),
+        ),
+        unstake_escrow.amount,
+        insurance_share_mint.decimals,
+    )?;
+
+    close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: unstake_escrow.to_account_info(),
+            destination: staker.to_account_info(),
+            authority: insurance_vault_authority.to_account_info(),
+        },
+        bank_signer!(
+            BankVaultType::Insurance,
+            bank_loader.key(),
+            bank.insurance_vault_authority_bump
+        ),
+    ))?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolCancelInsuranceUnstake<'info> {
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = insurance_share_mint,
+        token::authority = staker,
+    )]
+    pub staker_share_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            INSURANCE_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.insurance_vault_authority_bump
+    )]
+    pub insurance_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        seeds = [
+            INSURANCE_SHARE_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub insurance_share_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        has_one = bank,
+        has_one = staker,
+        close = staker,
+        seeds = [
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank.key().as_ref(),
+            staker.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_request: AccountLoader<'info, InsuranceUnstakeRequest>,
+
+    #[account(
+        mut,
+        seeds = [
+            INSURANCE_UNSTAKE_ESCROW_SEED.as_bytes(),
+            unstake_request.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub unstake_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
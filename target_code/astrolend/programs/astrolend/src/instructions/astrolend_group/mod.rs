@@ -3,13 +3,21 @@ mod add_pool;
 mod collect_bank_fees;
 mod configure;
 mod configure_bank;
+mod configure_bank_timelock;
+mod group_reserve;
 mod handle_bankruptcy;
 mod initialize;
+mod insurance_staking;
+mod socialize_deficit;
 
 pub use accrue_bank_interest::*;
 pub use add_pool::*;
 pub use collect_bank_fees::*;
 pub use configure::*;
 pub use configure_bank::*;
+pub use configure_bank_timelock::*;
+pub use group_reserve::*;
 pub use handle_bankruptcy::*;
 pub use initialize::*;
+pub use insurance_staking::*;
+pub use socialize_deficit::*;
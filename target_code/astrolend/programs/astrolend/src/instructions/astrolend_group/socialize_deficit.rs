@@ -0,0 +1,56 @@
+use crate::{
+    events::{GroupEventHeader, LendingPoolBankSocializeDeficitEvent},
+    state::astrolend_group::{AstrolendGroup, Bank},
+    AstrolendResult,
+};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+
+/// Socialize the outstanding deficit of a bank among its depositors.
+///
+/// Used when the deficit can't reasonably be repaid from interest, e.g. when winding down a bank.
+///
+/// Admin only
+pub fn lending_pool_socialize_deficit(
+    ctx: Context<LendingPoolSocializeDeficit>,
+) -> AstrolendResult {
+    let clock = Clock::get()?;
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    bank.accrue_interest(
+        clock.unix_timestamp,
+        #[cfg(not(feature = "client"))]
+        ctx.accounts.bank.key(),
+    )?;
+
+    let deficit: I80F48 = bank.deficit.into();
+
+    bank.deficit = I80F48::ZERO.into();
+    bank.socialize_loss(deficit)?;
+
+    emit!(LendingPoolBankSocializeDeficitEvent {
+        header: GroupEventHeader {
+            astrolend_group: ctx.accounts.astrolend_group.key(),
+            signer: Some(ctx.accounts.admin.key()),
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: bank.mint,
+        amount: deficit.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSocializeDeficit<'info> {
+    pub astrolend_group: AccountLoader<'info, AstrolendGroup>,
+
+    #[account(address = astrolend_group.load()?.admin)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == astrolend_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+}
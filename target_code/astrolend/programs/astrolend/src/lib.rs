@@ -10,7 +10,9 @@ pub mod utils;
 use anchor_lang::prelude::*;
 use instructions::*;
 use prelude::*;
-use state::astrolend_group::{BankConfigCompact, BankConfigOpt};
+use state::astrolend_group::{
+    BankConfigCompact, BankConfigOpt, EmissionsScheduleConfig, WrappedI80F48,
+};
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "mainnet-beta")] {
@@ -64,6 +66,26 @@ pub mod astrolend {
         astrolend_group::lending_pool_configure_bank(ctx, bank_config_opt)
     }
 
+    pub fn lending_pool_queue_bank_config(
+        ctx: Context<LendingPoolQueueBankConfig>,
+        bank_config_opt: BankConfigOpt,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_queue_bank_config(ctx, bank_config_opt)
+    }
+
+    /// Permissionless, applies a queued bank config once the group's bank config delay has passed
+    pub fn lending_pool_execute_bank_config(
+        ctx: Context<LendingPoolExecuteBankConfig>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_execute_bank_config(ctx)
+    }
+
+    pub fn lending_pool_cancel_bank_config(
+        ctx: Context<LendingPoolCancelBankConfig>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_cancel_bank_config(ctx)
+    }
+
     pub fn lending_pool_setup_emissions(
         ctx: Context<LendingPoolSetupEmissions>,
         flags: u64,
@@ -87,6 +109,20 @@ pub mod astrolend {
         )
     }
 
+    pub fn lending_pool_update_emissions_schedule(
+        ctx: Context<LendingPoolUpdateEmissionsSchedule>,
+        schedule: EmissionsScheduleConfig,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_update_emissions_schedule(ctx, schedule)
+    }
+
+    /// End an emissions stream and return its undistributed emissions
+    pub fn lending_pool_clawback_emissions(
+        ctx: Context<LendingPoolClawbackEmissions>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_clawback_emissions(ctx)
+    }
+
     /// Handle bad debt of a bankrupt astrolend account for a given bank.
     pub fn lending_pool_handle_bankruptcy<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleBankruptcy<'info>>,
@@ -94,6 +130,29 @@ pub mod astrolend {
         astrolend_group::lending_pool_handle_bankruptcy(ctx)
     }
 
+    /// Handle bad debt of a bankrupt astrolend account for a given bank,
+    /// buying down the bad debt left after insurance with the group reserve.
+    pub fn lending_pool_handle_bankruptcy_with_reserve<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleBankruptcyWithReserve<'info>>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_handle_bankruptcy_with_reserve(ctx)
+    }
+
+    /// Handle bad debt of a bankrupt astrolend account across all of its liability banks.
+    pub fn lending_pool_handle_account_bankruptcy<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolHandleAccountBankruptcy<'info>>,
+        hook_accounts_lens: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_handle_account_bankruptcy(ctx, hook_accounts_lens)
+    }
+
+    /// Socialize the outstanding deficit of a bank among its depositors.
+    pub fn lending_pool_socialize_deficit(
+        ctx: Context<LendingPoolSocializeDeficit>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_socialize_deficit(ctx)
+    }
+
     // User instructions
 
     /// Initialize a astrolend account for a given group
@@ -103,6 +162,19 @@ pub mod astrolend {
         astrolend_account::initialize_account(ctx)
     }
 
+    /// Initialize a astrolend account at the PDA of the group, the authority and `account_index`
+    pub fn astrolend_account_initialize_pda(
+        ctx: Context<AstrolendAccountInitializePda>,
+        account_index: u16,
+    ) -> AstrolendResult {
+        astrolend_account::initialize_account_pda(ctx, account_index)
+    }
+
+    /// Expand an account created with 16 balances to the current number of balances
+    pub fn astrolend_account_migrate(ctx: Context<AstrolendAccountMigrate>) -> AstrolendResult {
+        astrolend_account::migrate_account(ctx)
+    }
+
     pub fn lending_account_deposit<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingAccountDeposit<'info>>,
         amount: u64,
@@ -151,6 +223,15 @@ pub mod astrolend {
         astrolend_account::lending_account_settle_emissions(ctx)
     }
 
+    /// Settle and withdraw the emissions of several balances, optionally depositing them
+    pub fn lending_account_claim_all_emissions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountClaimAllEmissions<'info>>,
+        deposit: bool,
+        emissions_bumps: Vec<[u8; 2]>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_claim_all_emissions(ctx, deposit, emissions_bumps)
+    }
+
     /// Liquidate a lending account balance of an unhealthy astrolend account
     pub fn lending_account_liquidate<'info>(
         ctx: Context<'_, '_, 'info, 'info, LendingAccountLiquidate<'info>>,
@@ -159,6 +240,14 @@ pub mod astrolend {
         astrolend_account::lending_account_liquidate(ctx, asset_amount)
     }
 
+    /// Partially liquidate an astrolend account in the soft liquidation zone at a reduced fee
+    pub fn lending_account_soft_liquidate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountLiquidate<'info>>,
+        asset_amount: u64,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_soft_liquidate(ctx, asset_amount)
+    }
+
     pub fn lending_account_start_flashloan(
         ctx: Context<LendingAccountStartFlashloan>,
         end_index: u64,
@@ -172,6 +261,138 @@ pub mod astrolend {
         astrolend_account::lending_account_end_flashloan(ctx)
     }
 
+    /// Borrow `amount` from a bank, hand control to `callback_program` and require the loan and
+    /// its fee to be repaid into the liquidity vault by the end of the instruction.
+    ///
+    /// Unlike `lending_account_start_flashloan`, can be invoked through CPI.
+    pub fn lending_account_flashloan_with_callback<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountFlashloanWithCallback<'info>>,
+        amount: u64,
+        callback_accounts_count: u8,
+        callback_data: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_flashloan_with_callback(
+            ctx,
+            amount,
+            callback_accounts_count,
+            callback_data,
+        )
+    }
+
+    /// Withdraw collateral from one bank, swap it through a group allow-listed swap program and
+    /// deposit the output into another bank, with a single health check at the end.
+    pub fn lending_account_swap_collateral<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountSwapCollateral<'info>>,
+        withdraw_amount: u64,
+        min_deposit_amount: u64,
+        swap_accounts_count: u8,
+        swap_data: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_swap_collateral(
+            ctx,
+            withdraw_amount,
+            min_deposit_amount,
+            swap_accounts_count,
+            swap_data,
+        )
+    }
+
+    /// Open or increase a leveraged position: borrow, swap into collateral through a group
+    /// allow-listed swap program and deposit it, with a single health check at the end. The caller
+    /// sizes `borrow_amount` for the leverage it targets.
+    pub fn lending_account_loop<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+        borrow_amount: u64,
+        min_deposit_amount: u64,
+        swap_accounts_count: u8,
+        swap_data: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_loop(
+            ctx,
+            borrow_amount,
+            min_deposit_amount,
+            swap_accounts_count,
+            swap_data,
+        )
+    }
+
+    /// Unwind a leveraged position: withdraw collateral, swap it into the liability through a
+    /// group allow-listed swap program and repay, closing the position if `close_position`.
+    pub fn lending_account_deleverage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+        withdraw_amount: u64,
+        min_repay_amount: u64,
+        close_position: bool,
+        swap_accounts_count: u8,
+        swap_data: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_deleverage(
+            ctx,
+            withdraw_amount,
+            min_repay_amount,
+            close_position,
+            swap_accounts_count,
+            swap_data,
+        )
+    }
+
+    /// Deleverage an account that fails the initial requirement, as long as its maintenance
+    /// health improves. No liquidation fee is charged.
+    pub fn lending_account_self_deleverage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountLoopPosition<'info>>,
+        withdraw_amount: u64,
+        min_repay_amount: u64,
+        close_position: bool,
+        swap_accounts_count: u8,
+        swap_data: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_self_deleverage(
+            ctx,
+            withdraw_amount,
+            min_repay_amount,
+            close_position,
+            swap_accounts_count,
+            swap_data,
+        )
+    }
+
+    /// Place a stop-loss order letting any keeper deleverage the account for a fee once its
+    /// maintenance health falls below `trigger_health`.
+    pub fn lending_account_place_deleverage_order(
+        ctx: Context<LendingAccountPlaceDeleverageOrder>,
+        trigger_health: WrappedI80F48,
+        withdraw_amount: u64,
+        min_repay_amount: u64,
+        keeper_fee_bps: u16,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_place_deleverage_order(
+            ctx,
+            trigger_health,
+            withdraw_amount,
+            min_repay_amount,
+            keeper_fee_bps,
+        )
+    }
+
+    pub fn lending_account_cancel_deleverage_order(
+        ctx: Context<LendingAccountCancelDeleverageOrder>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_cancel_deleverage_order(ctx)
+    }
+
+    /// Execute a triggered deleverage order, called by a keeper.
+    pub fn lending_account_execute_deleverage_order<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountExecuteDeleverageOrder<'info>>,
+        swap_accounts_count: u8,
+        swap_data: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_execute_deleverage_order(
+            ctx,
+            swap_accounts_count,
+            swap_data,
+        )
+    }
+
     // Operational instructions
     pub fn lending_pool_accrue_bank_interest(
         ctx: Context<LendingPoolAccrueBankInterest>,
@@ -199,6 +420,57 @@ pub mod astrolend {
         astrolend_group::lending_pool_withdraw_insurance(ctx, amount)
     }
 
+    pub fn lending_pool_setup_insurance_staking(
+        ctx: Context<LendingPoolSetupInsuranceStaking>,
+        unstake_cooldown: u64,
+        unstake_window: u64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_setup_insurance_staking(
+            ctx,
+            unstake_cooldown,
+            unstake_window,
+        )
+    }
+
+    pub fn lending_pool_stake_insurance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolStakeInsurance<'info>>,
+        amount: u64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_stake_insurance(ctx, amount)
+    }
+
+    pub fn lending_pool_request_insurance_unstake(
+        ctx: Context<LendingPoolRequestInsuranceUnstake>,
+        shares: u64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_request_insurance_unstake(ctx, shares)
+    }
+
+    pub fn lending_pool_unstake_insurance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingPoolUnstakeInsurance<'info>>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_unstake_insurance(ctx)
+    }
+
+    pub fn lending_pool_cancel_insurance_unstake(
+        ctx: Context<LendingPoolCancelInsuranceUnstake>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_cancel_insurance_unstake(ctx)
+    }
+
+    pub fn lending_pool_setup_group_reserve(
+        ctx: Context<LendingPoolSetupGroupReserve>,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_setup_group_reserve(ctx)
+    }
+
+    pub fn lending_pool_withdraw_group_reserve(
+        ctx: Context<LendingPoolWithdrawGroupReserve>,
+        amount: u64,
+    ) -> AstrolendResult {
+        astrolend_group::lending_pool_withdraw_group_reserve(ctx, amount)
+    }
+
     pub fn set_account_flag(ctx: Context<SetAccountFlag>, flag: u64) -> AstrolendResult {
         astrolend_group::set_account_flag(ctx, flag)
     }
@@ -213,9 +485,94 @@ pub mod astrolend {
         astrolend_account::set_account_transfer_authority(ctx)
     }
 
+    /// Offer the account to a new authority, which takes it over by accepting the offer
+    pub fn astrolend_account_offer_authority_transfer(
+        ctx: Context<AstrolendAccountOfferAuthorityTransfer>,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_offer_authority_transfer(ctx)
+    }
+
+    pub fn astrolend_account_cancel_authority_transfer(
+        ctx: Context<AstrolendAccountCancelAuthorityTransfer>,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_cancel_authority_transfer(ctx)
+    }
+
+    pub fn astrolend_account_accept_authority_transfer(
+        ctx: Context<AstrolendAccountAcceptAuthorityTransfer>,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_accept_authority_transfer(ctx)
+    }
+
+    /// Block withdrawals and borrows of the account, deposits and repayments are still allowed
+    pub fn astrolend_account_freeze(ctx: Context<AstrolendAccountFreeze>) -> AstrolendResult {
+        astrolend_account::astrolend_account_freeze(ctx)
+    }
+
+    pub fn astrolend_account_unfreeze(ctx: Context<AstrolendAccountFreeze>) -> AstrolendResult {
+        astrolend_account::astrolend_account_unfreeze(ctx)
+    }
+
+    /// Set a key able to unfreeze the account and rotate its authority
+    pub fn astrolend_account_set_recovery_authority(
+        ctx: Context<AstrolendAccountSetRecoveryAuthority>,
+        recovery_authority: Pubkey,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_set_recovery_authority(ctx, recovery_authority)
+    }
+
+    pub fn astrolend_account_recover_authority(
+        ctx: Context<AstrolendAccountRecoverAuthority>,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_recover_authority(ctx)
+    }
+
+    /// Move a balance to another account of the same authority in the group, without token
+    /// transfers, checking the health of both accounts
+    pub fn lending_account_transfer_balance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, LendingAccountTransferBalance<'info>>,
+        amount: u64,
+        transfer_all: Option<bool>,
+    ) -> AstrolendResult {
+        astrolend_account::lending_account_transfer_balance(ctx, amount, transfer_all)
+    }
+
     pub fn astrolend_account_close(ctx: Context<AstrolendAccountClose>) -> AstrolendResult {
         astrolend_account::close_account(ctx)
     }
+
+    /// Withdraw the remaining assets, repay the remaining liabilities and close the account,
+    /// sending the rent to a recipient chosen by the authority
+    pub fn astrolend_account_sweep_and_close<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AstrolendAccountSweepAndClose<'info>>,
+        hook_accounts_lens: Vec<u8>,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_sweep_and_close(ctx, hook_accounts_lens)
+    }
+
+    /// Allow a delegate to deposit, repay, withdraw or withdraw emissions on the account,
+    /// optionally until `expiry`
+    pub fn astrolend_account_grant_delegate(
+        ctx: Context<AstrolendAccountGrantDelegate>,
+        permissions: u64,
+        expiry: i64,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_grant_delegate(ctx, permissions, expiry)
+    }
+
+    pub fn astrolend_account_update_delegate(
+        ctx: Context<AstrolendAccountUpdateDelegate>,
+        permissions: u64,
+        expiry: i64,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_update_delegate(ctx, permissions, expiry)
+    }
+
+    pub fn astrolend_account_revoke_delegate(
+        ctx: Context<AstrolendAccountRevokeDelegate>,
+    ) -> AstrolendResult {
+        astrolend_account::astrolend_account_revoke_delegate(ctx)
+    }
 }
 
 #[cfg(not(feature = "no-entrypoint"))]
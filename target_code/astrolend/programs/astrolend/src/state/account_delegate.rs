@@ -0,0 +1,115 @@
+use crate::{
+    assert_struct_align, assert_struct_size, check,
+    prelude::{AstrolendError, AstrolendResult},
+};
+use anchor_lang::prelude::*;
+
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+/// Deposit into and repay the account, from the delegate's own token accounts.
+pub const DELEGATE_DEPOSIT_REPAY_PERMISSION: u64 = 1 << 0;
+/// Withdraw from the account, only to token accounts owned by the account authority.
+pub const DELEGATE_WITHDRAW_PERMISSION: u64 = 1 << 1;
+/// Withdraw emissions, only to token accounts owned by the account authority.
+pub const DELEGATE_WITHDRAW_EMISSIONS_PERMISSION: u64 = 1 << 2;
+
+pub(crate) const DELEGATE_PERMISSIONS: u64 = DELEGATE_DEPOSIT_REPAY_PERMISSION
+    | DELEGATE_WITHDRAW_PERMISSION
+    | DELEGATE_WITHDRAW_EMISSIONS_PERMISSION;
+
+/// A key the account authority allowed to act on an astrolend account with scoped permissions.
+///
+/// PDA: [ACCOUNT_DELEGATE_SEED, astrolend_account, delegate]
+assert_struct_size!(AstrolendAccountDelegate, 120);
+assert_struct_align!(AstrolendAccountDelegate, 8);
+#[account(zero_copy)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+pub struct AstrolendAccountDelegate {
+    pub astrolend_account: Pubkey,
+    pub delegate: Pubkey,
+    /// The account authority that granted the permissions, the delegate can't act once the
+    /// account has another authority.
+    pub authority: Pubkey,
+    /// Bitfield of `DELEGATE_*_PERMISSION` flags.
+    pub permissions: u64,
+    /// Unix timestamp after which the delegate can no longer act, zero for no expiry.
+    pub expiry: i64,
+    pub _padding: [u64; 1],
+}
+
+impl AstrolendAccountDelegate {
+    pub const LEN: usize = std::mem::size_of::<AstrolendAccountDelegate>();
+
+    pub fn configure(
+        &mut self,
+        permissions: u64,
+        expiry: i64,
+        current_timestamp: i64,
+    ) -> AstrolendResult {
+        check!(
+            permissions != 0 && (permissions & !DELEGATE_PERMISSIONS) == 0,
+            AstrolendError::IllegalFlag
+        );
+        check!(
+            expiry == 0 || expiry > current_timestamp,
+            AstrolendError::InvalidConfig
+        );
+
+        self.permissions = permissions;
+        self.expiry = expiry;
+
+        Ok(())
+    }
+
+    pub fn is_authorized(&self, permission: u64, current_timestamp: i64) -> bool {
+        self.permissions & permission == permission
+            && (self.expiry == 0 || current_timestamp <= self.expiry)
+    }
+}
+
+/// Check that `signer` can act on the astrolend account with `permission`.
+///
+/// The authority always can. Any other signer must pass its delegate record as first remaining
+/// account, which is then taken from `remaining_accounts`. Returns whether the signer is a
+/// delegate.
+pub fn check_signer_permission<'info>(
+    remaining_accounts: &mut &'info [AccountInfo<'info>],
+    astrolend_account: &Pubkey,
+    authority: &Pubkey,
+    signer: &Pubkey,
+    permission: u64,
+) -> AstrolendResult<bool> {
+    if signer == authority {
+        return Ok(false);
+    }
+
+    let (delegate_ai, remaining) = remaining_accounts
+        .split_first()
+        .ok_or(AstrolendError::Unauthorized)?;
+    *remaining_accounts = remaining;
+
+    let delegate_loader = AccountLoader::<AstrolendAccountDelegate>::try_from(delegate_ai)?;
+    let delegate = delegate_loader.load()?;
+
+    check!(
+        delegate.astrolend_account == *astrolend_account && delegate.delegate == *signer,
+        AstrolendError::Unauthorized
+    );
+    check!(
+        delegate.authority == *authority,
+        AstrolendError::Unauthorized,
+        "Delegate granted by a previous account authority"
+    );
+    check!(
+        delegate.is_authorized(permission, Clock::get()?.unix_timestamp),
+        AstrolendError::Unauthorized,
+        "Delegate permission missing or expired"
+    );
+
+    Ok(true)
+}
@@ -6,8 +6,9 @@ use crate::{
     assert_struct_align, assert_struct_size, check,
     constants::{
         BANKRUPT_THRESHOLD, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
-        EMPTY_BALANCE_THRESHOLD, EXP_10_I80F48, MIN_EMISSIONS_START_TIME, SECONDS_PER_YEAR,
-        ZERO_AMOUNT_THRESHOLD,
+        EMISSIONS_FLAG_PRO_RATA, EMPTY_BALANCE_THRESHOLD, EXP_10_I80F48,
+        FLASHLOAN_BORROW_DISABLED_FLAG, MAX_EMISSIONS_STREAMS, MIN_EMISSIONS_START_TIME,
+        SECONDS_PER_YEAR, SOFT_LIQUIDATION_WINDOW, ZERO_AMOUNT_THRESHOLD,
     },
     debug, math_error,
     prelude::{AstrolendError, AstrolendResult},
@@ -23,7 +24,7 @@ use std::{
 #[cfg(any(feature = "test", feature = "client"))]
 use type_layout::TypeLayout;
 
-assert_struct_size!(AstrolendAccount, 2304);
+assert_struct_size!(AstrolendAccount, 3264);
 assert_struct_align!(AstrolendAccount, 8);
 #[account(zero_copy(unsafe))]
 #[repr(C)]
@@ -34,21 +35,39 @@ assert_struct_align!(AstrolendAccount, 8);
 pub struct AstrolendAccount {
     pub group: Pubkey,                   // 32
     pub authority: Pubkey,               // 32
-    pub lending_account: LendingAccount, // 1728
+    pub lending_account: LendingAccount, // 2560
     /// The flags that indicate the state of the account.
     /// This is u64 bitfield, where each bit represents a flag.
     ///
     /// Flags:
     /// - DISABLED_FLAG = 1 << 0 = 1 - This flag indicates that the account is disabled,
     /// and no further actions can be taken on it.
+    /// - FROZEN_FLAG = 1 << 4 = 16 - Set by the user, withdrawals and borrows are blocked
+    /// while deposits and repayments are still allowed.
     pub account_flags: u64, // 8
-    pub _padding: [u64; 63],             // 504
+    /// Outstanding emissions of the additional emissions streams, by balance index, or the
+    /// checkpoints of the balances for streams in pro-rata mode.
+    /// The primary stream is tracked in `Balance::emissions_outstanding`.
+    pub emissions_outstanding:
+        [[WrappedI80F48; MAX_EMISSIONS_STREAMS - 1]; MAX_LENDING_ACCOUNT_BALANCES], // 384
+    /// Start of the current soft liquidation window, see `record_soft_liquidation`.
+    pub soft_liquidation_window_start: i64, // 8
+    /// Value of the assets soft liquidated since `soft_liquidation_window_start`.
+    pub soft_liquidation_window_value: WrappedI80F48, // 16
+    /// Authority the account was offered to with `offer_authority_transfer`, or the default
+    /// pubkey if there is no pending offer.
+    pub pending_authority: Pubkey, // 32
+    /// Optional key able to unfreeze the account and rotate its authority, default pubkey if
+    /// not set.
+    pub recovery_authority: Pubkey, // 32
+    pub _padding: [u64; 20],             // 160
 }
 
 pub const DISABLED_FLAG: u64 = 1 << 0;
 pub const IN_FLASHLOAN_FLAG: u64 = 1 << 1;
 pub const FLASHLOAN_ENABLED_FLAG: u64 = 1 << 2;
 pub const TRANSFER_AUTHORITY_ALLOWED_FLAG: u64 = 1 << 3;
+pub const FROZEN_FLAG: u64 = 1 << 4;
 
 impl AstrolendAccount {
     /// Set the initial data for the astrolend account.
@@ -85,10 +104,13 @@ impl AstrolendAccount {
         if !self.get_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG) || self.get_flag(DISABLED_FLAG) {
             return Err(AstrolendError::IllegalAccountAuthorityTransfer.into());
         }
+        check!(!self.get_flag(FROZEN_FLAG), AstrolendError::AccountFrozen);
 
-        // update account authority
+        // update account authority, the recovery authority was set by the previous authority
         let old_authority = self.authority;
         self.authority = new_authority;
+        self.pending_authority = Pubkey::default();
+        self.recovery_authority = Pubkey::default();
 
         // unset flag after updating the account authority
         self.unset_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG);
@@ -102,6 +124,224 @@ impl AstrolendAccount {
         Ok(())
     }
 
+    /// Offer the account to `new_authority`, replacing any pending offer. The transfer only
+    /// happens once `new_authority` accepts it with `accept_authority_transfer`.
+    pub fn offer_authority_transfer(&mut self, new_authority: Pubkey) -> AstrolendResult {
+        check!(
+            !self.get_flag(DISABLED_FLAG),
+            AstrolendError::IllegalAccountAuthorityTransfer
+        );
+        check!(!self.get_flag(FROZEN_FLAG), AstrolendError::AccountFrozen);
+        check!(
+            new_authority != self.authority && new_authority != Pubkey::default(),
+            AstrolendError::IllegalAccountAuthorityTransfer
+        );
+
+        self.pending_authority = new_authority;
+
+        Ok(())
+    }
+
+    /// Withdraw the pending authority transfer offer, if any.
+    pub fn cancel_authority_transfer(&mut self) {
+        self.pending_authority = Pubkey::default();
+    }
+
+    /// Hand the account over to `new_authority`, which must be the pending authority, removing
+    /// the recovery authority set by the previous authority. Returns the previous authority.
+    pub fn accept_authority_transfer(&mut self, new_authority: Pubkey) -> AstrolendResult<Pubkey> {
+        check!(
+            !self.get_flag(DISABLED_FLAG),
+            AstrolendError::IllegalAccountAuthorityTransfer
+        );
+        check!(!self.get_flag(FROZEN_FLAG), AstrolendError::AccountFrozen);
+        check!(
+            self.pending_authority != Pubkey::default() && self.pending_authority == new_authority,
+            AstrolendError::IllegalAccountAuthorityTransfer
+        );
+
+        let old_authority = self.authority;
+        self.authority = new_authority;
+        self.pending_authority = Pubkey::default();
+        self.recovery_authority = Pubkey::default();
+
+        msg!(
+            "Transferred account authority from {:?} to {:?} in group {:?}",
+            old_authority,
+            self.authority,
+            self.group,
+        );
+
+        Ok(old_authority)
+    }
+
+    /// Freeze withdrawals and borrows, `signer` must be the authority or the recovery authority.
+    pub fn freeze(&mut self, signer: &Pubkey) -> AstrolendResult {
+        check!(
+            *signer == self.authority
+                || (self.recovery_authority != Pubkey::default()
+                    && *signer == self.recovery_authority),
+            AstrolendError::Unauthorized
+        );
+
+        self.set_flag(FROZEN_FLAG);
+
+        Ok(())
+    }
+
+    /// Lift a freeze. Once a recovery authority is set only it can unfreeze the account, so a
+    /// compromised authority can't undo the freeze.
+    pub fn unfreeze(&mut self, signer: &Pubkey) -> AstrolendResult {
+        let unfreeze_authority = if self.recovery_authority == Pubkey::default() {
+            self.authority
+        } else {
+            self.recovery_authority
+        };
+        check!(*signer == unfreeze_authority, AstrolendError::Unauthorized);
+
+        self.unset_flag(FROZEN_FLAG);
+
+        Ok(())
+    }
+
+    /// Set the recovery authority, the default pubkey removes it. Not allowed while frozen.
+    pub fn set_recovery_authority(&mut self, recovery_authority: Pubkey) -> AstrolendResult {
+        check!(!self.get_flag(FROZEN_FLAG), AstrolendError::AccountFrozen);
+        check!(
+            recovery_authority != self.authority,
+            AstrolendError::IllegalAction,
+            "Recovery authority must differ from the authority"
+        );
+
+        self.recovery_authority = recovery_authority;
+
+        Ok(())
+    }
+
+    /// Rotate the authority on behalf of the recovery authority, cancelling any pending
+    /// authority transfer. Returns the previous authority.
+    pub fn recover_authority(&mut self, new_authority: Pubkey) -> AstrolendResult<Pubkey> {
+        check!(
+            !self.get_flag(DISABLED_FLAG)
+                && new_authority != Pubkey::default()
+                && new_authority != self.recovery_authority,
+            AstrolendError::IllegalAccountAuthorityTransfer
+        );
+
+        let old_authority = self.authority;
+        self.authority = new_authority;
+        self.pending_authority = Pubkey::default();
+
+        msg!(
+            "Recovered account authority from {:?} to {:?} in group {:?}",
+            old_authority,
+            self.authority,
+            self.group,
+        );
+
+        Ok(old_authority)
+    }
+
+    /// Reallocate `astrolend_account_ai` and move its fields to the current layout if it is still
+    /// in the legacy layout, so it can be loaded. Used as the first constraint of the accounts
+    /// written by an instruction, always true.
+    ///
+    /// The account must already hold the rent of the current size, e.g. transferred to it earlier
+    /// in the transaction, or the transaction fails the rent check. `astrolend_account_migrate`
+    /// funds it instead.
+    pub fn migrate_if_legacy(astrolend_account_ai: &AccountInfo) -> AstrolendResult<bool> {
+        if astrolend_account_ai.data_len() != 8 + LEGACY_ASTROLEND_ACCOUNT_SIZE {
+            return Ok(true);
+        }
+
+        check!(
+            astrolend_account_ai.is_writable,
+            AstrolendError::IllegalAction,
+            "Legacy account must be writable to be migrated"
+        );
+
+        astrolend_account_ai.realloc(8 + std::mem::size_of::<AstrolendAccount>(), true)?;
+        Self::migrate_legacy_layout(&mut astrolend_account_ai.try_borrow_mut_data()?[8..])?;
+
+        msg!("Migrated account {:?}", astrolend_account_ai.key());
+
+        Ok(true)
+    }
+
+    /// Move the fields of an account created with `LEGACY_MAX_LENDING_ACCOUNT_BALANCES` balances
+    /// to the current layout. `data` is the account data after the discriminator, already
+    /// reallocated to the current size.
+    ///
+    /// Only the balances and the per-balance emissions grow, the fields following each of them
+    /// are shifted and the new slots are zeroed, which is an inactive balance.
+    pub fn migrate_legacy_layout(data: &mut [u8]) -> AstrolendResult {
+        check!(
+            data.len() == std::mem::size_of::<AstrolendAccount>(),
+            AstrolendError::IllegalAction
+        );
+
+        let balance_len = std::mem::size_of::<Balance>();
+        let emissions_len = std::mem::size_of::<[WrappedI80F48; MAX_EMISSIONS_STREAMS - 1]>();
+        // `LendingAccount::_padding` and `account_flags`, between the balances and the emissions
+        let middle_len = std::mem::size_of::<[u64; 8]>() + std::mem::size_of::<u64>();
+
+        let balances_start = 2 * std::mem::size_of::<Pubkey>();
+        let legacy_balances_end =
+            balances_start + LEGACY_MAX_LENDING_ACCOUNT_BALANCES * balance_len;
+        let balances_end = balances_start + MAX_LENDING_ACCOUNT_BALANCES * balance_len;
+
+        let legacy_emissions_start = legacy_balances_end + middle_len;
+        let legacy_emissions_len = LEGACY_MAX_LENDING_ACCOUNT_BALANCES * emissions_len;
+        let emissions_start = balances_end + middle_len;
+
+        // Fields are moved to higher offsets, last first so nothing is overwritten before it moves
+        data.copy_within(
+            legacy_emissions_start..legacy_emissions_start + legacy_emissions_len,
+            emissions_start,
+        );
+        data[emissions_start + legacy_emissions_len..].fill(0);
+
+        data.copy_within(
+            legacy_balances_end..legacy_balances_end + middle_len,
+            balances_end,
+        );
+        data[legacy_balances_end..balances_end].fill(0);
+
+        Ok(())
+    }
+
+    /// Record `value` of assets soft liquidated at `current_timestamp`, failing if the value soft
+    /// liquidated in the current `SOFT_LIQUIDATION_WINDOW` would exceed `max_value`.
+    pub fn record_soft_liquidation(
+        &mut self,
+        value: I80F48,
+        max_value: I80F48,
+        current_timestamp: i64,
+    ) -> AstrolendResult {
+        if current_timestamp >= self.soft_liquidation_window_start + SOFT_LIQUIDATION_WINDOW {
+            self.soft_liquidation_window_start = current_timestamp;
+            self.soft_liquidation_window_value = I80F48::ZERO.into();
+        }
+
+        let window_value = I80F48::from(self.soft_liquidation_window_value)
+            .checked_add(value)
+            .ok_or_else(math_error!())?;
+
+        debug!(
+            "soft_liquidation_window_value: {}, max: {}",
+            window_value, max_value
+        );
+
+        check!(
+            window_value <= max_value,
+            AstrolendError::SoftLiquidationCapExceeded
+        );
+
+        self.soft_liquidation_window_value = window_value.into();
+
+        Ok(())
+    }
+
     pub fn can_be_closed(&self) -> bool {
         let is_disabled = self.get_flag(DISABLED_FLAG);
         let only_has_empty_balances = self
@@ -126,6 +366,9 @@ pub enum BalanceIncreaseType {
 pub enum BalanceDecreaseType {
     Any,
     WithdrawOnly,
+    /// Withdraw only, at the nominal value of the deposits, which are moved to another balance
+    /// of the same bank rather than paid out.
+    WithdrawNominal,
     BorrowOnly,
     BypassBorrowLimit,
 }
@@ -134,6 +377,7 @@ pub enum BalanceDecreaseType {
 pub enum RequirementType {
     Initial,
     Maintenance,
+    SoftLiquidation,
     Equity,
 }
 
@@ -141,11 +385,13 @@ impl RequirementType {
     /// Get oracle price type for the requirement type.
     ///
     /// Initial and equity requirements use the time weighted price feed.
-    /// Maintenance requirement uses the real time price feed, as its more accurate for triggering liquidations.
+    /// Maintenance and soft liquidation requirements use the real time price feed, as its more accurate for triggering liquidations.
     pub fn get_oracle_price_type(&self) -> OraclePriceType {
         match self {
             RequirementType::Initial | RequirementType::Equity => OraclePriceType::TimeWeighted,
-            RequirementType::Maintenance => OraclePriceType::RealTime,
+            RequirementType::Maintenance | RequirementType::SoftLiquidation => {
+                OraclePriceType::RealTime
+            }
         }
     }
 }
@@ -162,6 +408,8 @@ pub enum BalanceSide {
 }
 
 impl<'info> BankAccountWithPriceFeed<'_, 'info> {
+    /// Only active balances are loaded, the cost depends on the open positions of the account
+    /// rather than on its number of balance slots.
     pub fn load<'a>(
         lending_account: &'a LendingAccount,
         remaining_ais: &'info [AccountInfo<'info>],
@@ -169,21 +417,20 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
         let active_balances = lending_account
             .balances
             .iter()
-            .filter(|balance| balance.active)
-            .collect::<Vec<_>>();
+            .filter(|balance| balance.active);
+        let active_balances_len = active_balances.clone().count();
 
-        debug!("Expecting {} remaining accounts", active_balances.len() * 2);
+        debug!("Expecting {} remaining accounts", active_balances_len * 2);
         debug!("Got {} remaining accounts", remaining_ais.len());
 
         check!(
-            active_balances.len() * 2 <= remaining_ais.len(),
+            active_balances_len * 2 <= remaining_ais.len(),
             AstrolendError::MissingPythOrBankAccount
         );
 
         let clock = Clock::get()?;
 
         active_balances
-            .iter()
             .enumerate()
             .map(|(i, balance)| {
                 let bank_index = i * 2;
@@ -217,6 +464,30 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
             .collect::<Result<Vec<_>>>()
     }
 
+    /// Price of `bank_pk` from the [bank, oracle] pairs of `remaining_ais`, laid out as for
+    /// `load`. The bank doesn't need to have a balance in the account.
+    pub fn load_bank_price(
+        remaining_ais: &'info [AccountInfo<'info>],
+        bank_pk: &Pubkey,
+        price_type: OraclePriceType,
+        bias: Option<PriceBias>,
+    ) -> AstrolendResult<I80F48> {
+        let bank_oracle_ais = remaining_ais
+            .chunks_exact(2)
+            .find(|ais| ais[0].key == bank_pk)
+            .ok_or(AstrolendError::MissingPythOrBankAccount)?;
+
+        let bank_al = AccountLoader::<Bank>::try_from(&bank_oracle_ais[0])?;
+        let bank = bank_al.load()?;
+
+        OraclePriceFeedAdapter::try_from_bank_config(
+            &bank.config,
+            &bank_oracle_ais[1..],
+            &Clock::get()?,
+        )?
+        .get_price_of_type(price_type, bias)
+    }
+
     #[inline(always)]
     /// Calculate the value of the assets and liabilities of the account in the form of (assets, liabilities)
     ///
@@ -293,6 +564,13 @@ impl<'info> BankAccountWithPriceFeed<'_, 'info> {
                     }
                 }
 
+                // Deposits of a bank with a deficit are only partly backed.
+                if let Some(discount) = bank.maybe_get_deficit_discount()? {
+                    asset_weight = asset_weight
+                        .checked_mul(discount)
+                        .ok_or_else(math_error!())?;
+                }
+
                 calc_value(
                     bank.get_asset_amount(self.balance.asset_shares.into())?,
                     lower_price,
@@ -408,6 +686,7 @@ pub fn calc_amount(value: I80F48, price: I80F48, mint_decimals: u8) -> Astrolend
 pub enum RiskRequirementType {
     Initial,
     Maintenance,
+    SoftLiquidation,
     Equity,
 }
 
@@ -416,6 +695,7 @@ impl RiskRequirementType {
         match self {
             RiskRequirementType::Initial => RequirementType::Initial,
             RiskRequirementType::Maintenance => RequirementType::Maintenance,
+            RiskRequirementType::SoftLiquidation => RequirementType::SoftLiquidation,
             RiskRequirementType::Equity => RequirementType::Equity,
         }
     }
@@ -536,6 +816,36 @@ impl<'info> RiskEngine<'_, 'info> {
     pub fn check_pre_liquidation_condition_and_get_account_health(
         &self,
         bank_pk: &Pubkey,
+    ) -> AstrolendResult<I80F48> {
+        self.check_pre_liquidation_condition(bank_pk, RiskRequirementType::Maintenance)
+    }
+
+    /// Checks
+    /// 1. Account is in the soft liquidation zone, failing the soft liquidation requirement but
+    ///    not the maintenance requirement
+    /// 2. Account has an outstanding liability for the provided liability bank
+    ///
+    /// Returns the soft liquidation health of the account.
+    pub fn check_pre_soft_liquidation_condition_and_get_account_health(
+        &self,
+        bank_pk: &Pubkey,
+    ) -> AstrolendResult<I80F48> {
+        let (assets, liabs) =
+            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        check!(
+            assets > liabs,
+            AstrolendError::IllegalLiquidation,
+            "Account below maintenance requirement"
+        );
+
+        self.check_pre_liquidation_condition(bank_pk, RiskRequirementType::SoftLiquidation)
+    }
+
+    fn check_pre_liquidation_condition(
+        &self,
+        bank_pk: &Pubkey,
+        requirement_type: RiskRequirementType,
     ) -> AstrolendResult<I80F48> {
         check!(
             !self.astrolend_account.get_flag(IN_FLASHLOAN_FLAG),
@@ -560,8 +870,7 @@ impl<'info> RiskEngine<'_, 'info> {
             AstrolendError::IllegalLiquidation
         );
 
-        let (assets, liabs) =
-            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+        let (assets, liabs) = self.get_account_health_components(requirement_type)?;
 
         let account_health = assets.checked_sub(liabs).ok_or_else(math_error!())?;
 
@@ -593,6 +902,33 @@ impl<'info> RiskEngine<'_, 'info> {
         &self,
         bank_pk: &Pubkey,
         pre_liquidation_health: I80F48,
+    ) -> AstrolendResult<I80F48> {
+        self.check_post_liquidation_condition(
+            bank_pk,
+            pre_liquidation_health,
+            RiskRequirementType::Maintenance,
+        )
+    }
+
+    /// Same as `check_post_liquidation_condition_and_get_account_health` against the soft
+    /// liquidation requirement, so a soft liquidation can't bring the account above it.
+    pub fn check_post_soft_liquidation_condition_and_get_account_health(
+        &self,
+        bank_pk: &Pubkey,
+        pre_liquidation_health: I80F48,
+    ) -> AstrolendResult<I80F48> {
+        self.check_post_liquidation_condition(
+            bank_pk,
+            pre_liquidation_health,
+            RiskRequirementType::SoftLiquidation,
+        )
+    }
+
+    fn check_post_liquidation_condition(
+        &self,
+        bank_pk: &Pubkey,
+        pre_liquidation_health: I80F48,
+        requirement_type: RiskRequirementType,
     ) -> AstrolendResult<I80F48> {
         check!(
             !self.astrolend_account.get_flag(IN_FLASHLOAN_FLAG),
@@ -619,15 +955,14 @@ impl<'info> RiskEngine<'_, 'info> {
             "Liability payoff too severe, liability balance has assets"
         );
 
-        let (assets, liabs) =
-            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+        let (assets, liabs) = self.get_account_health_components(requirement_type)?;
 
         let account_health = assets.checked_sub(liabs).ok_or_else(math_error!())?;
 
         check!(
             account_health <= I80F48::ZERO,
             AstrolendError::IllegalLiquidation,
-            "Liquidation too severe, account above maintenance requirement"
+            "Liquidation too severe, account above liquidation requirement"
         );
 
         debug!(
@@ -644,6 +979,53 @@ impl<'info> RiskEngine<'_, 'info> {
         Ok(account_health)
     }
 
+    /// Check that the maintenance health of the account is below `trigger_health`, the condition
+    /// of a deleverage order, and return it.
+    pub fn check_deleverage_order_condition_and_get_account_health(
+        &self,
+        trigger_health: I80F48,
+    ) -> AstrolendResult<I80F48> {
+        let (assets, liabs) =
+            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        let account_health = assets.checked_sub(liabs).ok_or_else(math_error!())?;
+
+        debug!(
+            "deleverage_order_health: {} ({} - {}), trigger: {}",
+            account_health, assets, liabs, trigger_health
+        );
+
+        check!(
+            account_health < trigger_health,
+            AstrolendError::DeleverageOrderNotTriggered
+        );
+
+        Ok(account_health)
+    }
+
+    /// Check that the maintenance health of the account improved since `pre_health`, after an
+    /// action allowed regardless of the initial requirement because it reduces risk.
+    pub fn check_account_health_improved(&self, pre_health: I80F48) -> AstrolendResult<I80F48> {
+        let (assets, liabs) =
+            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        let account_health = assets.checked_sub(liabs).ok_or_else(math_error!())?;
+
+        debug!(
+            "account_health: {} ({} - {}), pre_health: {}",
+            account_health, assets, liabs, pre_health
+        );
+
+        check!(
+            account_health > pre_health,
+            AstrolendError::AccountHealthNotImproved
+        );
+
+        self.check_account_risk_tiers()?;
+
+        Ok(account_health)
+    }
+
     /// Check that the account is in a bankrupt state.
     /// Account needs to be insolvent and total value of assets need to be below the bankruptcy threshold.
     pub fn check_account_bankrupt(&self) -> AstrolendResult {
@@ -705,9 +1087,16 @@ impl<'info> RiskEngine<'_, 'info> {
     }
 }
 
-const MAX_LENDING_ACCOUNT_BALANCES: usize = 16;
+/// Keeps the bank and oracle accounts of a health check of a full account, 2 per balance, within
+/// the accounts a transaction can lock.
+pub const MAX_LENDING_ACCOUNT_BALANCES: usize = 24;
+/// Balances of accounts created before the lending account was expanded, see
+/// `AstrolendAccount::migrate_legacy_layout`.
+pub const LEGACY_MAX_LENDING_ACCOUNT_BALANCES: usize = 16;
+/// Size of the accounts created before the lending account was expanded, without discriminator.
+pub const LEGACY_ASTROLEND_ACCOUNT_SIZE: usize = 2304;
 
-assert_struct_size!(LendingAccount, 1728);
+assert_struct_size!(LendingAccount, 2560);
 assert_struct_align!(LendingAccount, 8);
 #[zero_copy(unsafe)]
 #[repr(C)]
@@ -716,7 +1105,7 @@ assert_struct_align!(LendingAccount, 8);
     derive(Debug, PartialEq, Eq, TypeLayout)
 )]
 pub struct LendingAccount {
-    pub balances: [Balance; MAX_LENDING_ACCOUNT_BALANCES], // 104 * 16 = 1664
+    pub balances: [Balance; MAX_LENDING_ACCOUNT_BALANCES], // 104 * 24 = 2496
     pub _padding: [u64; 8],                                // 8 * 8 = 64
 }
 
@@ -724,6 +1113,14 @@ impl LendingAccount {
     pub fn get_first_empty_balance(&self) -> Option<usize> {
         self.balances.iter().position(|b| !b.active)
     }
+
+    /// Forget the flashloan borrows of all balances, liabilities left open at the end of a
+    /// flashloan are regular liabilities.
+    pub fn clear_flashloan_borrows(&mut self) {
+        self.balances
+            .iter_mut()
+            .for_each(|balance| balance.flashloan_borrowed = 0);
+    }
 }
 
 #[cfg(any(feature = "test", feature = "client"))]
@@ -753,9 +1150,14 @@ pub struct Balance {
     pub _pad0: [u8; 7],
     pub asset_shares: WrappedI80F48,
     pub liability_shares: WrappedI80F48,
+    /// Outstanding emissions of the primary emissions stream of the bank.
+    /// In pro-rata mode, the checkpoint of the balance instead, see
+    /// `calc_pro_rata_emissions_outstanding`.
     pub emissions_outstanding: WrappedI80F48,
     pub last_update: u64,
-    pub _padding: [u64; 1],
+    /// Amount borrowed during the ongoing flashloan and not repaid yet, in native tokens.
+    /// Cleared when the flashloan ends.
+    pub flashloan_borrowed: u64,
 }
 
 impl Balance {
@@ -827,13 +1229,15 @@ impl Balance {
             liability_shares: WrappedI80F48::from(I80F48::ZERO),
             emissions_outstanding: WrappedI80F48::from(I80F48::ZERO),
             last_update: 0,
-            _padding: [0; 1],
+            flashloan_borrowed: 0,
         }
     }
 }
 
 pub struct BankAccountWrapper<'a> {
     pub balance: &'a mut Balance,
+    /// Outstanding emissions of the balance for the additional emissions streams of the bank.
+    pub emissions_outstanding: &'a mut [WrappedI80F48; MAX_EMISSIONS_STREAMS - 1],
     pub bank: &'a mut Bank,
 }
 
@@ -842,15 +1246,21 @@ impl<'a> BankAccountWrapper<'a> {
     pub fn find(
         bank_pk: &Pubkey,
         bank: &'a mut Bank,
-        lending_account: &'a mut LendingAccount,
+        astrolend_account: &'a mut AstrolendAccount,
     ) -> AstrolendResult<BankAccountWrapper<'a>> {
-        let balance = lending_account
+        let (balance, emissions_outstanding) = astrolend_account
+            .lending_account
             .balances
             .iter_mut()
-            .find(|balance| balance.active && balance.bank_pk.eq(bank_pk))
+            .zip(astrolend_account.emissions_outstanding.iter_mut())
+            .find(|(balance, _)| balance.active && balance.bank_pk.eq(bank_pk))
             .ok_or_else(|| error!(AstrolendError::BankAccountNotFound))?;
 
-        Ok(Self { balance, bank })
+        Ok(Self {
+            balance,
+            emissions_outstanding,
+            bank,
+        })
     }
 
     // Find existing user lending account balance by bank address.
@@ -858,8 +1268,14 @@ impl<'a> BankAccountWrapper<'a> {
     pub fn find_or_create(
         bank_pk: &Pubkey,
         bank: &'a mut Bank,
-        lending_account: &'a mut LendingAccount,
+        astrolend_account: &'a mut AstrolendAccount,
     ) -> AstrolendResult<BankAccountWrapper<'a>> {
+        let AstrolendAccount {
+            lending_account,
+            emissions_outstanding,
+            ..
+        } = astrolend_account;
+
         let balance_index = lending_account
             .balances
             .iter()
@@ -872,7 +1288,11 @@ impl<'a> BankAccountWrapper<'a> {
                     .get_mut(balance_index)
                     .ok_or_else(|| error!(AstrolendError::BankAccountNotFound))?;
 
-                Ok(Self { balance, bank })
+                Ok(Self {
+                    balance,
+                    emissions_outstanding: &mut emissions_outstanding[balance_index],
+                    bank,
+                })
             }
             None => {
                 let empty_index = lending_account
@@ -887,11 +1307,13 @@ impl<'a> BankAccountWrapper<'a> {
                     liability_shares: I80F48::ZERO.into(),
                     emissions_outstanding: I80F48::ZERO.into(),
                     last_update: Clock::get()?.unix_timestamp as u64,
-                    _padding: [0; 1],
+                    flashloan_borrowed: 0,
                 };
+                emissions_outstanding[empty_index] = Default::default();
 
                 Ok(Self {
                     balance: lending_account.balances.get_mut(empty_index).unwrap(),
+                    emissions_outstanding: &mut emissions_outstanding[empty_index],
                     bank,
                 })
             }
@@ -911,10 +1333,18 @@ impl<'a> BankAccountWrapper<'a> {
     }
 
     /// Withdraw an asset, will error if there is not enough asset - borrowing is not allowed.
+    ///
+    /// While the bank has a deficit the deposits are only withdrawn at their backed value, the
+    /// deposits withdrawn on top of `amount` are forfeited to repay the deficit.
     pub fn withdraw(&mut self, amount: I80F48) -> AstrolendResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::WithdrawOnly)
     }
 
+    /// Withdraw an asset at its nominal value, for deposits moved to another balance of the bank.
+    pub fn withdraw_nominal(&mut self, amount: I80F48) -> AstrolendResult {
+        self.decrease_balance_internal(amount, BalanceDecreaseType::WithdrawNominal)
+    }
+
     /// Incur a borrow, will withdraw any existing assets.
     pub fn borrow(&mut self, amount: I80F48) -> AstrolendResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::Any)
@@ -948,6 +1378,8 @@ impl<'a> BankAccountWrapper<'a> {
     }
 
     /// Withdraw existing asset in full - will error if there is no asset.
+    ///
+    /// Returns the amount of tokens to pay out, discounted while the bank has a deficit.
     pub fn withdraw_all(&mut self) -> AstrolendResult<u64> {
         self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
 
@@ -973,17 +1405,37 @@ impl<'a> BankAccountWrapper<'a> {
             AstrolendError::NoAssetFound
         );
 
+        let payout_amount = match bank.maybe_get_deficit_discount()? {
+            Some(discount) => current_asset_amount
+                .checked_mul(discount)
+                .ok_or_else(math_error!())?,
+            None => current_asset_amount,
+        };
+
+        checkpoint_pro_rata_emissions(
+            bank,
+            &mut balance.emissions_outstanding,
+            self.emissions_outstanding,
+            -total_asset_shares,
+            -I80F48::from(balance.liability_shares),
+        )?;
         balance.close()?;
+        close_emissions_outstanding(self.emissions_outstanding)?;
         bank.change_asset_shares(-total_asset_shares, false)?;
 
+        // The discounted part of the deposits is forfeited to repay the deficit
+        bank.repay_deficit(
+            current_asset_amount
+                .checked_sub(payout_amount)
+                .ok_or_else(math_error!())?,
+        )?;
+
         bank.check_utilization_ratio()?;
 
-        let spl_withdraw_amount = current_asset_amount
-            .checked_floor()
-            .ok_or_else(math_error!())?;
+        let spl_withdraw_amount = payout_amount.checked_floor().ok_or_else(math_error!())?;
 
         bank.collected_insurance_fees_outstanding = {
-            current_asset_amount
+            payout_amount
                 .checked_sub(spl_withdraw_amount)
                 .ok_or_else(math_error!())?
                 .checked_add(bank.collected_insurance_fees_outstanding.into())
@@ -1021,7 +1473,15 @@ impl<'a> BankAccountWrapper<'a> {
             AstrolendError::NoLiabilityFound
         );
 
+        checkpoint_pro_rata_emissions(
+            bank,
+            &mut balance.emissions_outstanding,
+            self.emissions_outstanding,
+            -I80F48::from(balance.asset_shares),
+            -total_liability_shares,
+        )?;
         balance.close()?;
+        close_emissions_outstanding(self.emissions_outstanding)?;
         bank.change_liability_shares(-total_liability_shares, false)?;
 
         let spl_deposit_amount = current_liability_amount
@@ -1064,7 +1524,15 @@ impl<'a> BankAccountWrapper<'a> {
             "Balance has existing assets"
         );
 
+        checkpoint_pro_rata_emissions(
+            bank,
+            &mut balance.emissions_outstanding,
+            self.emissions_outstanding,
+            -I80F48::from(balance.asset_shares),
+            -I80F48::from(balance.liability_shares),
+        )?;
         balance.close()?;
+        close_emissions_outstanding(self.emissions_outstanding)?;
 
         Ok(())
     }
@@ -1083,6 +1551,16 @@ impl<'a> BankAccountWrapper<'a> {
 
         self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
 
+        // Repayments pay the flashloan fee on top of the repaid amount, any other increase
+        // repaying a flashloan liability pays it out of the increase.
+        let balance_delta = if self.balance.flashloan_borrowed > 0
+            && !matches!(operation_type, BalanceIncreaseType::RepayOnly)
+        {
+            self.deduct_flashloan_fee(balance_delta)?
+        } else {
+            balance_delta
+        };
+
         let balance = &mut self.balance;
         let bank = &mut self.bank;
 
@@ -1133,6 +1611,14 @@ impl<'a> BankAccountWrapper<'a> {
         balance.change_liability_shares(-liability_shares_decrease)?;
         bank.change_liability_shares(-liability_shares_decrease, true)?;
 
+        checkpoint_pro_rata_emissions(
+            bank,
+            &mut balance.emissions_outstanding,
+            self.emissions_outstanding,
+            asset_shares_increase,
+            -liability_shares_decrease,
+        )?;
+
         Ok(())
     }
 
@@ -1154,18 +1640,39 @@ impl<'a> BankAccountWrapper<'a> {
         let current_asset_shares: I80F48 = balance.asset_shares.into();
         let current_asset_amount = bank.get_asset_amount(current_asset_shares)?;
 
-        let (asset_amount_decrease, liability_amount_increase) = (
-            min(current_asset_amount, balance_delta),
+        // While the bank has a deficit the deposits are only worth their backed value
+        let deficit_discount = match operation_type {
+            BalanceDecreaseType::WithdrawNominal => None,
+            _ => bank.maybe_get_deficit_discount()?,
+        };
+        let current_asset_value = match deficit_discount {
+            Some(discount) => current_asset_amount
+                .checked_mul(discount)
+                .ok_or_else(math_error!())?,
+            None => current_asset_amount,
+        };
+
+        let (asset_value_decrease, liability_amount_increase) = (
+            min(current_asset_value, balance_delta),
             max(
                 balance_delta
-                    .checked_sub(current_asset_amount)
+                    .checked_sub(current_asset_value)
                     .ok_or_else(math_error!())?,
                 I80F48::ZERO,
             ),
         );
 
+        let asset_amount_decrease = match deficit_discount {
+            None => asset_value_decrease,
+            Some(_) if asset_value_decrease == I80F48::ZERO => I80F48::ZERO,
+            Some(_) if asset_value_decrease == current_asset_value => current_asset_amount,
+            Some(discount) => asset_value_decrease
+                .checked_div(discount)
+                .ok_or_else(math_error!())?,
+        };
+
         match operation_type {
-            BalanceDecreaseType::WithdrawOnly => {
+            BalanceDecreaseType::WithdrawOnly | BalanceDecreaseType::WithdrawNominal => {
                 check!(
                     liability_amount_increase.is_zero_with_tolerance(ZERO_AMOUNT_THRESHOLD),
                     AstrolendError::OperationWithdrawOnly
@@ -1197,74 +1704,88 @@ impl<'a> BankAccountWrapper<'a> {
             matches!(operation_type, BalanceDecreaseType::BypassBorrowLimit),
         )?;
 
+        checkpoint_pro_rata_emissions(
+            bank,
+            &mut balance.emissions_outstanding,
+            self.emissions_outstanding,
+            -asset_shares_decrease,
+            liability_shares_increase,
+        )?;
+
+        // The deposits withdrawn on top of their backed value are forfeited to repay the deficit
+        bank.repay_deficit(
+            asset_amount_decrease
+                .checked_sub(asset_value_decrease)
+                .ok_or_else(math_error!())?,
+        )?;
+
         bank.check_utilization_ratio()?;
 
         Ok(())
     }
 
-    /// Claim any unclaimed emissions and add them to the outstanding emissions amount.
+    /// Claim any unclaimed emissions of all emissions streams of the bank and add them to the
+    /// outstanding emissions amounts.
+    ///
+    /// Emissions of pro-rata streams are claimed by accruing the stream accumulators, the
+    /// balance checkpoints only move with share changes. Time-based streams accrue from the last
+    /// update of the balance, or from their setup if later.
     pub fn claim_emissions(&mut self, current_timestamp: u64) -> AstrolendResult {
-        if let Some(balance_amount) = match (
-            self.balance.get_side(),
-            self.bank.get_flag(EMISSIONS_FLAG_LENDING_ACTIVE),
-            self.bank.get_flag(EMISSIONS_FLAG_BORROW_ACTIVE),
-        ) {
-            (Some(BalanceSide::Assets), true, _) => Some(
-                self.bank
-                    .get_asset_amount(self.balance.asset_shares.into())?,
-            ),
-            (Some(BalanceSide::Liabilities), _, true) => Some(
-                self.bank
-                    .get_liability_amount(self.balance.liability_shares.into())?,
-            ),
-            _ => None,
-        } {
-            let last_update = if self.balance.last_update < MIN_EMISSIONS_START_TIME {
-                current_timestamp
-            } else {
-                self.balance.last_update
-            };
-            let period = I80F48::from_num(
-                current_timestamp
-                    .checked_sub(last_update)
-                    .ok_or_else(math_error!())?,
-            );
-            let emissions_rate = I80F48::from_num(self.bank.emissions_rate);
-            let emissions = calc_emissions(
+        self.bank
+            .accrue_pro_rata_emissions(current_timestamp as i64)?;
+
+        let last_update = if self.balance.last_update < MIN_EMISSIONS_START_TIME {
+            current_timestamp
+        } else {
+            self.balance.last_update
+        };
+
+        if let Some(balance_amount) =
+            get_emissions_balance_amount(self.balance, self.bank, self.bank.flags)?
+        {
+            let (period, emissions_rate) = self.bank.emissions_schedule.get_period_and_rate(
+                self.bank.emissions_rate,
+                (last_update as i64).max(self.bank.emissions_setup_time),
+                current_timestamp as i64,
+            )?;
+
+            accrue_emissions(
                 period,
                 balance_amount,
-                self.bank.mint_decimals as usize,
+                self.bank.mint_decimals,
                 emissions_rate,
+                &mut self.bank.emissions_remaining,
+                &mut self.balance.emissions_outstanding,
             )?;
+        }
 
-            let emissions_real = min(emissions, I80F48::from(self.bank.emissions_remaining));
+        for (index, outstanding) in self.emissions_outstanding.iter_mut().enumerate() {
+            let stream = self.bank.emissions_streams[index];
 
-            if emissions != emissions_real {
-                msg!(
-                    "Emissions capped: {} ({} calculated) for period {}s",
-                    emissions_real,
-                    emissions,
-                    period
-                );
+            if !stream.is_active() {
+                continue;
             }
 
-            debug!(
-                "Outstanding emissions: {}",
-                I80F48::from(self.balance.emissions_outstanding)
-            );
+            let balance_amount =
+                match get_emissions_balance_amount(self.balance, self.bank, stream.flags)? {
+                    Some(balance_amount) => balance_amount,
+                    None => continue,
+                };
 
-            self.balance.emissions_outstanding = {
-                I80F48::from(self.balance.emissions_outstanding)
-                    .checked_add(emissions_real)
-                    .ok_or_else(math_error!())?
-            }
-            .into();
-            self.bank.emissions_remaining = {
-                I80F48::from(self.bank.emissions_remaining)
-                    .checked_sub(emissions_real)
-                    .ok_or_else(math_error!())?
-            }
-            .into();
+            let (period, emissions_rate) = stream.schedule.get_period_and_rate(
+                stream.rate,
+                (last_update as i64).max(stream.setup_time),
+                current_timestamp as i64,
+            )?;
+
+            accrue_emissions(
+                period,
+                balance_amount,
+                self.bank.mint_decimals,
+                emissions_rate,
+                &mut self.bank.emissions_streams[index].remaining,
+                outstanding,
+            )?;
         }
 
         self.balance.last_update = current_timestamp;
@@ -1272,24 +1793,149 @@ impl<'a> BankAccountWrapper<'a> {
         Ok(())
     }
 
-    /// Claim any outstanding emissions, and return the max amount that can be withdrawn.
-    pub fn settle_emissions_and_get_transfer_amount(&mut self) -> AstrolendResult<u64> {
+    /// Claim any outstanding emissions of the stream paying out `emissions_mint`, and return the
+    /// max amount that can be withdrawn.
+    pub fn settle_emissions_and_get_transfer_amount(
+        &mut self,
+        emissions_mint: &Pubkey,
+    ) -> AstrolendResult<u64> {
         self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
 
-        let outstanding_emissions_floored = I80F48::from(self.balance.emissions_outstanding)
+        let stream_index = if self.bank.emissions_mint.eq(emissions_mint) {
+            None
+        } else {
+            Some(
+                self.bank
+                    .get_emissions_stream_index(emissions_mint)
+                    .ok_or_else(|| error!(AstrolendError::EmissionsStreamNotFound))?,
+            )
+        };
+
+        let (emissions_flags, emissions_index) = match stream_index {
+            None => (self.bank.flags, self.bank.emissions_index.value),
+            Some(index) => {
+                let stream = &self.bank.emissions_streams[index];
+                (stream.flags, stream.index.value)
+            }
+        };
+        let pro_rata_shares = get_pro_rata_shares(self.balance, emissions_flags);
+        let mint_decimals = self.bank.mint_decimals;
+
+        let emissions_outstanding = match stream_index {
+            None => &mut self.balance.emissions_outstanding,
+            Some(index) => &mut self.emissions_outstanding[index],
+        };
+
+        let outstanding_emissions = if emissions_flags & EMISSIONS_FLAG_PRO_RATA != 0 {
+            calc_pro_rata_emissions_outstanding(
+                (*emissions_outstanding).into(),
+                pro_rata_shares,
+                emissions_index.into(),
+                mint_decimals,
+            )?
+        } else {
+            (*emissions_outstanding).into()
+        };
+
+        let outstanding_emissions_floored = outstanding_emissions
             .checked_floor()
             .ok_or_else(math_error!())?;
-        let new_outstanding_amount = I80F48::from(self.balance.emissions_outstanding)
+        let new_outstanding_amount = I80F48::from(*emissions_outstanding)
             .checked_sub(outstanding_emissions_floored)
             .ok_or_else(math_error!())?;
 
-        self.balance.emissions_outstanding = new_outstanding_amount.into();
+        *emissions_outstanding = new_outstanding_amount.into();
 
         Ok(outstanding_emissions_floored
             .checked_to_num::<u64>()
             .ok_or_else(math_error!())?)
     }
 
+    // ------------ Flashloan fees
+
+    /// Borrow during a flashloan, recording the liability opened so that its repayment during
+    /// the flashloan is charged the flashloan fee.
+    pub fn flashloan_borrow(&mut self, amount: I80F48) -> AstrolendResult {
+        check!(
+            !self.bank.get_flag(FLASHLOAN_BORROW_DISABLED_FLAG),
+            AstrolendError::FlashloanBorrowDisabled
+        );
+
+        let liability_shares_before: I80F48 = self.balance.liability_shares.into();
+
+        self.borrow(amount)?;
+
+        // Existing assets are withdrawn first, only the liability opened is recorded
+        let liability_opened = self
+            .bank
+            .get_liability_amount(
+                I80F48::from(self.balance.liability_shares)
+                    .checked_sub(liability_shares_before)
+                    .ok_or_else(math_error!())?,
+            )?
+            .checked_ceil()
+            .ok_or_else(math_error!())?
+            .checked_to_num::<u64>()
+            .ok_or_else(math_error!())?;
+
+        self.balance.flashloan_borrowed = self
+            .balance
+            .flashloan_borrowed
+            .checked_add(liability_opened)
+            .ok_or_else(math_error!())?;
+
+        Ok(())
+    }
+
+    /// Charge the flashloan fee of the bank on the part of `repaid_amount` repaying liabilities
+    /// opened during the flashloan, `flashloan_borrowed` being the amount still to repay before
+    /// the repayment. The fee is credited to the group fees and returned.
+    pub fn charge_flashloan_fee(
+        &mut self,
+        flashloan_borrowed: u64,
+        repaid_amount: u64,
+    ) -> AstrolendResult<u64> {
+        let charged_amount = min(flashloan_borrowed, repaid_amount);
+
+        // The balance is closed on a full repayment
+        self.balance.flashloan_borrowed = self
+            .balance
+            .flashloan_borrowed
+            .saturating_sub(charged_amount);
+
+        let fee = self.bank.get_flashloan_fee(charged_amount);
+
+        if fee > 0 {
+            self.bank.collected_group_fees_outstanding =
+                I80F48::from(self.bank.collected_group_fees_outstanding)
+                    .checked_add(I80F48::from_num(fee))
+                    .ok_or_else(math_error!())?
+                    .into();
+        }
+
+        Ok(fee)
+    }
+
+    /// Charge the flashloan fee out of `amount` increasing the balance, on the part of it
+    /// repaying liabilities opened during the flashloan. Returns the amount left to increase the
+    /// balance by.
+    fn deduct_flashloan_fee(&mut self, amount: I80F48) -> AstrolendResult<I80F48> {
+        let liability_amount = self
+            .bank
+            .get_liability_amount(self.balance.liability_shares.into())?;
+        let repaid_amount = min(amount, liability_amount)
+            .checked_floor()
+            .ok_or_else(math_error!())?
+            .checked_to_num::<u64>()
+            .ok_or_else(math_error!())?;
+
+        let fee = self.charge_flashloan_fee(self.balance.flashloan_borrowed, repaid_amount)?;
+
+        Ok(amount
+            .checked_sub(I80F48::from_num(fee))
+            .ok_or_else(math_error!())?)
+    }
+
     // ------------ SPL helpers
 
     pub fn deposit_spl_transfer<'info>(
@@ -1337,6 +1983,197 @@ impl<'a> BankAccountWrapper<'a> {
     }
 }
 
+/// Balance amount earning the emissions of a time-based stream with the given emissions flags,
+/// if any.
+fn get_emissions_balance_amount(
+    balance: &Balance,
+    bank: &Bank,
+    emissions_flags: u64,
+) -> AstrolendResult<Option<I80F48>> {
+    if emissions_flags & EMISSIONS_FLAG_PRO_RATA != 0 {
+        return Ok(None);
+    }
+
+    Ok(
+        match (
+            balance.get_side(),
+            emissions_flags & EMISSIONS_FLAG_LENDING_ACTIVE != 0,
+            emissions_flags & EMISSIONS_FLAG_BORROW_ACTIVE != 0,
+        ) {
+            (Some(BalanceSide::Assets), true, _) => {
+                Some(bank.get_asset_amount(balance.asset_shares.into())?)
+            }
+            (Some(BalanceSide::Liabilities), _, true) => {
+                Some(bank.get_liability_amount(balance.liability_shares.into())?)
+            }
+            _ => None,
+        },
+    )
+}
+
+/// Accrue the emissions of one stream over `period` to `outstanding`, capped by the emissions
+/// remaining in the stream.
+fn accrue_emissions(
+    period: I80F48,
+    balance_amount: I80F48,
+    mint_decimals: u8,
+    emissions_rate: I80F48,
+    emissions_remaining: &mut WrappedI80F48,
+    outstanding: &mut WrappedI80F48,
+) -> AstrolendResult {
+    let emissions = calc_emissions(
+        period,
+        balance_amount,
+        mint_decimals as usize,
+        emissions_rate,
+    )?;
+
+    let emissions_real = min(emissions, I80F48::from(*emissions_remaining));
+
+    if emissions != emissions_real {
+        msg!(
+            "Emissions capped: {} ({} calculated) for period {}s",
+            emissions_real,
+            emissions,
+            period
+        );
+    }
+
+    debug!("Outstanding emissions: {}", I80F48::from(*outstanding));
+
+    *outstanding = I80F48::from(*outstanding)
+        .checked_add(emissions_real)
+        .ok_or_else(math_error!())?
+        .into();
+    *emissions_remaining = I80F48::from(*emissions_remaining)
+        .checked_sub(emissions_real)
+        .ok_or_else(math_error!())?
+        .into();
+
+    Ok(())
+}
+
+/// Shares of the balance earning the emissions of a pro-rata stream with the given emissions flags.
+fn get_pro_rata_shares(balance: &Balance, emissions_flags: u64) -> I80F48 {
+    if emissions_flags & EMISSIONS_FLAG_LENDING_ACTIVE != 0 {
+        balance.asset_shares.into()
+    } else {
+        balance.liability_shares.into()
+    }
+}
+
+/// Emissions earned by `shares` over the whole accumulator of a pro-rata stream.
+fn calc_pro_rata_emissions(
+    shares: I80F48,
+    emissions_index: I80F48,
+    mint_decimals: u8,
+) -> AstrolendResult<I80F48> {
+    Ok(shares
+        .checked_div(EXP_10_I80F48[mint_decimals as usize])
+        .ok_or_else(math_error!())?
+        .checked_mul(emissions_index)
+        .ok_or_else(math_error!())?)
+}
+
+/// Outstanding emissions of a balance in a pro-rata stream.
+///
+/// The balance keeps a checkpoint instead of its outstanding emissions: the outstanding emissions
+/// less what its current shares earned over the whole accumulator. The checkpoint moves with
+/// every share change of the balance, see `checkpoint_pro_rata_emissions`.
+fn calc_pro_rata_emissions_outstanding(
+    checkpoint: I80F48,
+    shares: I80F48,
+    emissions_index: I80F48,
+    mint_decimals: u8,
+) -> AstrolendResult<I80F48> {
+    Ok(checkpoint
+        .checked_add(calc_pro_rata_emissions(
+            shares,
+            emissions_index,
+            mint_decimals,
+        )?)
+        .ok_or_else(math_error!())?)
+}
+
+/// Move the checkpoints of a balance in the pro-rata streams of the bank along with a change of
+/// its shares, so that the change doesn't alter the emissions already earned by the balance.
+fn checkpoint_pro_rata_emissions(
+    bank: &Bank,
+    primary_emissions_outstanding: &mut WrappedI80F48,
+    emissions_outstanding: &mut [WrappedI80F48; MAX_EMISSIONS_STREAMS - 1],
+    asset_shares_delta: I80F48,
+    liability_shares_delta: I80F48,
+) -> AstrolendResult {
+    let shares_delta = |emissions_flags: u64| {
+        if emissions_flags & EMISSIONS_FLAG_LENDING_ACTIVE != 0 {
+            asset_shares_delta
+        } else {
+            liability_shares_delta
+        }
+    };
+
+    if bank.emissions_mint.ne(&Pubkey::default()) && bank.get_flag(EMISSIONS_FLAG_PRO_RATA) {
+        move_pro_rata_checkpoint(
+            primary_emissions_outstanding,
+            shares_delta(bank.flags),
+            bank.emissions_index.value.into(),
+            bank.mint_decimals,
+        )?;
+    }
+
+    for (stream, outstanding) in bank
+        .emissions_streams
+        .iter()
+        .zip(emissions_outstanding.iter_mut())
+    {
+        if stream.is_active() && stream.flags & EMISSIONS_FLAG_PRO_RATA != 0 {
+            move_pro_rata_checkpoint(
+                outstanding,
+                shares_delta(stream.flags),
+                stream.index.value.into(),
+                bank.mint_decimals,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn move_pro_rata_checkpoint(
+    checkpoint: &mut WrappedI80F48,
+    shares_delta: I80F48,
+    emissions_index: I80F48,
+    mint_decimals: u8,
+) -> AstrolendResult {
+    *checkpoint = I80F48::from(*checkpoint)
+        .checked_sub(calc_pro_rata_emissions(
+            shares_delta,
+            emissions_index,
+            mint_decimals,
+        )?)
+        .ok_or_else(math_error!())?
+        .into();
+
+    Ok(())
+}
+
+/// Check that no emissions of the additional streams are left on a balance being closed, and
+/// clear the remaining dust.
+fn close_emissions_outstanding(
+    emissions_outstanding: &mut [WrappedI80F48; MAX_EMISSIONS_STREAMS - 1],
+) -> AstrolendResult {
+    check!(
+        emissions_outstanding
+            .iter()
+            .all(|outstanding| I80F48::from(*outstanding) < I80F48::ONE),
+        AstrolendError::CannotCloseOutstandingEmissions
+    );
+
+    *emissions_outstanding = Default::default();
+
+    Ok(())
+}
+
 /// Calculates the emissions based on the given period, balance amount, mint decimals,
 /// emissions rate, and seconds per year.
 ///
@@ -1353,7 +2190,7 @@ impl<'a> BankAccountWrapper<'a> {
 /// # Returns
 ///
 /// The calculated emissions value.
-fn calc_emissions(
+pub(crate) fn calc_emissions(
     period: I80F48,
     balance_amount: I80F48,
     mint_decimals: usize,
@@ -1378,6 +2215,7 @@ fn calc_emissions(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::state::astrolend_group::{EmissionsIndex, EmissionsSchedule};
     use fixed_macro::types::I80F48;
 
     #[test]
@@ -1417,12 +2255,17 @@ mod test {
                     liability_shares: WrappedI80F48::default(),
                     emissions_outstanding: WrappedI80F48::default(),
                     last_update: 0,
-                    _padding: [0_u64],
-                }; 16],
+                    flashloan_borrowed: 0,
+                }; MAX_LENDING_ACCOUNT_BALANCES],
                 _padding: [0; 8],
             },
             account_flags: TRANSFER_AUTHORITY_ALLOWED_FLAG,
-            _padding: [0; 63],
+            emissions_outstanding: Default::default(),
+            soft_liquidation_window_start: 0,
+            soft_liquidation_window_value: WrappedI80F48::default(),
+            pending_authority: Pubkey::default(),
+            recovery_authority: Pubkey::default(),
+            _padding: [0; 20],
         };
 
         assert!(acc.get_flag(TRANSFER_AUTHORITY_ALLOWED_FLAG));
@@ -1433,6 +2276,102 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_account_authority_offer_accept() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        acc.authority = [1; 32].into();
+        let new_authority: Pubkey = [2; 32].into();
+        let other: Pubkey = [3; 32].into();
+
+        // No offer, nothing to accept
+        assert!(acc.accept_authority_transfer(new_authority).is_err());
+        assert!(acc.offer_authority_transfer(acc.authority).is_err());
+
+        acc.set_recovery_authority(other).unwrap();
+        acc.offer_authority_transfer(new_authority).unwrap();
+        assert!(acc.accept_authority_transfer(other).is_err());
+
+        let old_authority = acc.accept_authority_transfer(new_authority).unwrap();
+        assert_eq!(old_authority, Pubkey::from([1; 32]));
+        assert_eq!(acc.authority, new_authority);
+        assert_eq!(acc.pending_authority, Pubkey::default());
+        assert_eq!(acc.recovery_authority, Pubkey::default());
+
+        // Disabled accounts can't be transferred
+        acc.offer_authority_transfer(other).unwrap();
+        acc.set_flag(DISABLED_FLAG);
+        assert!(acc.accept_authority_transfer(other).is_err());
+    }
+
+    #[test]
+    fn test_account_freeze_and_recovery() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        let authority: Pubkey = [1; 32].into();
+        let recovery_authority: Pubkey = [2; 32].into();
+        let new_authority: Pubkey = [3; 32].into();
+        acc.authority = authority;
+
+        // Without a recovery authority the authority can lift the freeze
+        assert!(acc.freeze(&recovery_authority).is_err());
+        acc.freeze(&authority).unwrap();
+        assert!(acc.get_flag(FROZEN_FLAG));
+        assert!(acc.set_recovery_authority(recovery_authority).is_err());
+        acc.unfreeze(&authority).unwrap();
+        assert!(!acc.get_flag(FROZEN_FLAG));
+
+        acc.set_recovery_authority(recovery_authority).unwrap();
+        acc.freeze(&authority).unwrap();
+        assert!(acc.unfreeze(&authority).is_err());
+        assert!(acc.offer_authority_transfer(new_authority).is_err());
+
+        let old_authority = acc.recover_authority(new_authority).unwrap();
+        assert_eq!(old_authority, authority);
+        assert_eq!(acc.authority, new_authority);
+        assert!(acc.get_flag(FROZEN_FLAG));
+
+        acc.unfreeze(&recovery_authority).unwrap();
+        assert!(!acc.get_flag(FROZEN_FLAG));
+    }
+
+    #[test]
+    fn test_migrate_legacy_layout() {
+        let mut acc: AstrolendAccount = bytemuck::Zeroable::zeroed();
+        acc.group = [1; 32].into();
+        acc.authority = [2; 32].into();
+        for i in 0..LEGACY_MAX_LENDING_ACCOUNT_BALANCES {
+            acc.lending_account.balances[i].active = true;
+            acc.lending_account.balances[i].bank_pk = [i as u8 + 3; 32].into();
+            acc.lending_account.balances[i].asset_shares = I80F48::from_num(i).into();
+            acc.emissions_outstanding[i][0] = I80F48::from_num(i + 1).into();
+        }
+        acc.lending_account._padding[7] = 1;
+        acc.account_flags = FLASHLOAN_ENABLED_FLAG;
+
+        // The same fields in the legacy layout, with fewer balances
+        let balances = &acc.lending_account.balances[..LEGACY_MAX_LENDING_ACCOUNT_BALANCES];
+        let emissions = &acc.emissions_outstanding[..LEGACY_MAX_LENDING_ACCOUNT_BALANCES];
+        let mut data = [
+            bytemuck::bytes_of(&acc.group),
+            bytemuck::bytes_of(&acc.authority),
+            bytemuck::cast_slice(balances),
+            bytemuck::bytes_of(&acc.lending_account._padding),
+            bytemuck::bytes_of(&acc.account_flags),
+            bytemuck::cast_slice(emissions),
+            bytemuck::bytes_of(&acc.soft_liquidation_window_start),
+            bytemuck::bytes_of(&acc.soft_liquidation_window_value),
+            bytemuck::bytes_of(&acc.pending_authority),
+            bytemuck::bytes_of(&acc.recovery_authority),
+            bytemuck::bytes_of(&acc._padding),
+        ]
+        .concat();
+        assert_eq!(data.len(), LEGACY_ASTROLEND_ACCOUNT_SIZE);
+
+        data.resize(std::mem::size_of::<AstrolendAccount>(), 0);
+        AstrolendAccount::migrate_legacy_layout(&mut data).unwrap();
+
+        assert!(data == bytemuck::bytes_of(&acc));
+    }
+
     #[test]
     fn test_calc_emissions() {
         let balance_amount: u64 = 106153222432271169;
@@ -1538,4 +2477,93 @@ mod test {
             assert!(emissions_new - emissions < I80F48::from_num(0.00000001));
         }
     }
+
+    #[test]
+    fn test_accrue_emissions_capped_by_remaining() {
+        let mut remaining: WrappedI80F48 = I80F48!(10).into();
+        let mut outstanding: WrappedI80F48 = I80F48!(0.5).into();
+
+        // 1 year of 1_000 tokens at 1 token per token per year, only 10 left in the stream
+        accrue_emissions(
+            SECONDS_PER_YEAR,
+            I80F48!(1_000_000_000_000),
+            9,
+            I80F48!(1),
+            &mut remaining,
+            &mut outstanding,
+        )
+        .unwrap();
+
+        assert_eq!(I80F48::from(remaining), I80F48::ZERO);
+        assert_eq!(I80F48::from(outstanding), I80F48!(10.5));
+
+        let mut emissions_outstanding = [outstanding; MAX_EMISSIONS_STREAMS - 1];
+        assert!(close_emissions_outstanding(&mut emissions_outstanding).is_err());
+
+        let mut emissions_outstanding = [I80F48!(0.5).into(); MAX_EMISSIONS_STREAMS - 1];
+        assert!(close_emissions_outstanding(&mut emissions_outstanding).is_ok());
+        assert!(emissions_outstanding
+            .iter()
+            .all(|outstanding| I80F48::from(*outstanding) == I80F48::ZERO));
+    }
+
+    #[test]
+    fn test_pro_rata_emissions() {
+        let mut index = EmissionsIndex::default();
+        let schedule = EmissionsSchedule::default();
+        let mut remaining: WrappedI80F48 = I80F48!(1_000).into();
+
+        // 300 emissions over 3 shares
+        index
+            .accrue(10, 30, &schedule, I80F48!(3_000_000), 6, &mut remaining)
+            .unwrap();
+        assert_eq!(I80F48::from(index.value), I80F48!(100));
+        assert_eq!(I80F48::from(remaining), I80F48!(700));
+
+        let mut checkpoint: WrappedI80F48 = I80F48::ZERO.into();
+        let outstanding = |checkpoint: WrappedI80F48, shares: I80F48, index: &EmissionsIndex| {
+            calc_pro_rata_emissions_outstanding(checkpoint.into(), shares, index.value.into(), 6)
+                .unwrap()
+        };
+        assert_eq!(
+            outstanding(checkpoint, I80F48!(1_000_000), &index),
+            I80F48!(100)
+        );
+
+        // Doubling the shares doesn't change what was already earned
+        move_pro_rata_checkpoint(&mut checkpoint, I80F48!(1_000_000), index.value.into(), 6)
+            .unwrap();
+        assert_eq!(
+            outstanding(checkpoint, I80F48!(2_000_000), &index),
+            I80F48!(100)
+        );
+
+        // 300 emissions over 4 shares, half of them for the balance
+        index
+            .accrue(20, 30, &schedule, I80F48!(4_000_000), 6, &mut remaining)
+            .unwrap();
+        assert_eq!(
+            outstanding(checkpoint, I80F48!(2_000_000), &index),
+            I80F48!(250)
+        );
+
+        // Capped by the remaining emissions, nothing emitted without shares
+        index
+            .accrue(100, 30, &schedule, I80F48!(4_000_000), 6, &mut remaining)
+            .unwrap();
+        assert_eq!(I80F48::from(remaining), I80F48::ZERO);
+        assert_eq!(
+            outstanding(checkpoint, I80F48!(2_000_000), &index),
+            I80F48!(450)
+        );
+
+        index
+            .accrue(200, 30, &schedule, I80F48::ZERO, 6, &mut remaining)
+            .unwrap();
+        assert_eq!(index.last_update, 200);
+        assert_eq!(
+            outstanding(checkpoint, I80F48!(2_000_000), &index),
+            I80F48!(450)
+        );
+    }
 }
@@ -8,10 +8,13 @@ use crate::events::{GroupEventHeader, LendingPoolBankAccrueInterestEvent};
 use crate::{
     assert_struct_align, assert_struct_size, check,
     constants::{
-        EMISSION_FLAGS, FEE_VAULT_AUTHORITY_SEED, FEE_VAULT_SEED, GROUP_FLAGS,
-        INSURANCE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED,
-        LIQUIDITY_VAULT_SEED, MAX_ORACLE_KEYS, MAX_PYTH_ORACLE_AGE, MAX_SWB_ORACLE_AGE,
-        PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG, PYTH_ID, SECONDS_PER_YEAR,
+        EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, EMISSIONS_FLAG_PRO_RATA,
+        EMISSION_FLAGS, EXP_10_I80F48, FEE_VAULT_AUTHORITY_SEED, FEE_VAULT_SEED,
+        FLASHLOAN_BORROW_DISABLED_FLAG, GROUP_FLAGS, INSURANCE_VAULT_AUTHORITY_SEED,
+        INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
+        MAX_BANK_CONFIG_DELAY, MAX_EMISSIONS_STREAMS, MAX_FLASHLOAN_FEE_BPS,
+        MAX_GROUP_RESERVE_HAIRCUT, MAX_ORACLE_KEYS, MAX_PYTH_ORACLE_AGE, MAX_SWAP_PROGRAMS,
+        MAX_SWB_ORACLE_AGE, PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG, PYTH_ID, SECONDS_PER_YEAR,
         TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
     },
     debug, math_error,
@@ -44,7 +47,23 @@ use type_layout::TypeLayout;
 #[derive(Default)]
 pub struct AstrolendGroup {
     pub admin: Pubkey,
-    pub _padding_0: [[u64; 2]; 32],
+    /// Minimum number of seconds between queueing a bank config change and executing it.
+    /// Zero disables the timelock and bank config changes apply immediately.
+    /// The delay can only be increased once set, up to `MAX_BANK_CONFIG_DELAY`.
+    pub bank_config_delay: u64,
+    pub reserve_vault_bump: u8,
+    pub reserve_vault_authority_bump: u8,
+    pub _pad0: [u8; 6], // 2x u8 + 6 = 8
+    /// Bank whose mint and oracle are used for the group reserve vault.
+    /// Default pubkey while the group reserve is not setup.
+    pub reserve_bank: Pubkey,
+    /// Premium over the oracle value of the repaid bad debt paid out in reserve tokens when the
+    /// group reserve buys down bad debt, earned by whoever supplies the repaid tokens.
+    pub reserve_haircut: WrappedI80F48,
+    /// Programs that can be invoked to swap tokens within an instruction, e.g. collateral swaps.
+    /// Unused entries are the default pubkey.
+    pub swap_programs: [Pubkey; MAX_SWAP_PROGRAMS],
+    pub _padding_0: [[u64; 2]; 20],
     pub _padding_1: [[u64; 2]; 32],
 }
 
@@ -55,9 +74,48 @@ impl AstrolendGroup {
     pub fn configure(&mut self, config: &GroupConfig) -> AstrolendResult {
         set_if_some!(self.admin, config.admin);
 
+        if let Some(bank_config_delay) = config.bank_config_delay {
+            // Lowering the delay would let the admin skip the timelock for its own changes, the
+            // cap keeps queued changes executable
+            check!(
+                bank_config_delay >= self.bank_config_delay
+                    && bank_config_delay <= MAX_BANK_CONFIG_DELAY,
+                AstrolendError::InvalidConfig
+            );
+        }
+
+        set_if_some!(self.bank_config_delay, config.bank_config_delay);
+
+        if let Some(reserve_haircut) = config.reserve_haircut {
+            let reserve_haircut: I80F48 = reserve_haircut.into();
+            check!(
+                reserve_haircut >= I80F48::ZERO && reserve_haircut <= MAX_GROUP_RESERVE_HAIRCUT,
+                AstrolendError::InvalidConfig
+            );
+        }
+
+        set_if_some!(self.reserve_haircut, config.reserve_haircut);
+
+        if let Some(swap_programs) = config.swap_programs {
+            check!(
+                !swap_programs.contains(&crate::ID),
+                AstrolendError::InvalidConfig
+            );
+        }
+
+        set_if_some!(self.swap_programs, config.swap_programs);
+
         Ok(())
     }
 
+    pub fn is_swap_program_allowed(&self, program: &Pubkey) -> bool {
+        *program != Pubkey::default() && self.swap_programs.contains(program)
+    }
+
+    pub fn is_reserve_enabled(&self) -> bool {
+        self.reserve_bank != Pubkey::default()
+    }
+
     /// Set the group parameters when initializing a group.
     /// This should be called only when the group is first initialized.
     /// Both astrol requirements are initially set to 100% and should be configured before use.
@@ -71,6 +129,9 @@ impl AstrolendGroup {
 #[derive(AnchorSerialize, AnchorDeserialize, Default, Debug, Clone)]
 pub struct GroupConfig {
     pub admin: Option<Pubkey>,
+    pub bank_config_delay: Option<u64>,
+    pub reserve_haircut: Option<WrappedI80F48>,
+    pub swap_programs: Option<[Pubkey; MAX_SWAP_PROGRAMS]>,
 }
 
 /// Load and validate a pyth price feed account.
@@ -334,6 +395,7 @@ pub struct Bank {
     /// - EMISSIONS_FLAG_BORROW_ACTIVE: 1
     /// - EMISSIONS_FLAG_LENDING_ACTIVE: 2
     /// - PERMISSIONLESS_BAD_DEBT_SETTLEMENT: 4
+    /// - EMISSIONS_FLAG_PRO_RATA: 8
     ///
     pub flags: u64,
     /// Emissions APR.
@@ -342,7 +404,33 @@ pub struct Bank {
     pub emissions_remaining: WrappedI80F48,
     pub emissions_mint: Pubkey,
 
-    pub _padding_0: [[u64; 2]; 28],
+    /// Mint of the insurance fund share tokens, set once insurance staking is enabled.
+    /// While set, the insurance vault belongs to the share holders.
+    pub insurance_share_mint: Pubkey,
+    /// Seconds a staker has to wait between requesting an unstake and completing it.
+    pub insurance_unstake_cooldown: u64,
+    /// Seconds after the cooldown during which an unstake request can be completed.
+    pub insurance_unstake_window: u64,
+
+    /// Bad debt recorded instead of being socialized, in bank tokens.
+    /// Depositors' claims exceed the bank's holdings by this amount until it is repaid.
+    pub deficit: WrappedI80F48,
+    /// Share of the interest earned by depositors used to repay the deficit.
+    /// Zero socializes bad debt right away, any other value records it as a deficit.
+    pub deficit_interest_share: WrappedI80F48,
+
+    /// Emissions streams running next to the primary stream (`emissions_*` fields above),
+    /// each paying out a different mint.
+    pub emissions_streams: [EmissionsStream; MAX_EMISSIONS_STREAMS - 1],
+    /// Accumulator of the primary emissions stream, used in pro-rata mode.
+    pub emissions_index: EmissionsIndex,
+    /// Schedule of the primary emissions stream.
+    pub emissions_schedule: EmissionsSchedule,
+    /// Setup timestamp of the primary emissions stream, see `EmissionsStream::setup_time`.
+    pub emissions_setup_time: i64,
+    pub _pad3: [u8; 8],
+
+    pub _padding_0: [[u64; 2]; 7],
     pub _padding_1: [[u64; 2]; 32], // 16 * 2 * 32 = 1024B
 }
 
@@ -508,8 +596,40 @@ impl Bank {
         Ok(())
     }
 
-    pub fn check_utilization_ratio(&self) -> AstrolendResult {
+    /// Total deposits of the bank less the deficit, i.e. the deposits actually backed by
+    /// liquidity or liabilities.
+    pub fn get_backed_asset_amount(&self) -> AstrolendResult<I80F48> {
+        Ok(self
+            .get_asset_amount(self.total_asset_shares.into())?
+            .checked_sub(self.deficit.into())
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Discount applied to the value of deposits while the bank has a deficit.
+    pub fn maybe_get_deficit_discount(&self) -> AstrolendResult<Option<I80F48>> {
+        let deficit: I80F48 = self.deficit.into();
+
+        if deficit <= I80F48::ZERO {
+            return Ok(None);
+        }
+
         let total_assets = self.get_asset_amount(self.total_asset_shares.into())?;
+
+        if total_assets <= deficit {
+            return Ok(Some(I80F48::ZERO));
+        }
+
+        Ok(Some(
+            total_assets
+                .checked_sub(deficit)
+                .ok_or_else(math_error!())?
+                .checked_div(total_assets)
+                .ok_or_else(math_error!())?,
+        ))
+    }
+
+    pub fn check_utilization_ratio(&self) -> AstrolendResult {
+        let total_assets = self.get_backed_asset_amount()?;
         let total_liabilities = self.get_liability_amount(self.total_liability_shares.into())?;
 
         check!(
@@ -520,6 +640,13 @@ impl Bank {
         Ok(())
     }
 
+    /// Flashloan fee owed on top of repaying `amount`, rounded up. At most `amount`.
+    pub fn get_flashloan_fee(&self, amount: u64) -> u64 {
+        ((amount as u128 * self.config.flashloan_fee_bps as u128 + MAX_FLASHLOAN_FEE_BPS as u128
+            - 1)
+            / MAX_FLASHLOAN_FEE_BPS as u128) as u64
+    }
+
     pub fn configure(&mut self, config: &BankConfigOpt) -> AstrolendResult {
         set_if_some!(self.config.asset_weight_init, config.asset_weight_init);
         set_if_some!(self.config.asset_weight_maint, config.asset_weight_maint);
@@ -558,6 +685,34 @@ impl Bank {
             self.update_flag(flag, PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG);
         }
 
+        if let Some(deficit_interest_share) = config.deficit_interest_share {
+            let deficit_interest_share: I80F48 = deficit_interest_share.into();
+            check!(
+                deficit_interest_share >= I80F48::ZERO && deficit_interest_share <= I80F48::ONE,
+                AstrolendError::InvalidConfig
+            );
+        }
+
+        set_if_some!(self.deficit_interest_share, config.deficit_interest_share);
+
+        if let Some(flashloan_fee_bps) = config.flashloan_fee_bps {
+            check!(
+                flashloan_fee_bps <= MAX_FLASHLOAN_FEE_BPS,
+                AstrolendError::InvalidConfig
+            );
+        }
+
+        set_if_some!(self.config.flashloan_fee_bps, config.flashloan_fee_bps);
+
+        if let Some(flag) = config.flashloan_borrow_disabled {
+            self.update_flag(flag, FLASHLOAN_BORROW_DISABLED_FLAG);
+        }
+
+        set_if_some!(
+            self.config.soft_liquidation_threshold,
+            config.soft_liquidation_threshold
+        );
+
         self.config.validate()?;
 
         Ok(())
@@ -582,11 +737,12 @@ impl Bank {
         }
 
         let total_assets = self.get_asset_amount(self.total_asset_shares.into())?;
+        let backed_assets = self.get_backed_asset_amount()?;
         let total_liabilities = self.get_liability_amount(self.total_liability_shares.into())?;
 
         self.last_update = current_timestamp;
 
-        if (total_assets == I80F48::ZERO) || (total_liabilities == I80F48::ZERO) {
+        if (backed_assets <= I80F48::ZERO) || (total_liabilities == I80F48::ZERO) {
             #[cfg(not(feature = "client"))]
             emit!(LendingPoolBankAccrueInterestEvent {
                 header: GroupEventHeader {
@@ -598,6 +754,7 @@ impl Bank {
                 delta: time_delta,
                 fees_collected: 0.,
                 insurance_collected: 0.,
+                deficit_repaid: 0.,
             });
 
             return Ok(());
@@ -606,7 +763,7 @@ impl Bank {
         let (asset_share_value, liability_share_value, fees_collected, insurance_collected) =
             calc_interest_rate_accrual_state_changes(
                 time_delta,
-                total_assets,
+                backed_assets,
                 total_liabilities,
                 &self.config.interest_rate_config,
                 self.asset_share_value.into(),
@@ -614,6 +771,24 @@ impl Bank {
             )
             .ok_or_else(math_error!())?;
 
+        let (asset_share_value, deficit_repaid) = if backed_assets < total_assets {
+            calc_deficit_repayment(
+                self.asset_share_value.into(),
+                asset_share_value,
+                total_assets,
+                backed_assets,
+                self.deficit_interest_share.into(),
+            )
+            .ok_or_else(math_error!())?
+        } else {
+            (asset_share_value, I80F48::ZERO)
+        };
+
+        self.deficit = I80F48::from(self.deficit)
+            .checked_sub(deficit_repaid)
+            .ok_or_else(math_error!())?
+            .into();
+
         debug!("deposit share value: {}\nliability share value: {}\nfees collected: {}\ninsurance collected: {}",
             asset_share_value, liability_share_value, fees_collected, insurance_collected);
 
@@ -649,6 +824,7 @@ impl Bank {
                 delta: time_delta,
                 fees_collected: fees_collected.to_num::<f64>(),
                 insurance_collected: insurance_collected.to_num::<f64>(),
+                deficit_repaid: deficit_repaid.to_num::<f64>(),
             });
         }
 
@@ -706,6 +882,34 @@ impl Bank {
         Ok(())
     }
 
+    /// Transfer tokens signed by a staker into the bank's insurance vault.
+    pub fn insurance_deposit_spl_transfer<'info>(
+        &self,
+        amount: u64,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+        maybe_mint: Option<&InterfaceAccount<'info, Mint>>,
+        program: AccountInfo<'info>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> AstrolendResult {
+        check!(
+            to.key.eq(&self.insurance_vault),
+            AstrolendError::InvalidTransfer
+        );
+
+        self.withdraw_spl_transfer(
+            amount,
+            from,
+            to,
+            authority,
+            maybe_mint,
+            program,
+            &[],
+            remaining_accounts,
+        )
+    }
+
     pub fn withdraw_spl_transfer<'info>(
         &self,
         amount: u64,
@@ -777,6 +981,34 @@ impl Bank {
         Ok(())
     }
 
+    /// Record a loss `loss_amount` as a deficit of the bank instead of socializing it,
+    /// the deficit is repaid over time from the interest earned by depositors.
+    pub fn record_deficit(&mut self, loss_amount: I80F48) -> AstrolendResult {
+        self.deficit = I80F48::from(self.deficit)
+            .checked_add(loss_amount)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(())
+    }
+
+    /// Repay `amount` of the deficit with deposits forfeited by withdrawals at the discounted
+    /// value of the deposits.
+    pub fn repay_deficit(&mut self, amount: I80F48) -> AstrolendResult {
+        self.deficit = I80F48::from(self.deficit)
+            .checked_sub(amount)
+            .ok_or_else(math_error!())?
+            .max(I80F48::ZERO)
+            .into();
+
+        Ok(())
+    }
+
+    /// Bad debt is recorded as a deficit when part of the depositors' interest repays it.
+    pub fn defers_loss_socialization(&self) -> bool {
+        I80F48::from(self.deficit_interest_share) > I80F48::ZERO
+    }
+
     pub fn assert_operational_mode(
         &self,
         is_asset_or_liability_amount_increasing: Option<bool>,
@@ -818,8 +1050,70 @@ impl Bank {
         }
     }
 
-    const fn verify_emissions_flags(flags: u64) -> bool {
+    /// Index in `emissions_streams` of the additional stream paying out `mint`, if any.
+    pub fn get_emissions_stream_index(&self, mint: &Pubkey) -> Option<usize> {
+        self.emissions_streams
+            .iter()
+            .position(|stream| stream.is_active() && stream.mint.eq(mint))
+    }
+
+    /// Accrue the accumulators of all pro-rata emissions streams of the bank up to
+    /// `current_timestamp`.
+    ///
+    /// Must run before any change of the bank's total shares.
+    pub fn accrue_pro_rata_emissions(&mut self, current_timestamp: i64) -> AstrolendResult {
+        let total_asset_shares: I80F48 = self.total_asset_shares.into();
+        let total_liability_shares: I80F48 = self.total_liability_shares.into();
+
+        let get_total_shares = |emissions_flags: u64| {
+            if emissions_flags & EMISSIONS_FLAG_LENDING_ACTIVE != 0 {
+                total_asset_shares
+            } else {
+                total_liability_shares
+            }
+        };
+
+        if self.emissions_mint.ne(&Pubkey::default()) && self.get_flag(EMISSIONS_FLAG_PRO_RATA) {
+            self.emissions_index.accrue(
+                current_timestamp,
+                self.emissions_rate,
+                &self.emissions_schedule,
+                get_total_shares(self.flags),
+                self.mint_decimals,
+                &mut self.emissions_remaining,
+            )?;
+        }
+
+        for stream in self.emissions_streams.iter_mut() {
+            if stream.is_active() && stream.flags & EMISSIONS_FLAG_PRO_RATA != 0 {
+                stream.index.accrue(
+                    current_timestamp,
+                    stream.rate,
+                    &stream.schedule,
+                    get_total_shares(stream.flags),
+                    self.mint_decimals,
+                    &mut stream.remaining,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `mint` is paid out by the primary or any of the additional emissions streams.
+    pub fn is_emissions_mint(&self, mint: &Pubkey) -> bool {
+        (self.emissions_mint.ne(&Pubkey::default()) && self.emissions_mint.eq(mint))
+            || self.get_emissions_stream_index(mint).is_some()
+    }
+
+    pub(crate) const fn verify_emissions_flags(flags: u64) -> bool {
+        let side_flags = flags & (EMISSIONS_FLAG_LENDING_ACTIVE | EMISSIONS_FLAG_BORROW_ACTIVE);
+
+        // Pro-rata emissions are shared by the shares of exactly one side
         flags & EMISSION_FLAGS == flags
+            && (flags & EMISSIONS_FLAG_PRO_RATA == 0
+                || side_flags == EMISSIONS_FLAG_LENDING_ACTIVE
+                || side_flags == EMISSIONS_FLAG_BORROW_ACTIVE)
     }
 
     const fn verify_group_flags(flags: u64) -> bool {
@@ -827,6 +1121,342 @@ impl Bank {
     }
 }
 
+assert_struct_size!(EmissionsStream, 160);
+assert_struct_align!(EmissionsStream, 8);
+#[zero_copy(unsafe)]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+pub struct EmissionsStream {
+    pub mint: Pubkey,
+    /// - EMISSIONS_FLAG_BORROW_ACTIVE: 1
+    /// - EMISSIONS_FLAG_LENDING_ACTIVE: 2
+    /// - EMISSIONS_FLAG_PRO_RATA: 8
+    pub flags: u64,
+    /// Number of emitted tokens (mint) per 1e(bank.mint_decimal) tokens (bank mint) (native amount) per 1 YEAR.
+    /// In pro-rata mode, number of emitted tokens (native amount) per second.
+    pub rate: u64,
+    pub remaining: WrappedI80F48,
+    /// Accumulator of the stream, used in pro-rata mode.
+    pub index: EmissionsIndex,
+    pub schedule: EmissionsSchedule,
+    /// Balances accrue the time-based emissions of the stream from this timestamp on, zero for
+    /// streams set up before it was recorded.
+    pub setup_time: i64,
+    pub _padding: [u64; 1],
+}
+
+impl EmissionsStream {
+    pub fn is_active(&self) -> bool {
+        self.mint.ne(&Pubkey::default())
+    }
+}
+
+assert_struct_size!(EmissionsIndex, 32);
+assert_struct_align!(EmissionsIndex, 8);
+#[zero_copy(unsafe)]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+/// Reward-per-share accumulator of a pro-rata emissions stream, see `EMISSIONS_FLAG_PRO_RATA`.
+pub struct EmissionsIndex {
+    /// Emissions distributed per 1e(bank.mint_decimal) shares of the emitting side, since setup.
+    pub value: WrappedI80F48,
+    pub last_update: i64,
+    pub _padding: u64,
+}
+
+impl EmissionsIndex {
+    /// Distribute the emissions of a stream with `rate` emissions per second, following its
+    /// `schedule`, since the last update over `total_shares`, capped by `emissions_remaining`.
+    ///
+    /// Nothing is emitted while there are no shares to emit to.
+    pub fn accrue(
+        &mut self,
+        current_timestamp: i64,
+        rate: u64,
+        schedule: &EmissionsSchedule,
+        total_shares: I80F48,
+        mint_decimals: u8,
+        emissions_remaining: &mut WrappedI80F48,
+    ) -> AstrolendResult {
+        let (period, rate) =
+            schedule.get_period_and_rate(rate, self.last_update, current_timestamp)?;
+
+        self.last_update = current_timestamp;
+
+        if period <= I80F48::ZERO || total_shares <= I80F48::ZERO {
+            return Ok(());
+        }
+
+        let emissions = period
+            .checked_mul(rate)
+            .ok_or_else(math_error!())?
+            .min(I80F48::from(*emissions_remaining));
+
+        let total_shares_ui = total_shares
+            .checked_div(EXP_10_I80F48[mint_decimals as usize])
+            .ok_or_else(math_error!())?;
+
+        self.value = I80F48::from(self.value)
+            .checked_add(
+                emissions
+                    .checked_div(total_shares_ui)
+                    .ok_or_else(math_error!())?,
+            )
+            .ok_or_else(math_error!())?
+            .into();
+        *emissions_remaining = I80F48::from(*emissions_remaining)
+            .checked_sub(emissions)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(())
+    }
+}
+
+#[repr(u8)]
+#[cfg_attr(any(feature = "test", feature = "client"), derive(PartialEq, Eq))]
+#[derive(Copy, Clone, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub enum EmissionsDecay {
+    #[default]
+    None,
+    /// The rate moves linearly from the stream rate at `start_time` to `end_rate` at `end_time`.
+    Linear,
+    /// The rate follows the linear decay, updated at the start of every `step_period`.
+    Step,
+}
+
+assert_struct_size!(EmissionsSchedule, 48);
+assert_struct_align!(EmissionsSchedule, 8);
+#[zero_copy(unsafe)]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+/// Time window and decay of the rate of an emissions stream.
+pub struct EmissionsSchedule {
+    /// Emissions start at this timestamp, or right away if zero.
+    pub start_time: i64,
+    /// Emissions end at this timestamp, or never if zero.
+    pub end_time: i64,
+    /// Rate reached at `end_time` by decaying emissions.
+    pub end_rate: u64,
+    /// Seconds between two rate updates of step decaying emissions.
+    pub step_period: u64,
+    pub decay: EmissionsDecay,
+    pub _pad0: [u8; 7],
+    /// Emissions stop at this timestamp once the stream is clawed back, or never if zero.
+    pub clawback_time: i64,
+}
+
+impl EmissionsSchedule {
+    pub fn validate(&self) -> AstrolendResult {
+        check!(
+            self.end_time == 0 || self.end_time > self.start_time,
+            AstrolendError::InvalidEmissionsSchedule
+        );
+
+        match self.decay {
+            EmissionsDecay::None => {}
+            EmissionsDecay::Linear | EmissionsDecay::Step => {
+                check!(
+                    self.start_time > 0 && self.end_time > 0,
+                    AstrolendError::InvalidEmissionsSchedule,
+                    "Decaying emissions need a start and an end time"
+                );
+            }
+        }
+
+        if let EmissionsDecay::Step = self.decay {
+            check!(
+                self.step_period > 0,
+                AstrolendError::InvalidEmissionsSchedule
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Whether the schedule of a stream set up at `setup_time` can be replaced by `schedule` at
+    /// `current_timestamp`, leaving the rate before `current_timestamp` unchanged.
+    ///
+    /// Balances settle time-based emissions over the whole period since their last update, a
+    /// change reaching into the past would apply retroactively to that period.
+    pub fn can_update_to(
+        &self,
+        schedule: &EmissionsSchedule,
+        setup_time: i64,
+        current_timestamp: i64,
+    ) -> bool {
+        // Nothing emitted yet, the new schedule can't start in the past either
+        if self.start_time.max(setup_time) >= current_timestamp {
+            return schedule.start_time.max(setup_time) >= current_timestamp;
+        }
+
+        if self.start_time != schedule.start_time
+            || self.decay as u8 != schedule.decay as u8
+            || self.end_rate != schedule.end_rate
+            || self.step_period != schedule.step_period
+        {
+            return false;
+        }
+
+        match self.decay {
+            // The decayed rate depends on the end time at any point of the schedule
+            EmissionsDecay::Linear | EmissionsDecay::Step => self.end_time == schedule.end_time,
+            EmissionsDecay::None => {
+                if self.end_time != 0 && self.end_time <= current_timestamp {
+                    schedule.end_time == self.end_time
+                } else {
+                    schedule.end_time == 0 || schedule.end_time >= current_timestamp
+                }
+            }
+        }
+    }
+
+    /// Seconds of `[from, to]` within the schedule and the average emissions rate over them, for
+    /// a stream starting at `rate`.
+    pub fn get_period_and_rate(
+        &self,
+        rate: u64,
+        from: i64,
+        to: i64,
+    ) -> AstrolendResult<(I80F48, I80F48)> {
+        let from = from.max(self.start_time);
+        let to = if self.end_time == 0 {
+            to
+        } else {
+            to.min(self.end_time)
+        };
+        let to = if self.clawback_time == 0 {
+            to
+        } else {
+            to.min(self.clawback_time)
+        };
+
+        if to <= from {
+            return Ok((I80F48::ZERO, I80F48::ZERO));
+        }
+
+        let period = I80F48::from_num(to - from);
+        let rate = I80F48::from_num(rate);
+
+        let average_rate = match self.decay {
+            EmissionsDecay::None => rate,
+            EmissionsDecay::Linear => self
+                .get_linear_rate(rate, from)?
+                .checked_add(self.get_linear_rate(rate, to)?)
+                .ok_or_else(math_error!())?
+                .checked_div(I80F48::from_num(2))
+                .ok_or_else(math_error!())?,
+            EmissionsDecay::Step => self
+                .calc_step_rate_integral(rate, from, to)?
+                .checked_div(period)
+                .ok_or_else(math_error!())?,
+        };
+
+        Ok((period, average_rate))
+    }
+
+    /// Linearly decayed rate at `timestamp`, within the schedule.
+    fn get_linear_rate(&self, rate: I80F48, timestamp: i64) -> AstrolendResult<I80F48> {
+        Ok(rate
+            .checked_add(
+                self.get_rate_slope(rate)?
+                    .checked_mul(I80F48::from_num(timestamp - self.start_time))
+                    .ok_or_else(math_error!())?,
+            )
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Change of the linearly decayed rate per second.
+    fn get_rate_slope(&self, rate: I80F48) -> AstrolendResult<I80F48> {
+        Ok(I80F48::from_num(self.end_rate)
+            .checked_sub(rate)
+            .ok_or_else(math_error!())?
+            .checked_div(I80F48::from_num(self.end_time - self.start_time))
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Integral of the step decayed rate over `[from, to]`, within the schedule.
+    ///
+    /// The rate of step `k` is the linear rate at its start, `start_time + k * step_period`.
+    fn calc_step_rate_integral(&self, rate: I80F48, from: i64, to: i64) -> AstrolendResult<I80F48> {
+        let step_period = self.step_period as i64;
+        let step_start = |step: i64| self.start_time + step * step_period;
+        let step_rate = |step: i64| self.get_linear_rate(rate, step_start(step));
+
+        let from_step = (from - self.start_time) / step_period;
+        let to_step = (to - self.start_time) / step_period;
+
+        if from_step == to_step {
+            return Ok(step_rate(from_step)?
+                .checked_mul(I80F48::from_num(to - from))
+                .ok_or_else(math_error!())?);
+        }
+
+        let first_step_integral = step_rate(from_step)?
+            .checked_mul(I80F48::from_num(step_start(from_step + 1) - from))
+            .ok_or_else(math_error!())?;
+        let last_step_integral = step_rate(to_step)?
+            .checked_mul(I80F48::from_num(to - step_start(to_step)))
+            .ok_or_else(math_error!())?;
+
+        // Full steps in between, their rates form an arithmetic series
+        let full_steps = to_step - from_step - 1;
+        let full_steps_integral = if full_steps > 0 {
+            step_rate(from_step + 1)?
+                .checked_add(step_rate(to_step - 1)?)
+                .ok_or_else(math_error!())?
+                .checked_mul(I80F48::from_num(full_steps))
+                .ok_or_else(math_error!())?
+                .checked_div(I80F48::from_num(2))
+                .ok_or_else(math_error!())?
+                .checked_mul(I80F48::from_num(step_period))
+                .ok_or_else(math_error!())?
+        } else {
+            I80F48::ZERO
+        };
+
+        Ok(first_step_integral
+            .checked_add(full_steps_integral)
+            .ok_or_else(math_error!())?
+            .checked_add(last_step_integral)
+            .ok_or_else(math_error!())?)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Clone, Debug)]
+pub struct EmissionsScheduleConfig {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub end_rate: u64,
+    pub step_period: u64,
+    pub decay: EmissionsDecay,
+}
+
+impl From<EmissionsScheduleConfig> for EmissionsSchedule {
+    fn from(config: EmissionsScheduleConfig) -> Self {
+        Self {
+            start_time: config.start_time,
+            end_time: config.end_time,
+            end_rate: config.end_rate,
+            step_period: config.step_period,
+            decay: config.decay,
+            ..Default::default()
+        }
+    }
+}
+
 /// We use a simple interest rate model that auto settles the accrued interest into the lending account balances.
 /// The plan is to move to a compound interest model in the future.
 ///
@@ -884,6 +1514,34 @@ fn calc_interest_rate_accrual_state_changes(
     ))
 }
 
+/// Interest is accrued on the backed deposits only while the bank has a deficit, since the
+/// rest of the deposits earns nothing for the bank. `deficit_interest_share` of that interest
+/// repays the deficit, the rest is distributed over all deposits.
+///
+/// Returns the new asset share value and the repaid deficit.
+fn calc_deficit_repayment(
+    asset_share_value: I80F48,
+    backed_asset_share_value: I80F48,
+    total_assets: I80F48,
+    backed_assets: I80F48,
+    deficit_interest_share: I80F48,
+) -> Option<(I80F48, I80F48)> {
+    let deficit = total_assets.checked_sub(backed_assets)?;
+
+    let interest = backed_assets
+        .checked_mul(backed_asset_share_value.checked_div(asset_share_value)?)?
+        .checked_sub(backed_assets)?;
+
+    let deficit_repaid = interest.checked_mul(deficit_interest_share)?.min(deficit);
+
+    let distributed_interest = interest.checked_sub(deficit_repaid)?;
+
+    let new_asset_share_value = asset_share_value
+        .checked_mul(I80F48::ONE.checked_add(distributed_interest.checked_div(total_assets)?)?)?;
+
+    Some((new_asset_share_value, deficit_repaid))
+}
+
 /// Calculates the fee rate for a given base rate and fees specified.
 /// The returned rate is only the fee rate without the base rate.
 ///
@@ -1019,7 +1677,9 @@ impl From<BankConfigCompact> for BankConfig {
             _pad1: [0; 7],
             total_asset_value_init_limit: config.total_asset_value_init_limit,
             oracle_max_age: config.oracle_max_age,
-            _padding: [0; 38],
+            flashloan_fee_bps: 0,
+            soft_liquidation_threshold: I80F48::ZERO.into(),
+            _padding: [0; 20],
         }
     }
 }
@@ -1092,7 +1752,16 @@ pub struct BankConfig {
     /// Time window in seconds for the oracle price feed to be considered live.
     pub oracle_max_age: u16,
 
-    pub _padding: [u8; 38],
+    /// Fee in basis points on liabilities opened and repaid during a flashloan, charged on
+    /// repayment and credited to the group fees.
+    pub flashloan_fee_bps: u16,
+
+    /// Position of the soft liquidation requirement between the maintenance (0) and the initial
+    /// (1) weights. Accounts failing it but not the maintenance requirement can be partially
+    /// liquidated at a reduced fee. Zero disables the soft liquidation zone.
+    pub soft_liquidation_threshold: WrappedI80F48,
+
+    pub _padding: [u8; 20],
 }
 
 impl Default for BankConfig {
@@ -1113,7 +1782,9 @@ impl Default for BankConfig {
             _pad1: [0; 7],
             total_asset_value_init_limit: TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
             oracle_max_age: 0,
-            _padding: [0; 38],
+            flashloan_fee_bps: 0,
+            soft_liquidation_threshold: I80F48::ZERO.into(),
+            _padding: [0; 20],
         }
     }
 }
@@ -1130,6 +1801,10 @@ impl BankConfig {
                 self.asset_weight_maint.into(),
                 self.liability_weight_maint.into(),
             ),
+            RequirementType::SoftLiquidation => (
+                self.get_weight(RequirementType::SoftLiquidation, BalanceSide::Assets),
+                self.get_weight(RequirementType::SoftLiquidation, BalanceSide::Liabilities),
+            ),
             RequirementType::Equity => (I80F48::ONE, I80F48::ONE),
         }
     }
@@ -1149,10 +1824,28 @@ impl BankConfig {
             (RequirementType::Maintenance, BalanceSide::Liabilities) => {
                 self.liability_weight_maint.into()
             }
+            (RequirementType::SoftLiquidation, BalanceSide::Assets) => self
+                .get_soft_liquidation_weight(
+                    self.asset_weight_init.into(),
+                    self.asset_weight_maint.into(),
+                ),
+            (RequirementType::SoftLiquidation, BalanceSide::Liabilities) => self
+                .get_soft_liquidation_weight(
+                    self.liability_weight_init.into(),
+                    self.liability_weight_maint.into(),
+                ),
             (RequirementType::Equity, _) => I80F48::ONE,
         }
     }
 
+    /// `maint + (init - maint) * soft_liquidation_threshold`, always between both weights.
+    #[inline]
+    fn get_soft_liquidation_weight(&self, weight_init: I80F48, weight_maint: I80F48) -> I80F48 {
+        let threshold = I80F48::from(self.soft_liquidation_threshold);
+
+        weight_maint + (weight_init - weight_maint) * threshold
+    }
+
     pub fn validate(&self) -> AstrolendResult {
         let asset_init_w = I80F48::from(self.asset_weight_init);
         let asset_maint_w = I80F48::from(self.asset_weight_maint);
@@ -1172,6 +1865,13 @@ impl BankConfig {
             AstrolendError::InvalidConfig
         );
 
+        let soft_liquidation_threshold = I80F48::from(self.soft_liquidation_threshold);
+
+        check!(
+            soft_liquidation_threshold >= I80F48::ZERO && soft_liquidation_threshold <= I80F48::ONE,
+            AstrolendError::InvalidConfig
+        );
+
         self.interest_rate_config.validate()?;
 
         if self.risk_tier == RiskTier::Isolated {
@@ -1253,9 +1953,9 @@ impl From<WrappedI80F48> for I80F48 {
 
 #[cfg_attr(
     any(feature = "test", feature = "client"),
-    derive(Clone, PartialEq, Eq, TypeLayout)
+    derive(PartialEq, Eq, TypeLayout)
 )]
-#[derive(AnchorDeserialize, AnchorSerialize, Default)]
+#[derive(AnchorDeserialize, AnchorSerialize, Default, Clone)]
 pub struct BankConfigOpt {
     pub asset_weight_init: Option<WrappedI80F48>,
     pub asset_weight_maint: Option<WrappedI80F48>,
@@ -1279,6 +1979,105 @@ pub struct BankConfigOpt {
     pub oracle_max_age: Option<u16>,
 
     pub permissionless_bad_debt_settlement: Option<bool>,
+
+    pub deficit_interest_share: Option<WrappedI80F48>,
+
+    pub flashloan_fee_bps: Option<u16>,
+
+    pub flashloan_borrow_disabled: Option<bool>,
+
+    pub soft_liquidation_threshold: Option<WrappedI80F48>,
+}
+
+impl BankConfigOpt {
+    /// Max serialized size of the config, used to size accounts that store it.
+    pub const MAX_SIZE: usize = 4 * (1 + 16) // weights
+        + 2 * (1 + 8) // deposit and borrow limits
+        + (1 + 1) // operational state
+        + (1 + 1 + 32 * MAX_ORACLE_KEYS) // oracle
+        + (1 + 7 * (1 + 16)) // interest rate config
+        + (1 + 1) // risk tier
+        + (1 + 8) // total asset value init limit
+        + (1 + 2) // oracle max age
+        + (1 + 1) // permissionless bad debt settlement
+        + (1 + 16) // deficit interest share
+        + (1 + 2) // flashloan fee
+        + (1 + 1) // flashloan borrow disabled
+        + (1 + 16); // soft liquidation threshold
+
+    /// Risk reducing changes can be applied without going through the bank config timelock.
+    ///
+    /// A change is risk reducing if it only pauses the bank, puts it in reduce only mode,
+    /// lowers the deposit and borrow limits, or disables borrowing during flashloans.
+    pub fn is_risk_reducing(&self, bank: &Bank) -> bool {
+        let BankConfigOpt {
+            asset_weight_init,
+            asset_weight_maint,
+            liability_weight_init,
+            liability_weight_maint,
+            deposit_limit,
+            borrow_limit,
+            operational_state,
+            oracle,
+            interest_rate_config,
+            risk_tier,
+            total_asset_value_init_limit,
+            oracle_max_age,
+            permissionless_bad_debt_settlement,
+            deficit_interest_share,
+            flashloan_fee_bps,
+            flashloan_borrow_disabled,
+            soft_liquidation_threshold,
+        } = self;
+
+        if asset_weight_init.is_some()
+            || asset_weight_maint.is_some()
+            || liability_weight_init.is_some()
+            || liability_weight_maint.is_some()
+            || oracle.is_some()
+            || interest_rate_config.is_some()
+            || risk_tier.is_some()
+            || total_asset_value_init_limit.is_some()
+            || oracle_max_age.is_some()
+            || permissionless_bad_debt_settlement.is_some()
+            || deficit_interest_share.is_some()
+            || flashloan_fee_bps.is_some()
+            || matches!(flashloan_borrow_disabled, Some(false))
+            || soft_liquidation_threshold.is_some()
+        {
+            return false;
+        }
+
+        let operational_state_ok = match operational_state {
+            None | Some(BankOperationalState::Paused) => true,
+            Some(BankOperationalState::ReduceOnly) => {
+                !matches!(bank.config.operational_state, BankOperationalState::Paused)
+            }
+            Some(BankOperationalState::Operational) => false,
+        };
+
+        operational_state_ok
+            && deposit_limit.map_or(true, |limit| limit <= bank.config.deposit_limit)
+            && borrow_limit.map_or(true, |limit| limit <= bank.config.borrow_limit)
+    }
+}
+
+/// A bank config change waiting for the group's bank config delay to pass.
+///
+/// PDA: [PENDING_BANK_CONFIG_SEED, bank]
+#[account]
+#[derive(Default)]
+pub struct PendingBankConfig {
+    pub bank: Pubkey,
+    /// Account that paid the rent for this account, refunded on execution or cancellation.
+    pub rent_payer: Pubkey,
+    /// Unix timestamp after which the config can be applied.
+    pub earliest_execution_time: i64,
+    pub config: BankConfigOpt,
+}
+
+impl PendingBankConfig {
+    pub const LEN: usize = 32 + 32 + 8 + BankConfigOpt::MAX_SIZE;
 }
 
 #[cfg_attr(
@@ -1648,4 +2447,367 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn bank_config_opt_risk_reducing() {
+        let bank = Bank {
+            config: BankConfig {
+                deposit_limit: 1_000,
+                borrow_limit: 1_000,
+                operational_state: BankOperationalState::Operational,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(BankConfigOpt::default().is_risk_reducing(&bank));
+
+        assert!(BankConfigOpt {
+            operational_state: Some(BankOperationalState::Paused),
+            deposit_limit: Some(500),
+            borrow_limit: Some(1_000),
+            ..Default::default()
+        }
+        .is_risk_reducing(&bank));
+
+        assert!(BankConfigOpt {
+            operational_state: Some(BankOperationalState::ReduceOnly),
+            ..Default::default()
+        }
+        .is_risk_reducing(&bank));
+
+        // Raising a limit must go through the timelock
+        assert!(!BankConfigOpt {
+            deposit_limit: Some(1_001),
+            ..Default::default()
+        }
+        .is_risk_reducing(&bank));
+
+        // Weight changes must go through the timelock, even when bundled with a pause
+        assert!(!BankConfigOpt {
+            operational_state: Some(BankOperationalState::Paused),
+            asset_weight_init: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        }
+        .is_risk_reducing(&bank));
+
+        // Unpausing a bank must go through the timelock
+        let paused_bank = Bank {
+            config: BankConfig {
+                operational_state: BankOperationalState::Paused,
+                ..bank.config
+            },
+            ..bank
+        };
+
+        assert!(!BankConfigOpt {
+            operational_state: Some(BankOperationalState::ReduceOnly),
+            ..Default::default()
+        }
+        .is_risk_reducing(&paused_bank));
+
+        assert!(!BankConfigOpt {
+            operational_state: Some(BankOperationalState::Operational),
+            ..Default::default()
+        }
+        .is_risk_reducing(&paused_bank));
+    }
+
+    #[test]
+    fn group_config_delay_only_increases_up_to_max() {
+        let mut group = AstrolendGroup::default();
+
+        group
+            .configure(&GroupConfig {
+                bank_config_delay: Some(3600),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(group.bank_config_delay, 3600);
+
+        assert!(group
+            .configure(&GroupConfig {
+                bank_config_delay: Some(0),
+                ..Default::default()
+            })
+            .is_err());
+        assert_eq!(group.bank_config_delay, 3600);
+
+        group
+            .configure(&GroupConfig {
+                bank_config_delay: Some(7200),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(group.bank_config_delay, 7200);
+
+        assert!(group
+            .configure(&GroupConfig {
+                bank_config_delay: Some(MAX_BANK_CONFIG_DELAY + 1),
+                ..Default::default()
+            })
+            .is_err());
+        assert_eq!(group.bank_config_delay, 7200);
+    }
+
+    #[test]
+    /// backed interest: 10% on 800 of 1000 deposits
+    fn deficit_repayment() {
+        // Half of the interest repays the deficit
+        let (asset_share_value, deficit_repaid) = calc_deficit_repayment(
+            I80F48!(1),
+            I80F48!(1.1),
+            I80F48!(1000),
+            I80F48!(800),
+            I80F48!(0.5),
+        )
+        .unwrap();
+
+        assert_eq_with_tolerance!(deficit_repaid, I80F48!(40), I80F48!(0.001));
+        assert_eq_with_tolerance!(asset_share_value, I80F48!(1.04), I80F48!(0.001));
+
+        // All of the interest repays the deficit
+        let (asset_share_value, deficit_repaid) = calc_deficit_repayment(
+            I80F48!(1),
+            I80F48!(1.1),
+            I80F48!(1000),
+            I80F48!(800),
+            I80F48!(1),
+        )
+        .unwrap();
+
+        assert_eq_with_tolerance!(deficit_repaid, I80F48!(80), I80F48!(0.001));
+        assert_eq_with_tolerance!(asset_share_value, I80F48!(1), I80F48!(0.001));
+
+        // Repayment is capped by the deficit, the rest goes to depositors
+        let (asset_share_value, deficit_repaid) = calc_deficit_repayment(
+            I80F48!(1),
+            I80F48!(1.1),
+            I80F48!(1000),
+            I80F48!(990),
+            I80F48!(1),
+        )
+        .unwrap();
+
+        assert_eq_with_tolerance!(deficit_repaid, I80F48!(10), I80F48!(0.001));
+        assert_eq_with_tolerance!(asset_share_value, I80F48!(1.089), I80F48!(0.001));
+    }
+
+    #[test]
+    fn deficit_discount() {
+        let mut bank = Bank {
+            asset_share_value: I80F48!(1).into(),
+            total_asset_shares: I80F48!(1000).into(),
+            ..Default::default()
+        };
+
+        assert!(bank.maybe_get_deficit_discount().unwrap().is_none());
+
+        bank.record_deficit(I80F48!(250)).unwrap();
+
+        assert_eq_with_tolerance!(
+            bank.maybe_get_deficit_discount().unwrap().unwrap(),
+            I80F48!(0.75),
+            I80F48!(0.001)
+        );
+        assert_eq_with_tolerance!(
+            bank.get_backed_asset_amount().unwrap(),
+            I80F48!(750),
+            I80F48!(0.001)
+        );
+
+        // Paying out 150 burns 200 of deposits, the forfeited 50 repay the deficit
+        bank.change_asset_shares(I80F48!(-200), false).unwrap();
+        bank.repay_deficit(I80F48!(50)).unwrap();
+
+        assert_eq_with_tolerance!(
+            bank.maybe_get_deficit_discount().unwrap().unwrap(),
+            I80F48!(0.75),
+            I80F48!(0.001)
+        );
+        assert_eq_with_tolerance!(
+            bank.get_backed_asset_amount().unwrap(),
+            I80F48!(600),
+            I80F48!(0.001)
+        );
+    }
+
+    #[test]
+    fn emissions_schedule() {
+        let schedule = EmissionsSchedule {
+            start_time: 100,
+            end_time: 200,
+            ..Default::default()
+        };
+        assert_eq!(
+            schedule.get_period_and_rate(10, 50, 150).unwrap(),
+            (I80F48!(50), I80F48!(10))
+        );
+        assert_eq!(
+            schedule.get_period_and_rate(10, 150, 300).unwrap(),
+            (I80F48!(50), I80F48!(10))
+        );
+        assert_eq!(
+            schedule.get_period_and_rate(10, 250, 300).unwrap(),
+            (I80F48::ZERO, I80F48::ZERO)
+        );
+
+        // Clawed back emissions stop at the clawback
+        let clawed_back = EmissionsSchedule {
+            clawback_time: 120,
+            ..schedule
+        };
+        assert_eq!(
+            clawed_back.get_period_and_rate(10, 50, 150).unwrap(),
+            (I80F48!(20), I80F48!(10))
+        );
+        assert_eq!(
+            clawed_back.get_period_and_rate(10, 130, 150).unwrap(),
+            (I80F48::ZERO, I80F48::ZERO)
+        );
+
+        let linear = EmissionsSchedule {
+            end_rate: 0,
+            decay: EmissionsDecay::Linear,
+            ..schedule
+        };
+        assert_eq!(
+            linear.get_period_and_rate(100, 0, 300).unwrap(),
+            (I80F48!(100), I80F48!(50))
+        );
+        assert_eq!(
+            linear.get_period_and_rate(100, 150, 200).unwrap(),
+            (I80F48!(50), I80F48!(25))
+        );
+
+        // Step rates: 100, 75, 50, 25
+        let step = EmissionsSchedule {
+            step_period: 25,
+            decay: EmissionsDecay::Step,
+            ..linear
+        };
+        assert_eq!(
+            step.get_period_and_rate(100, 0, 300).unwrap(),
+            (I80F48!(100), I80F48!(62.5))
+        );
+        assert_eq!(
+            step.get_period_and_rate(100, 110, 160).unwrap(),
+            (I80F48!(50), I80F48!(77.5))
+        );
+        assert_eq!(
+            step.get_period_and_rate(100, 110, 120).unwrap(),
+            (I80F48!(10), I80F48!(100))
+        );
+
+        assert!(schedule.validate().is_ok());
+        assert!(step.validate().is_ok());
+        assert!(EmissionsSchedule {
+            end_time: 50,
+            ..schedule
+        }
+        .validate()
+        .is_err());
+        assert!(EmissionsSchedule {
+            start_time: 0,
+            ..linear
+        }
+        .validate()
+        .is_err());
+        assert!(EmissionsSchedule {
+            step_period: 0,
+            ..step
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn emissions_schedule_update() {
+        let schedule = EmissionsSchedule {
+            start_time: 100,
+            end_time: 200,
+            ..Default::default()
+        };
+
+        // Before the start, the schedule can move anywhere in the future
+        let later = EmissionsSchedule {
+            start_time: 150,
+            end_time: 300,
+            end_rate: 0,
+            decay: EmissionsDecay::Linear,
+            ..Default::default()
+        };
+        assert!(schedule.can_update_to(&later, 0, 50));
+        assert!(!schedule.can_update_to(
+            &EmissionsSchedule {
+                start_time: 40,
+                ..later
+            },
+            0,
+            50
+        ));
+        // Setup at the current time, nothing was emitted
+        assert!(EmissionsSchedule::default().can_update_to(&later, 50, 50));
+        assert!(!EmissionsSchedule::default().can_update_to(&later, 40, 50));
+
+        // Once started, only the future end of a non-decaying schedule can change
+        assert!(schedule.can_update_to(
+            &EmissionsSchedule {
+                end_time: 300,
+                ..schedule
+            },
+            0,
+            150
+        ));
+        assert!(schedule.can_update_to(
+            &EmissionsSchedule {
+                end_time: 0,
+                ..schedule
+            },
+            0,
+            150
+        ));
+        assert!(!schedule.can_update_to(
+            &EmissionsSchedule {
+                end_time: 120,
+                ..schedule
+            },
+            0,
+            150
+        ));
+        assert!(!schedule.can_update_to(
+            &EmissionsSchedule {
+                start_time: 120,
+                ..schedule
+            },
+            0,
+            150
+        ));
+        assert!(!schedule.can_update_to(&later, 0, 150));
+
+        // Ended schedules are final
+        assert!(!schedule.can_update_to(
+            &EmissionsSchedule {
+                end_time: 300,
+                ..schedule
+            },
+            0,
+            250
+        ));
+
+        // The decay of a started schedule depends on its end time
+        let linear = EmissionsSchedule {
+            decay: EmissionsDecay::Linear,
+            ..schedule
+        };
+        assert!(linear.can_update_to(&linear, 0, 150));
+        assert!(!linear.can_update_to(
+            &EmissionsSchedule {
+                end_time: 300,
+                ..linear
+            },
+            0,
+            150
+        ));
+    }
 }
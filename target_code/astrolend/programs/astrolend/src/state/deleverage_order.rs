@@ -0,0 +1,78 @@
+use super::astrolend_group::WrappedI80F48;
+use crate::{
+    assert_struct_align, assert_struct_size, check,
+    constants::MAX_DELEVERAGE_ORDER_KEEPER_FEE_BPS,
+    prelude::{AstrolendError, AstrolendResult},
+};
+use anchor_lang::prelude::*;
+
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+/// A conditional order to reduce a position before it becomes liquidatable.
+///
+/// Once the maintenance health of the account drops below `trigger_health`, any keeper can
+/// withdraw `withdraw_amount` of collateral, swap it through a swap program allowed by the group
+/// and repay the liability with at least `min_repay_amount`. The keeper earns `keeper_fee_bps` of
+/// the withdrawn collateral. The order is closed once executed, and can't be executed once the
+/// account has another authority than the one that placed it.
+///
+/// PDA: [DELEVERAGE_ORDER_SEED, astrolend_account, collateral_bank, liability_bank]
+assert_struct_size!(DeleverageOrder, 168);
+assert_struct_align!(DeleverageOrder, 8);
+#[account(zero_copy)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+pub struct DeleverageOrder {
+    pub astrolend_account: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub liability_bank: Pubkey,
+    /// Maintenance health, in USD, below which the order can be executed.
+    /// See `RiskEngine::get_account_health`.
+    pub trigger_health: WrappedI80F48,
+    /// Amount of collateral swapped, in native tokens, the keeper fee excluded.
+    pub withdraw_amount: u64,
+    /// Minimum amount of liability tokens the swap must return, in native tokens.
+    pub min_repay_amount: u64,
+    /// Fee paid to the keeper, in basis points of `withdraw_amount`, in collateral tokens.
+    pub keeper_fee_bps: u16,
+    pub _pad0: [u8; 6],
+    /// The account authority that placed the order.
+    pub authority: Pubkey,
+}
+
+impl DeleverageOrder {
+    pub const LEN: usize = std::mem::size_of::<DeleverageOrder>();
+
+    pub fn configure(
+        &mut self,
+        trigger_health: WrappedI80F48,
+        withdraw_amount: u64,
+        min_repay_amount: u64,
+        keeper_fee_bps: u16,
+    ) -> AstrolendResult {
+        check!(
+            withdraw_amount > 0 && min_repay_amount > 0,
+            AstrolendError::InvalidConfig
+        );
+        check!(
+            keeper_fee_bps <= MAX_DELEVERAGE_ORDER_KEEPER_FEE_BPS,
+            AstrolendError::InvalidConfig
+        );
+
+        self.trigger_health = trigger_health;
+        self.withdraw_amount = withdraw_amount;
+        self.min_repay_amount = min_repay_amount;
+        self.keeper_fee_bps = keeper_fee_bps;
+
+        Ok(())
+    }
+
+    /// Keeper fee for the withdrawn amount, rounded down.
+    pub fn get_keeper_fee(&self) -> u64 {
+        (self.withdraw_amount as u128 * self.keeper_fee_bps as u128 / 10_000) as u64
+    }
+}
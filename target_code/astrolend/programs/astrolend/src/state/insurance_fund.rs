@@ -0,0 +1,118 @@
+use crate::{assert_struct_align, assert_struct_size};
+use anchor_lang::prelude::*;
+
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+/// A pending withdrawal from a bank's insurance fund.
+///
+/// Shares are held in the request's escrow, and keep absorbing bad debt, until the request is
+/// completed after the bank's cooldown. The request can only be completed within the bank's
+/// unstake window, cancelling it returns the shares to the staker.
+///
+/// PDA: [INSURANCE_UNSTAKE_REQUEST_SEED, bank, staker]
+/// Escrow PDA: [INSURANCE_UNSTAKE_ESCROW_SEED, unstake_request]
+assert_struct_size!(InsuranceUnstakeRequest, 88);
+assert_struct_align!(InsuranceUnstakeRequest, 8);
+#[account(zero_copy)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+pub struct InsuranceUnstakeRequest {
+    pub bank: Pubkey,
+    pub staker: Pubkey,
+    /// Amount of insurance share tokens escrowed, burned on completion.
+    pub shares: u64,
+    /// Unix timestamp after which the request can be completed.
+    pub cooldown_end: i64,
+    pub _padding: [u64; 1],
+}
+
+impl InsuranceUnstakeRequest {
+    pub const LEN: usize = std::mem::size_of::<InsuranceUnstakeRequest>();
+}
+
+/// Number of insurance shares to mint for `amount` of tokens added to the insurance vault.
+///
+/// A virtual share and token are added to both sides so that inflating the share price by
+/// donating to the vault is unprofitable, and so that a vault emptied by bad debt can be
+/// restaked while worthless shares are still outstanding.
+pub fn calc_insurance_shares_to_mint(
+    amount: u64,
+    insurance_vault_amount: u64,
+    share_supply: u64,
+) -> Option<u64> {
+    let shares = (amount as u128)
+        .checked_mul((share_supply as u128).checked_add(1)?)?
+        .checked_div((insurance_vault_amount as u128).checked_add(1)?)?;
+
+    shares.try_into().ok()
+}
+
+/// Amount of tokens owed to a staker burning `shares` insurance shares.
+pub fn calc_insurance_shares_value(
+    shares: u64,
+    insurance_vault_amount: u64,
+    share_supply: u64,
+) -> Option<u64> {
+    let amount = (shares as u128)
+        .checked_mul((insurance_vault_amount as u128).checked_add(1)?)?
+        .checked_div((share_supply as u128).checked_add(1)?)?;
+
+    amount.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insurance_shares_first_stake() {
+        assert_eq!(calc_insurance_shares_to_mint(1_000, 0, 0), Some(1_000));
+        assert_eq!(calc_insurance_shares_value(1_000, 1_000, 1_000), Some(1_000));
+    }
+
+    #[test]
+    fn insurance_shares_after_fees() {
+        // Vault doubled through insurance fees, new stakers get half as many shares
+        let shares = calc_insurance_shares_to_mint(1_000, 2_000, 1_000).unwrap();
+        assert_eq!(shares, 500);
+
+        let value = calc_insurance_shares_value(1_000, 3_000, 1_500).unwrap();
+        assert_eq!(value, 1_999);
+    }
+
+    #[test]
+    fn insurance_shares_after_bankruptcy() {
+        // Vault halved by bad debt, stakers absorb the loss pro rata
+        let value = calc_insurance_shares_value(500, 1_000, 2_000).unwrap();
+        assert_eq!(value, 250);
+
+        // Vault fully drained, a new staker owns nearly all of the fund
+        let shares = calc_insurance_shares_to_mint(1_000, 0, 2_000).unwrap();
+        assert_eq!(shares, 2_001_000);
+        let value = calc_insurance_shares_value(shares, 1_000, 2_000 + shares).unwrap();
+        assert_eq!(value, 1_000);
+    }
+
+    #[test]
+    fn insurance_shares_donation() {
+        // Inflating the share price by donating to the vault costs the attacker more than
+        // what they take from the next staker
+        let attacker_shares = calc_insurance_shares_to_mint(1, 0, 0).unwrap();
+        let donation = 1_000_000;
+        let vault_amount = 1 + donation;
+
+        let victim_shares =
+            calc_insurance_shares_to_mint(1_000_000, vault_amount, attacker_shares).unwrap();
+        assert!(victim_shares > 0);
+
+        let vault_amount = vault_amount + 1_000_000;
+        let share_supply = attacker_shares + victim_shares;
+        let attacker_value =
+            calc_insurance_shares_value(attacker_shares, vault_amount, share_supply).unwrap();
+        assert!(attacker_value < 1 + donation);
+    }
+}
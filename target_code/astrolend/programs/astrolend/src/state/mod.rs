@@ -1,3 +1,6 @@
+pub mod account_delegate;
 pub mod astrolend_account;
 pub mod astrolend_group;
+pub mod deleverage_order;
+pub mod insurance_fund;
 pub mod price;
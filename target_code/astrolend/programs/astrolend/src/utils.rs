@@ -16,6 +16,7 @@ use anchor_spl::{
     token_interface::Mint,
 };
 use fixed::types::I80F48;
+use solana_program::{instruction::Instruction, program::invoke};
 
 pub fn find_bank_vault_pda(bank_pk: &Pubkey, vault_type: BankVaultType) -> (Pubkey, u8) {
     Pubkey::find_program_address(bank_seed!(vault_type, bank_pk), &crate::id())
@@ -143,6 +144,36 @@ pub fn maybe_take_bank_mint<'info>(
     }
 }
 
+/// Invoke `program` with `data`, forwarding `accounts` with their signer and writable flags.
+///
+/// Used to hand control to an external program (flashloan callback, swap router) from the middle
+/// of an instruction, astrolend itself never signs for the forwarded accounts.
+pub fn invoke_with_forwarded_accounts<'info>(
+    program: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    data: Vec<u8>,
+) -> AstrolendResult {
+    let ix = Instruction {
+        program_id: program.key(),
+        accounts: accounts
+            .iter()
+            .map(|ai| AccountMeta {
+                pubkey: ai.key(),
+                is_signer: ai.is_signer,
+                is_writable: ai.is_writable,
+            })
+            .collect(),
+        data,
+    };
+
+    let mut account_infos = accounts.to_vec();
+    account_infos.push(program.clone());
+
+    invoke(&ix, &account_infos)?;
+
+    Ok(())
+}
+
 const ONE_IN_BASIS_POINTS: u128 = 10_000;
 /// backported fix from
 /// https://github.com/solana-labs/solana-program-library/commit/20e6792179fc7f1251579c1c33a4a0feec48e15e
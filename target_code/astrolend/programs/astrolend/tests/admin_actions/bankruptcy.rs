@@ -1,3 +1,4 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
 use anchor_spl::token_2022::spl_token_2022::extension::{
     transfer_fee::TransferFeeConfig, BaseStateWithExtensions,
 };
@@ -6,10 +7,15 @@ use fixed_macro::types::I80F48;
 use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
 use astrolend::{
     prelude::{GroupConfig, AstrolendError},
-    state::astrolend_group::{BankConfig, BankVaultType},
+    state::{
+        astrolend_account::{BalanceSide, DISABLED_FLAG},
+        astrolend_group::{BankConfig, BankVaultType},
+    },
 };
 use pretty_assertions::assert_eq;
+use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program_test::*;
+use solana_sdk::{signer::Signer, transaction::Transaction};
 use test_case::test_case;
 
 #[test_case(BankMint::Usdc, BankMint::Sol)]
@@ -853,7 +859,12 @@ async fn astrolend_group_handle_bankruptcy_success_not_insured_3_depositors() ->
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -939,3 +950,155 @@ async fn astrolend_group_handle_bankruptcy_success_not_insured_3_depositors() ->
 
     Ok(())
 }
+
+/// Remaining accounts of `lending_pool_handle_account_bankruptcy` for the given liability banks.
+fn bankruptcy_bank_account_metas(banks: &[&BankFixture]) -> Vec<AccountMeta> {
+    banks
+        .iter()
+        .flat_map(|bank_f| {
+            vec![
+                AccountMeta::new(bank_f.key, false),
+                AccountMeta::new(bank_f.get_vault(BankVaultType::Liquidity).0, false),
+                AccountMeta::new(bank_f.get_vault(BankVaultType::Insurance).0, false),
+                AccountMeta::new_readonly(
+                    bank_f.get_vault_authority(BankVaultType::Insurance).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(bank_f.get_token_program(), false),
+            ]
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn astrolend_group_handle_account_bankruptcy_multiple_banks() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+    let sol_eq_bank_f = test_f.get_bank(&BankMint::SolEquivalent);
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(10_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 10_000)
+        .await?;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
+        .await?;
+
+    // Borrow from two banks against SOL-equivalent collateral
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_sol_eq = sol_eq_bank_f
+        .mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_astl_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq_bank_f, 1_000)
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_astl_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await?;
+    let borrower_token_account_sol = test_f.sol_mint.create_empty_token_account().await;
+    borrower_astl_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+
+    // Artificially nullify the collateral to place the account in a bankrupt state
+    let mut borrower_astl_account = borrower_astl_account_f.load().await;
+    borrower_astl_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0_i128.to_le_bytes();
+    borrower_astl_account_f
+        .set_account(&borrower_astl_account)
+        .await?;
+
+    let mut accounts = astrolend::accounts::LendingPoolHandleAccountBankruptcy {
+        astrolend_group: test_f.astrolend_group.key,
+        signer: test_f.payer(),
+        astrolend_account: borrower_astl_account_f.key,
+    }
+    .to_account_metas(Some(true));
+    // Bank groups in a different order than the balances
+    accounts.extend(bankruptcy_bank_account_metas(&[sol_bank_f, usdc_bank_f]));
+    accounts.extend(
+        borrower_astl_account_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+
+    let make_ix = |hook_accounts_lens: Vec<u8>| Instruction {
+        program_id: astrolend::id(),
+        accounts: accounts.clone(),
+        data: astrolend::instruction::LendingPoolHandleAccountBankruptcy { hook_accounts_lens }
+            .data(),
+    };
+
+    // SPL Token bank groups take no transfer hook accounts
+    let res = {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix(vec![1, 0])],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await
+    };
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidBankAccount);
+
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix(vec![0, 0])],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
+    }
+
+    let borrower_astl_account = borrower_astl_account_f.load().await;
+    assert!(borrower_astl_account.get_flag(DISABLED_FLAG));
+    assert!(borrower_astl_account
+        .lending_account
+        .balances
+        .iter()
+        .all(|balance| !balance.active || balance.is_empty(BalanceSide::Liabilities)));
+
+    // Both losses are socialized among the lender's deposits
+    let lender_astl_account = lender_astl_account_f.load().await;
+    let usdc_bank = usdc_bank_f.load().await;
+    let sol_bank = sol_bank_f.load().await;
+
+    assert_eq_noise!(
+        usdc_bank.get_asset_amount(
+            lender_astl_account.lending_account.balances[0]
+                .asset_shares
+                .into()
+        )?,
+        I80F48::from(native!(9_000, "USDC")),
+        I80F48::from(native!(1, "USDC"))
+    );
+    assert_eq_noise!(
+        sol_bank.get_asset_amount(
+            lender_astl_account.lending_account.balances[1]
+                .asset_shares
+                .into()
+        )?,
+        I80F48::from(native!(900, "SOL")),
+        I80F48::from(native!(1, "SOL"))
+    );
+
+    Ok(())
+}
@@ -16,7 +16,12 @@ use solana_sdk::pubkey::Pubkey;
 #[tokio::test]
 async fn astrolend_group_handle_bankruptcy_unauthorized() -> anyhow::Result<()> {
     let mut test_f = TestFixture::new(Some(TestSettings {
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
         banks: vec![
             TestBankSetting {
                 mint: BankMint::Usdc,
@@ -91,6 +96,9 @@ async fn astrolend_group_handle_bankruptcy_unauthorized() -> anyhow::Result<()>
         .astrolend_group
         .try_update(GroupConfig {
             admin: Some(Pubkey::new_unique()),
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
         })
         .await?;
 
@@ -110,7 +118,12 @@ async fn astrolend_group_handle_bankruptcy_unauthorized() -> anyhow::Result<()>
 #[tokio::test]
 async fn astrolend_group_handle_bankruptcy_perimssionless() -> anyhow::Result<()> {
     let mut test_f = TestFixture::new(Some(TestSettings {
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
         banks: vec![
             TestBankSetting {
                 mint: BankMint::Usdc,
@@ -193,6 +206,9 @@ async fn astrolend_group_handle_bankruptcy_perimssionless() -> anyhow::Result<()
         .astrolend_group
         .try_update(GroupConfig {
             admin: Some(Pubkey::new_unique()),
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
         })
         .await?;
 
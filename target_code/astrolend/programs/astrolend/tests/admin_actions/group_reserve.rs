@@ -0,0 +1,417 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    constants::{GROUP_RESERVE_VAULT_AUTHORITY_SEED, GROUP_RESERVE_VAULT_SEED},
+    prelude::{GroupConfig, AstrolendError},
+    state::{
+        astrolend_account::DISABLED_FLAG,
+        astrolend_group::{BankConfig, BankConfigOpt, BankVaultType},
+    },
+};
+use pretty_assertions::assert_eq;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::*;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+// Group reserve tests
+// 1. The reserve buys down bad debt left after insurance, the rest is socialized
+// 2. The reserve price is read from a [reserve bank, oracle] pair
+// 3. Losses are only socialized once the reserve is exhausted, permissionless handling included
+// 4. The reserve haircut is capped
+
+async fn process_ix(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+fn get_reserve_vault_addresses(test_f: &TestFixture) -> (Pubkey, Pubkey) {
+    let group = test_f.astrolend_group.key;
+    (
+        Pubkey::find_program_address(
+            &[GROUP_RESERVE_VAULT_SEED.as_bytes(), group.as_ref()],
+            &astrolend::id(),
+        )
+        .0,
+        Pubkey::find_program_address(
+            &[
+                GROUP_RESERVE_VAULT_AUTHORITY_SEED.as_bytes(),
+                group.as_ref(),
+            ],
+            &astrolend::id(),
+        )
+        .0,
+    )
+}
+
+/// Lender deposits 100k USDC, borrower borrows 10k USDC against 1001 SOL, whose collateral is
+/// then nullified to make the account bankrupt.
+async fn setup_bankrupt_borrower() -> anyhow::Result<(
+    TestFixture,
+    AstrolendAccountFixture,
+    AstrolendAccountFixture,
+)> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::Usdc,
+                config: None,
+            },
+            TestBankSetting {
+                mint: BankMint::Sol,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_001)
+        .await;
+    borrower_astl_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 1_001)
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_astl_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_astl_account = borrower_astl_account_f.load().await;
+    borrower_astl_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0_i128.to_le_bytes();
+    borrower_astl_account_f
+        .set_account(&borrower_astl_account)
+        .await?;
+
+    Ok((test_f, lender_astl_account_f, borrower_astl_account_f))
+}
+
+/// Setup the group reserve in the SOL bank mint, funded with `amount` SOL.
+async fn setup_sol_group_reserve(test_f: &TestFixture, amount: f64) -> anyhow::Result<Pubkey> {
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+    let (reserve_vault, reserve_vault_authority) = get_reserve_vault_addresses(test_f);
+
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolSetupGroupReserve {
+            astrolend_group: test_f.astrolend_group.key,
+            admin: test_f.payer(),
+            reserve_bank: sol_bank_f.key,
+            reserve_mint: sol_bank_f.mint.key,
+            reserve_vault_authority,
+            reserve_vault,
+            token_program: sol_bank_f.get_token_program(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolSetupGroupReserve {}.data(),
+    };
+    process_ix(test_f, ix).await?;
+
+    sol_bank_f.mint.mint_to(&reserve_vault, amount).await;
+
+    Ok(reserve_vault)
+}
+
+async fn make_handle_bankruptcy_with_reserve_ix(
+    test_f: &TestFixture,
+    borrower_astl_account_f: &AstrolendAccountFixture,
+    signer_token_account: Pubkey,
+    signer_reserve_token_account: Pubkey,
+    reserve_pair_bank: &BankFixture,
+) -> Instruction {
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+    let (reserve_vault, reserve_vault_authority) = get_reserve_vault_addresses(test_f);
+
+    let mut accounts = astrolend::accounts::LendingPoolHandleBankruptcyWithReserve {
+        astrolend_group: test_f.astrolend_group.key,
+        signer: test_f.payer(),
+        bank: usdc_bank_f.key,
+        astrolend_account: borrower_astl_account_f.key,
+        liquidity_vault: usdc_bank_f.get_vault(BankVaultType::Liquidity).0,
+        insurance_vault: usdc_bank_f.get_vault(BankVaultType::Insurance).0,
+        insurance_vault_authority: usdc_bank_f.get_vault_authority(BankVaultType::Insurance).0,
+        reserve_bank: sol_bank_f.key,
+        reserve_mint: sol_bank_f.mint.key,
+        reserve_vault,
+        reserve_vault_authority,
+        signer_token_account,
+        signer_reserve_token_account,
+        token_program: usdc_bank_f.get_token_program(),
+        reserve_token_program: sol_bank_f.get_token_program(),
+    }
+    .to_account_metas(Some(true));
+    accounts.extend([
+        AccountMeta::new_readonly(reserve_pair_bank.key, false),
+        AccountMeta::new_readonly(reserve_pair_bank.load().await.config.oracle_keys[0], false),
+    ]);
+    accounts.extend(
+        borrower_astl_account_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingPoolHandleBankruptcyWithReserve {}.data(),
+    }
+}
+
+async fn make_handle_bankruptcy_ix(
+    test_f: &TestFixture,
+    borrower_astl_account_f: &AstrolendAccountFixture,
+) -> Instruction {
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let (reserve_vault, _) = get_reserve_vault_addresses(test_f);
+
+    let mut accounts = astrolend::accounts::LendingPoolHandleBankruptcy {
+        astrolend_group: test_f.astrolend_group.key,
+        signer: test_f.payer(),
+        bank: usdc_bank_f.key,
+        astrolend_account: borrower_astl_account_f.key,
+        liquidity_vault: usdc_bank_f.get_vault(BankVaultType::Liquidity).0,
+        insurance_vault: usdc_bank_f.get_vault(BankVaultType::Insurance).0,
+        insurance_vault_authority: usdc_bank_f.get_vault_authority(BankVaultType::Insurance).0,
+        token_program: usdc_bank_f.get_token_program(),
+    }
+    .to_account_metas(Some(true));
+    accounts.push(AccountMeta::new_readonly(reserve_vault, false));
+    accounts.extend(
+        borrower_astl_account_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingPoolHandleBankruptcy {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn group_reserve_buys_down_bad_debt() -> anyhow::Result<()> {
+    let (test_f, lender_astl_account_f, borrower_astl_account_f) =
+        setup_bankrupt_borrower().await?;
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    let reserve_vault = setup_sol_group_reserve(&test_f, 100.).await?;
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: Some(I80F48!(0.1).into()),
+            swap_programs: None,
+        })
+        .await?;
+
+    let buyer_usdc_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    let buyer_sol_account = test_f.sol_mint.create_empty_token_account().await;
+
+    let ix = make_handle_bankruptcy_with_reserve_ix(
+        &test_f,
+        &borrower_astl_account_f,
+        buyer_usdc_account.key,
+        buyer_sol_account.key,
+        sol_bank_f,
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    // 100 SOL of reserve at $10 and a 10% haircut buy down ~909 USDC of bad debt
+    assert_eq_noise!(
+        I80F48::from(buyer_usdc_account.balance().await),
+        I80F48::from(native!(1_091, "USDC")),
+        I80F48::from(native!(10, "USDC"))
+    );
+    assert_eq_noise!(
+        I80F48::from(buyer_sol_account.balance().await),
+        I80F48::from(native!(100, "SOL")),
+        I80F48::from(native!(1, "SOL"))
+    );
+    let reserve_vault_balance = TokenAccountFixture::fetch(test_f.context.clone(), reserve_vault)
+        .await
+        .balance()
+        .await;
+    assert!(reserve_vault_balance < native!(1, "SOL"));
+
+    let borrower_astl_account = borrower_astl_account_f.load().await;
+    assert!(borrower_astl_account.get_flag(DISABLED_FLAG));
+    assert_eq!(
+        I80F48::from(borrower_astl_account.lending_account.balances[1].liability_shares),
+        I80F48::ZERO
+    );
+
+    // The rest of the bad debt is socialized
+    let lender_astl_account = lender_astl_account_f.load().await;
+    assert_eq_noise!(
+        usdc_bank_f.load().await.get_asset_amount(
+            lender_astl_account.lending_account.balances[0]
+                .asset_shares
+                .into()
+        )?,
+        I80F48::from(native!(90_909, "USDC")),
+        I80F48::from(native!(10, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_reserve_requires_reserve_oracle_pair() -> anyhow::Result<()> {
+    let (test_f, _, borrower_astl_account_f) = setup_bankrupt_borrower().await?;
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+
+    setup_sol_group_reserve(&test_f, 100.).await?;
+
+    let buyer_usdc_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    let buyer_sol_account = test_f.sol_mint.create_empty_token_account().await;
+
+    // The USDC pair doesn't price the reserve
+    let ix = make_handle_bankruptcy_with_reserve_ix(
+        &test_f,
+        &borrower_astl_account_f,
+        buyer_usdc_account.key,
+        buyer_sol_account.key,
+        usdc_bank_f,
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::MissingPythOrBankAccount);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_reserve_exhausted_before_socializing() -> anyhow::Result<()> {
+    let (test_f, _, borrower_astl_account_f) = setup_bankrupt_borrower().await?;
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    let reserve_vault = setup_sol_group_reserve(&test_f, 100.).await?;
+
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            permissionless_bad_debt_settlement: Some(true),
+            ..Default::default()
+        })
+        .await?;
+
+    // The loss can't be socialized while the reserve holds tokens
+    let ix = make_handle_bankruptcy_ix(&test_f, &borrower_astl_account_f).await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::GroupReserveCoverageRequired
+    );
+
+    // Drain the reserve
+    let (_, reserve_vault_authority) = get_reserve_vault_addresses(&test_f);
+    let admin_sol_account = test_f.sol_mint.create_empty_token_account().await;
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolWithdrawGroupReserve {
+            astrolend_group: test_f.astrolend_group.key,
+            admin: test_f.payer(),
+            reserve_vault,
+            reserve_mint: sol_bank_f.mint.key,
+            reserve_vault_authority,
+            dst_token_account: admin_sol_account.key,
+            token_program: sol_bank_f.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolWithdrawGroupReserve {
+            amount: native!(100, "SOL"),
+        }
+        .data(),
+    };
+    process_ix(&test_f, ix).await?;
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            admin: Some(Pubkey::new_unique()),
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        })
+        .await?;
+
+    // Anyone can socialize the loss once the reserve is exhausted
+    let ix = make_handle_bankruptcy_ix(&test_f, &borrower_astl_account_f).await;
+    process_ix(&test_f, ix).await?;
+
+    let borrower_astl_account = borrower_astl_account_f.load().await;
+    assert!(borrower_astl_account.get_flag(DISABLED_FLAG));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_reserve_haircut_capped() -> anyhow::Result<()> {
+    let (test_f, _, _) = setup_bankrupt_borrower().await?;
+
+    let res = test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: Some(I80F48!(0.2).into()),
+            swap_programs: None,
+        })
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidConfig);
+
+    Ok(())
+}
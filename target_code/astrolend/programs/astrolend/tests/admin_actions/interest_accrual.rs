@@ -12,7 +12,12 @@ use solana_program_test::*;
 #[tokio::test]
 async fn astrolend_group_accrue_interest_rates_success_1() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
         banks: vec![
             TestBankSetting {
                 mint: BankMint::Usdc,
@@ -118,7 +123,12 @@ async fn astrolend_group_accrue_interest_rates_success_2() -> anyhow::Result<()>
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
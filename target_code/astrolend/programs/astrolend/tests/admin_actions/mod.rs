@@ -2,6 +2,7 @@ mod account_transfer;
 mod bankruptcy;
 mod bankruptcy_auth;
 mod create_astrolend_group;
+mod group_reserve;
 mod interest_accrual;
 mod setup_bank;
 mod withdraw_fees;
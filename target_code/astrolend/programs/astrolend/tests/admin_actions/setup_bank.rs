@@ -2,7 +2,7 @@ use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
 use fixtures::{assert_custom_error, prelude::*};
 use astrolend::{
-    constants::PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG,
+    constants::{FLASHLOAN_BORROW_DISABLED_FLAG, PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG},
     prelude::AstrolendError,
     state::astrolend_group::{Bank, BankConfig, BankConfigOpt, BankVaultType},
 };
@@ -100,7 +100,7 @@ async fn add_bank_success() -> anyhow::Result<()> {
             assert_eq!(emissions_mint, Pubkey::new_from_array([0; 32]));
             assert_eq!(emissions_remaining, I80F48!(0.0).into());
 
-            assert_eq!(_padding_0, <[[u64; 2]; 28] as Default>::default());
+            assert_eq!(_padding_0, <[[u64; 2]; 7] as Default>::default());
             assert_eq!(_padding_1, <[[u64; 2]; 32] as Default>::default());
 
             // this is the only loosely checked field
@@ -203,7 +203,7 @@ async fn add_bank_with_seed_success() -> anyhow::Result<()> {
             assert_eq!(emissions_mint, Pubkey::new_from_array([0; 32]));
             assert_eq!(emissions_remaining, I80F48!(0.0).into());
 
-            assert_eq!(_padding_0, <[[u64; 2]; 28] as Default>::default());
+            assert_eq!(_padding_0, <[[u64; 2]; 7] as Default>::default());
             assert_eq!(_padding_1, <[[u64; 2]; 32] as Default>::default());
 
             // this is the only loosely checked field
@@ -283,6 +283,10 @@ async fn configure_bank_success(bank_mint: BankMint) -> anyhow::Result<()> {
         total_asset_value_init_limit,
         oracle_max_age,
         permissionless_bad_debt_settlement,
+        deficit_interest_share,
+        flashloan_fee_bps,
+        flashloan_borrow_disabled,
+        soft_liquidation_threshold,
     } = &config_bank_opt;
     // Compare bank field to opt field if Some, otherwise compare to old bank field
     macro_rules! check_bank_field {
@@ -324,6 +328,13 @@ async fn configure_bank_success(bank_mint: BankMint) -> anyhow::Result<()> {
         check_bank_field!(risk_tier);
         check_bank_field!(total_asset_value_init_limit);
         check_bank_field!(oracle_max_age);
+        check_bank_field!(flashloan_fee_bps);
+        check_bank_field!(soft_liquidation_threshold);
+
+        assert_eq!(
+            bank.deficit_interest_share,
+            deficit_interest_share.unwrap_or(old_bank.deficit_interest_share)
+        );
 
 
 
@@ -334,6 +345,11 @@ async fn configure_bank_success(bank_mint: BankMint) -> anyhow::Result<()> {
             .unwrap_or( bank.get_flag(PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG) == old_bank.get_flag(PERMISSIONLESS_BAD_DEBT_SETTLEMENT_FLAG))
         );
 
+        assert!(flashloan_borrow_disabled
+            .map(|set| set == bank.get_flag(FLASHLOAN_BORROW_DISABLED_FLAG))
+            .unwrap_or( bank.get_flag(FLASHLOAN_BORROW_DISABLED_FLAG) == old_bank.get_flag(FLASHLOAN_BORROW_DISABLED_FLAG))
+        );
+
         assert_eq!(
             bank.config.oracle_keys,
             // If Some(...) check keys set properly
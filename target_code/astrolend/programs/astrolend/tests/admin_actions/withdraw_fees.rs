@@ -102,6 +102,9 @@ async fn astrolend_group_withdraw_fees_and_insurance_fund_as_non_admin_failure(
         .astrolend_group
         .try_update(GroupConfig {
             admin: Some(Pubkey::new_unique()),
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
         })
         .await?;
 
@@ -14,7 +14,12 @@ async fn astrolend_group_bank_paused_should_error() -> anyhow::Result<()> {
             mint: BankMint::Usdc,
             config: None,
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -53,7 +58,12 @@ async fn astrolend_group_bank_reduce_only_withdraw_success() -> anyhow::Result<(
             mint: BankMint::Usdc,
             config: None,
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -100,7 +110,12 @@ async fn astrolend_group_bank_reduce_only_deposit_success() -> anyhow::Result<()
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -159,7 +174,12 @@ async fn astrolend_group_bank_reduce_only_borrow_failure() -> anyhow::Result<()>
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -206,7 +226,12 @@ async fn astrolend_group_bank_reduce_only_deposit_failure() -> anyhow::Result<()
             mint: BankMint::Usdc,
             config: None,
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
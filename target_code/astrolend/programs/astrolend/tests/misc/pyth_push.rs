@@ -27,7 +27,12 @@ async fn pyth_push_fullv_borrow() -> anyhow::Result<()> {
                 config: Some(*DEFAULT_SOL_TEST_PYTH_PUSH_FULLV_BANK_CONFIG),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -95,7 +100,12 @@ async fn pyth_push_partv_borrow() -> anyhow::Result<()> {
                 config: Some(*DEFAULT_SOL_TEST_PYTH_PUSH_PARTV_BANK_CONFIG),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -158,7 +168,12 @@ async fn pyth_push_fullv_liquidate() -> anyhow::Result<()> {
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
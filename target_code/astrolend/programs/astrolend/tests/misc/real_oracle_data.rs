@@ -22,7 +22,12 @@ async fn real_oracle_astrolend_account_borrow_success() -> anyhow::Result<()> {
                 config: Some(*DEFAULT_SOL_TEST_REAL_BANK_CONFIG),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -96,7 +101,12 @@ async fn real_oracle_pyth_push_astrolend_account_borrow_success() -> anyhow::Res
                 config: Some(*DEFAULT_PYTH_PUSH_SOL_TEST_REAL_BANK_CONFIG),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
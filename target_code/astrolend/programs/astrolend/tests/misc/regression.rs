@@ -5,8 +5,10 @@ use anyhow::bail;
 use base64::{prelude::BASE64_STANDARD, Engine};
 use fixed::types::I80F48;
 use astrolend::state::{
-    astrolend_account::AstrolendAccount,
-    astrolend_group::{Bank, BankOperationalState, RiskTier},
+    astrolend_account::{AstrolendAccount, LEGACY_ASTROLEND_ACCOUNT_SIZE},
+    astrolend_group::{
+        Bank, BankOperationalState, EmissionsIndex, EmissionsSchedule, EmissionsStream, RiskTier,
+    },
     price::OracleSetup,
 };
 use solana_account_decoder::UiAccountData;
@@ -15,6 +17,17 @@ use solana_program::pubkey;
 use solana_program_test::tokio;
 use switchboard_solana::Pubkey;
 
+/// The samples were recorded with 16 balances, migrate them to the current layout first.
+fn deserialize_legacy_account(data: &str) -> anyhow::Result<AstrolendAccount> {
+    let mut data = BASE64_STANDARD.decode(data)?;
+    assert_eq!(data.len(), 8 + LEGACY_ASTROLEND_ACCOUNT_SIZE);
+
+    data.resize(8 + std::mem::size_of::<AstrolendAccount>(), 0);
+    AstrolendAccount::migrate_legacy_layout(&mut data[8..])?;
+
+    Ok(AstrolendAccount::try_deserialize(&mut data.as_slice())?)
+}
+
 #[tokio::test]
 async fn account_field_values_reg() -> anyhow::Result<()> {
     let account_fixtures_path = "tests/fixtures/astrolend_account";
@@ -33,7 +46,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
     let UiAccountData::Binary(data, _) = account.keyed_account.account.data else {
         bail!("Expecting Binary format for fixtures")
     };
-    let account = AstrolendAccount::try_deserialize(&mut BASE64_STANDARD.decode(data)?.as_slice())?;
+    let account = deserialize_legacy_account(&data)?;
 
     assert_eq!(
         account.group,
@@ -44,7 +57,15 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         pubkey!("Dq7wypbedtaqQK9QqEFvfrxc4ppfRGXCeTVd7ee7n2jw")
     );
     assert_eq!(account.account_flags, 0);
-    assert_eq!(account._padding, [0; 63]);
+    assert_eq!(account.emissions_outstanding, Default::default());
+    assert_eq!(account.soft_liquidation_window_start, 0);
+    assert_eq!(
+        I80F48::from(account.soft_liquidation_window_value),
+        I80F48::ZERO
+    );
+    assert_eq!(account.pending_authority, Pubkey::default());
+    assert_eq!(account.recovery_authority, Pubkey::default());
+    assert_eq!(account._padding, [0; 20]);
 
     let balance_1 = account.lending_account.balances[0];
     assert!(balance_1.active);
@@ -69,7 +90,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         I80F48::from(balance_1.last_update),
         I80F48::from_str("1711158766").unwrap()
     );
-    assert_eq!(balance_1._padding, [0; 1]);
+    assert_eq!(balance_1.flashloan_borrowed, 0);
 
     let balance_2 = account.lending_account.balances[1];
     assert!(balance_2.active);
@@ -94,7 +115,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         I80F48::from(balance_2.last_update),
         I80F48::from_str("1711158793").unwrap()
     );
-    assert_eq!(balance_2._padding, [0; 1]);
+    assert_eq!(balance_2.flashloan_borrowed, 0);
 
     // Sample 2
 
@@ -108,7 +129,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
     let UiAccountData::Binary(data, _) = account.keyed_account.account.data else {
         bail!("Expecting Binary format for fixtures")
     };
-    let account = AstrolendAccount::try_deserialize(&mut BASE64_STANDARD.decode(data)?.as_slice())?;
+    let account = deserialize_legacy_account(&data)?;
 
     assert_eq!(
         account.group,
@@ -119,7 +140,15 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         pubkey!("3T1kGHp7CrdeW9Qj1t8NMc2Ks233RyvzVhoaUPWoBEFK")
     );
     assert_eq!(account.account_flags, 0);
-    assert_eq!(account._padding, [0; 63]);
+    assert_eq!(account.emissions_outstanding, Default::default());
+    assert_eq!(account.soft_liquidation_window_start, 0);
+    assert_eq!(
+        I80F48::from(account.soft_liquidation_window_value),
+        I80F48::ZERO
+    );
+    assert_eq!(account.pending_authority, Pubkey::default());
+    assert_eq!(account.recovery_authority, Pubkey::default());
+    assert_eq!(account._padding, [0; 20]);
 
     let balance_1 = account.lending_account.balances[0];
     assert!(balance_1.active);
@@ -144,7 +173,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         I80F48::from(balance_1.last_update),
         I80F48::from_str("1705760628").unwrap()
     );
-    assert_eq!(balance_1._padding, [0; 1]);
+    assert_eq!(balance_1.flashloan_borrowed, 0);
 
     let balance_2 = account.lending_account.balances[1];
     assert!(!balance_2.active);
@@ -169,7 +198,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         I80F48::from(balance_2.last_update),
         I80F48::from_str("0").unwrap()
     );
-    assert_eq!(balance_2._padding, [0; 1]);
+    assert_eq!(balance_2.flashloan_borrowed, 0);
 
     // Sample 3
 
@@ -183,7 +212,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
     let UiAccountData::Binary(data, _) = account.keyed_account.account.data else {
         bail!("Expecting Binary format for fixtures")
     };
-    let account = AstrolendAccount::try_deserialize(&mut BASE64_STANDARD.decode(data)?.as_slice())?;
+    let account = deserialize_legacy_account(&data)?;
 
     assert_eq!(
         account.group,
@@ -194,7 +223,15 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         pubkey!("7hmfVTuXc7HeX3YQjpiCXGVQuTeXonzjp795jorZukVR")
     );
     assert_eq!(account.account_flags, 0);
-    assert_eq!(account._padding, [0; 63]);
+    assert_eq!(account.emissions_outstanding, Default::default());
+    assert_eq!(account.soft_liquidation_window_start, 0);
+    assert_eq!(
+        I80F48::from(account.soft_liquidation_window_value),
+        I80F48::ZERO
+    );
+    assert_eq!(account.pending_authority, Pubkey::default());
+    assert_eq!(account.recovery_authority, Pubkey::default());
+    assert_eq!(account._padding, [0; 20]);
 
     let balance_1 = account.lending_account.balances[0];
     assert!(!balance_1.active);
@@ -219,7 +256,7 @@ async fn account_field_values_reg() -> anyhow::Result<()> {
         I80F48::from(balance_1.last_update),
         I80F48::from_str("0").unwrap()
     );
-    assert_eq!(balance_1._padding, [0; 1]);
+    assert_eq!(balance_1.flashloan_borrowed, 0);
 
     Ok(())
 }
@@ -640,7 +677,12 @@ async fn bank_field_values_reg() -> anyhow::Result<()> {
     assert_eq!(bank.config._pad1, [0; 7]);
     assert_eq!(bank.config.total_asset_value_init_limit, 0);
     assert_eq!(bank.config.oracle_max_age, 300);
-    assert_eq!(bank.config._padding, [0; 38]);
+    assert_eq!(bank.config.flashloan_fee_bps, 0);
+    assert_eq!(
+        I80F48::from(bank.config.soft_liquidation_threshold),
+        I80F48::ZERO
+    );
+    assert_eq!(bank.config._padding, [0; 20]);
 
     assert_eq!(bank.flags, 2);
 
@@ -657,7 +699,18 @@ async fn bank_field_values_reg() -> anyhow::Result<()> {
         pubkey!("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo")
     );
 
-    assert_eq!(bank._padding_0, [[0, 0]; 28]);
+    assert_eq!(bank.insurance_share_mint, Pubkey::default());
+    assert_eq!(bank.insurance_unstake_cooldown, 0);
+    assert_eq!(bank.insurance_unstake_window, 0);
+
+    assert_eq!(I80F48::from(bank.deficit), I80F48::ZERO);
+    assert_eq!(I80F48::from(bank.deficit_interest_share), I80F48::ZERO);
+
+    assert_eq!(bank.emissions_streams, [EmissionsStream::default(); 1]);
+    assert_eq!(bank.emissions_index, EmissionsIndex::default());
+    assert_eq!(bank.emissions_schedule, EmissionsSchedule::default());
+    assert_eq!(bank.emissions_setup_time, 0);
+    assert_eq!(bank._padding_0, [[0, 0]; 7]);
     assert_eq!(bank._padding_1, [[0, 0]; 32]);
 
     Ok(())
@@ -239,7 +239,12 @@ async fn re_liquidaiton_fail() -> anyhow::Result<()> {
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -323,7 +328,12 @@ async fn re_liquidaiton_fail() -> anyhow::Result<()> {
 #[tokio::test]
 async fn re_bankruptcy_fail() -> anyhow::Result<()> {
     let mut test_f = TestFixture::new(Some(TestSettings {
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
         banks: vec![
             TestBankSetting {
                 mint: BankMint::Usdc,
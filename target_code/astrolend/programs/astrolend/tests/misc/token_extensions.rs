@@ -54,7 +54,12 @@ async fn astrolend_account_liquidation_success_with_extension(
                     }),
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig {
+                admin: None,
+                bank_config_delay: None,
+                reserve_haircut: None,
+                swap_programs: None,
+            }),
         }),
         &extensions,
     )
@@ -2,7 +2,8 @@ use anchor_spl::token_2022::spl_token_2022::extension::{
     transfer_fee::TransferFeeConfig, BaseStateWithExtensions,
 };
 use fixed::types::I80F48;
-use fixtures::{assert_custom_error, native, prelude::*, ui_to_native};
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*, ui_to_native};
 use astrolend::{
     assert_eq_with_tolerance,
     prelude::*,
@@ -413,3 +414,101 @@ async fn isolated_borrows() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn astrolend_account_borrow_against_own_deposit_discounted_by_deficit() -> anyhow::Result<()>
+{
+    // -------------------------------------------------------------------------
+    // Setup
+    // -------------------------------------------------------------------------
+
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            deficit_interest_share: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Lender
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    // Borrower, made bankrupt by nullifying its collateral
+
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let sufficient_collateral_amount = test_f
+        .get_sufficient_collateral_for_outflow(10_000., &BankMint::Sol, &BankMint::Usdc)
+        .await;
+    let borrower_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(sufficient_collateral_amount)
+        .await;
+    borrower_astl_account_f
+        .try_bank_deposit(
+            borrower_token_account_sol.key,
+            sol_bank_f,
+            sufficient_collateral_amount,
+        )
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_astl_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_astl_account = borrower_astl_account_f.load().await;
+    borrower_astl_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0_i128.to_le_bytes();
+    borrower_astl_account_f
+        .set_account(&borrower_astl_account)
+        .await?;
+
+    // The 10k of bad debt is recorded as a deficit, deposits are only 90% backed
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank_f, &borrower_astl_account_f)
+        .await?;
+
+    // -------------------------------------------------------------------------
+    // Test
+    // -------------------------------------------------------------------------
+
+    // Borrowing 45k against the own deposits burns 50k of them, the forfeited 5k repays the
+    // deficit and no liability is opened
+    let destination_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    lender_astl_account_f
+        .try_bank_borrow(destination_token_account_usdc.key, usdc_bank_f, 45_000)
+        .await?;
+
+    assert_eq!(
+        destination_token_account_usdc.balance().await,
+        native!(45_000, "USDC")
+    );
+    let usdc_bank = usdc_bank_f.load().await;
+    let lender_balance = lender_astl_account_f.load().await.lending_account.balances[0];
+    assert_eq_noise!(
+        usdc_bank.get_asset_amount(lender_balance.asset_shares.into())?,
+        I80F48::from(native!(50_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq!(I80F48::from(lender_balance.liability_shares), I80F48::ZERO);
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.deficit),
+        I80F48::from(native!(5_000, "USDC")),
+        I80F48::ONE
+    );
+
+    Ok(())
+}
@@ -1,10 +1,14 @@
-use fixtures::{
-    assert_custom_error,
-    spl::TokenAccountFixture,
-    test::{BankMint, TestFixture, TestSettings},
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{errors::AstrolendError, state::astrolend_group::BankVaultType};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
 };
-use astrolend::errors::AstrolendError;
 use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use super::swap_collateral::process_ix;
 
 #[tokio::test]
 async fn close_astrolend_account() -> anyhow::Result<()> {
@@ -62,3 +66,120 @@ async fn close_astrolend_account() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn make_sweep_and_close_ix(
+    test_f: &TestFixture,
+    astrolend_account: Pubkey,
+    rent_recipient: Pubkey,
+    sweeps: &[(&BankFixture, Pubkey)],
+) -> Instruction {
+    let mut accounts = astrolend::accounts::AstrolendAccountSweepAndClose {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account,
+        authority: test_f.payer(),
+        rent_recipient,
+    }
+    .to_account_metas(Some(true));
+
+    for (bank_f, token_account) in sweeps {
+        accounts.extend([
+            AccountMeta::new(bank_f.key, false),
+            AccountMeta::new_readonly(bank_f.mint.key, false),
+            AccountMeta::new_readonly(
+                bank_f.get_vault_authority(BankVaultType::Liquidity).0,
+                false,
+            ),
+            AccountMeta::new(bank_f.get_vault(BankVaultType::Liquidity).0, false),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new_readonly(bank_f.get_token_program(), false),
+        ]);
+    }
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::AstrolendAccountSweepAndClose {
+            hook_accounts_lens: vec![0; sweeps.len()],
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn sweep_and_close_astrolend_account() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+    let t22_bank_f = test_f.get_bank(&BankMint::T22WithFee);
+
+    let sol_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let depositor = test_f.create_astrolend_account().await;
+    depositor
+        .try_bank_deposit(sol_account.key, sol_bank_f, 100)
+        .await?;
+
+    let astrolend_account_f = test_f.create_astrolend_account().await;
+    let usdc_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    astrolend_account_f
+        .try_bank_deposit(usdc_account.key, usdc_bank_f, 1_000)
+        .await?;
+    let sol_account_2 = test_f.sol_mint.create_token_account_and_mint_to(1).await;
+    astrolend_account_f
+        .try_bank_borrow(sol_account_2.key, sol_bank_f, 10)
+        .await?;
+    // Token22 balance, swept with its own token program in the same instruction
+    let t22_account = t22_bank_f.mint.create_token_account_and_mint_to(200).await;
+    astrolend_account_f
+        .try_bank_deposit(t22_account.key, t22_bank_f, 100)
+        .await?;
+    let t22_balance_before = t22_account.balance().await;
+
+    let rent_recipient = Keypair::new().pubkey();
+
+    // Every active balance has to be swept
+    let ix = make_sweep_and_close_ix(
+        &test_f,
+        astrolend_account_f.key,
+        rent_recipient,
+        &[(usdc_bank_f, usdc_account.key)],
+    );
+    let res = process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalAction);
+
+    let ix = make_sweep_and_close_ix(
+        &test_f,
+        astrolend_account_f.key,
+        rent_recipient,
+        &[
+            (sol_bank_f, sol_account_2.key),
+            (usdc_bank_f, usdc_account.key),
+            (t22_bank_f, t22_account.key),
+        ],
+    );
+    process_ix(&test_f, ix).await?;
+
+    assert_eq!(usdc_account.balance().await, native!(1_000, "USDC"));
+    assert_eq!(sol_account_2.balance().await, native!(1, "SOL"));
+    assert!(t22_account.balance().await > t22_balance_before);
+
+    let mut ctx = test_f.context.borrow_mut();
+    assert!(ctx
+        .banks_client
+        .get_account(astrolend_account_f.key)
+        .await?
+        .is_none());
+    assert!(
+        ctx.banks_client
+            .get_account(rent_recipient)
+            .await?
+            .unwrap()
+            .lamports
+            > 0
+    );
+
+    Ok(())
+}
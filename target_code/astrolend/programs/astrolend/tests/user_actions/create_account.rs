@@ -1,10 +1,21 @@
 use anchor_lang::{InstructionData, ToAccountMetas};
-use fixtures::test::TestFixture;
-use astrolend::state::astrolend_account::AstrolendAccount;
+use fixed::types::I80F48;
+use fixtures::{native, prelude::*, test::TestFixture};
+use astrolend::{
+    constants::ASTROLEND_ACCOUNT_SEED,
+    state::{
+        astrolend_account::{
+            AstrolendAccount, FLASHLOAN_ENABLED_FLAG, LEGACY_ASTROLEND_ACCOUNT_SIZE,
+            LEGACY_MAX_LENDING_ACCOUNT_BALANCES, MAX_LENDING_ACCOUNT_BALANCES,
+        },
+        astrolend_group::BankVaultType,
+    },
+};
 use solana_program_test::tokio;
 use solana_sdk::{
-    instruction::Instruction, signature::Keypair, signer::Signer, system_program,
-    transaction::Transaction,
+    account::Account, compute_budget::ComputeBudgetInstruction, instruction::Instruction,
+    pubkey::Pubkey, rent::Rent, signature::Keypair, signer::Signer, system_instruction,
+    system_program, transaction::Transaction,
 };
 
 #[tokio::test]
@@ -55,3 +66,321 @@ async fn astrolend_account_create_success() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn astrolend_account_create_pda_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let make_ix = |account_index: u16| {
+        let (astrolend_account, _) = Pubkey::find_program_address(
+            &[
+                ASTROLEND_ACCOUNT_SEED.as_bytes(),
+                test_f.astrolend_group.key.as_ref(),
+                test_f.payer().as_ref(),
+                &account_index.to_le_bytes(),
+            ],
+            &astrolend::id(),
+        );
+        let ix = Instruction {
+            program_id: astrolend::id(),
+            accounts: astrolend::accounts::AstrolendAccountInitializePda {
+                astrolend_group: test_f.astrolend_group.key,
+                astrolend_account,
+                authority: test_f.payer(),
+                fee_payer: test_f.payer(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: astrolend::instruction::AstrolendAccountInitializePda { account_index }.data(),
+        };
+
+        (astrolend_account, ix)
+    };
+
+    let (first_account, first_ix) = make_ix(0);
+    let (second_account, second_ix) = make_ix(1);
+    assert_ne!(first_account, second_account);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[first_ix.clone(), second_ix],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await?;
+
+    for key in [first_account, second_account] {
+        let astrolend_account: AstrolendAccount = test_f.load_and_deserialize(&key).await;
+
+        assert_eq!(astrolend_account.group, test_f.astrolend_group.key);
+        assert_eq!(astrolend_account.authority, test_f.payer());
+    }
+
+    // The same index can't be initialized twice, the compute price only makes the tx unique
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            ComputeBudgetInstruction::set_compute_unit_price(1),
+            first_ix,
+        ],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    let res = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await;
+
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+/// Rewrite `astrolend_account` in the 16 balances layout, returning it in the current layout.
+async fn set_legacy_layout(
+    test_f: &TestFixture,
+    astrolend_account: Pubkey,
+) -> anyhow::Result<Account> {
+    let account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(astrolend_account)
+        .await?
+        .unwrap();
+
+    // Discriminator, group and authority, the first balances, the lending account padding and
+    // flags, the first emissions, the padding
+    let balances_start = 8 + 64;
+    let legacy_balances_end = balances_start + LEGACY_MAX_LENDING_ACCOUNT_BALANCES * 104;
+    let balances_end = balances_start + MAX_LENDING_ACCOUNT_BALANCES * 104;
+    let emissions_start = balances_end + 72;
+    let mut legacy_data = [
+        &account.data[..legacy_balances_end],
+        &account.data[balances_end..emissions_start],
+        &account.data[emissions_start..emissions_start + LEGACY_MAX_LENDING_ACCOUNT_BALANCES * 16],
+    ]
+    .concat();
+    legacy_data.resize(8 + LEGACY_ASTROLEND_ACCOUNT_SIZE, 0);
+
+    test_f.context.borrow_mut().set_account(
+        &astrolend_account,
+        &Account {
+            lamports: Rent::default().minimum_balance(legacy_data.len()),
+            data: legacy_data,
+            owner: astrolend::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+
+    Ok(account)
+}
+
+#[tokio::test]
+async fn astrolend_account_migrate_legacy_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    astl_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    let account = set_legacy_layout(&test_f, astl_account_f.key).await?;
+
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountMigrate {
+            astrolend_account: astl_account_f.key,
+            fee_payer: test_f.payer(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountMigrate {}.data(),
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[ix.clone()],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await?;
+
+    let migrated_account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(astl_account_f.key)
+        .await?
+        .unwrap();
+    assert_eq!(migrated_account.data, account.data);
+
+    // The account keeps working, and can't be migrated twice
+    astl_account_f
+        .try_bank_withdraw(token_account_f.key, usdc_bank, 50, None)
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[ComputeBudgetInstruction::set_compute_unit_price(1), ix],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    let res = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await;
+
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn astrolend_account_migrate_legacy_on_write() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(150).await;
+    astl_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 100)
+        .await?;
+
+    let account = set_legacy_layout(&test_f, astl_account_f.key).await?;
+
+    let deposit_ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingAccountDeposit {
+            astrolend_group: test_f.astrolend_group.key,
+            astrolend_account: astl_account_f.key,
+            signer: test_f.payer(),
+            bank: usdc_bank.key,
+            signer_token_account: token_account_f.key,
+            bank_liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+            token_program: usdc_bank.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingAccountDeposit {
+            amount: native!(50, "USDC"),
+        }
+        .data(),
+    };
+
+    // The deposit migrates the account, which fails the rent check without the additional rent
+    let tx = Transaction::new_signed_with_payer(
+        &[deposit_ix.clone()],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    let res = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await;
+
+    assert!(res.is_err());
+
+    let missing_rent = Rent::default().minimum_balance(account.data.len())
+        - Rent::default().minimum_balance(8 + LEGACY_ASTROLEND_ACCOUNT_SIZE);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::transfer(&test_f.payer(), &astl_account_f.key, missing_rent),
+            deposit_ix,
+        ],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await?;
+
+    let migrated_account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(astl_account_f.key)
+        .await?
+        .unwrap();
+    assert_eq!(migrated_account.data.len(), account.data.len());
+
+    let astrolend_account = astl_account_f.load().await;
+    let usdc_balance = astrolend_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    assert_eq!(
+        usdc_bank
+            .load()
+            .await
+            .get_asset_amount(usdc_balance.asset_shares.into())?,
+        I80F48::from(native!(150, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn astrolend_account_migrate_legacy_on_set_flag() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let account = set_legacy_layout(&test_f, astl_account_f.key).await?;
+
+    // The admin instruction migrates the account too, once it holds the rent of the new size
+    let missing_rent = Rent::default().minimum_balance(account.data.len())
+        - Rent::default().minimum_balance(8 + LEGACY_ASTROLEND_ACCOUNT_SIZE);
+    let tx = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &test_f.payer(),
+            &astl_account_f.key,
+            missing_rent,
+        )],
+        Some(&test_f.payer()),
+        &[&test_f.payer_keypair()],
+        test_f.get_latest_blockhash().await,
+    );
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await?;
+
+    astl_account_f.try_set_flag(FLASHLOAN_ENABLED_FLAG).await?;
+
+    let migrated_account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(astl_account_f.key)
+        .await?
+        .unwrap();
+    assert_eq!(migrated_account.data.len(), account.data.len());
+    assert!(astl_account_f.load().await.get_flag(FLASHLOAN_ENABLED_FLAG));
+
+    Ok(())
+}
@@ -0,0 +1,442 @@
+use anchor_lang::{prelude::Clock, InstructionData, ToAccountMetas};
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    constants::ACCOUNT_DELEGATE_SEED,
+    prelude::*,
+    state::{
+        account_delegate::{
+            AstrolendAccountDelegate, DELEGATE_DEPOSIT_REPAY_PERMISSION,
+            DELEGATE_WITHDRAW_PERMISSION,
+        },
+        astrolend_group::BankVaultType,
+    },
+};
+use pretty_assertions::assert_eq;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+// Account delegate tests
+// 1. Delegate deposits and withdraws to the authority, but can't withdraw to itself or after expiry
+// 2. Delegate can't withdraw without the permission, and can no longer act once revoked
+// 3. Delegate can't act once the account has another authority, until it grants it again
+
+fn get_account_delegate_address(astrolend_account: Pubkey, delegate: Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            ACCOUNT_DELEGATE_SEED.as_bytes(),
+            astrolend_account.as_ref(),
+            delegate.as_ref(),
+        ],
+        &astrolend::id(),
+    )
+    .0
+}
+
+async fn process_ix(
+    test_f: &TestFixture,
+    ix: Instruction,
+    signer: Option<&Keypair>,
+) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let mut signers = vec![&ctx.payer];
+    signers.extend(signer);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &signers,
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+fn make_grant_delegate_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    delegate: Pubkey,
+    permissions: u64,
+    expiry: i64,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountGrantDelegate {
+            astrolend_account: astrolend_account_f.key,
+            authority: test_f.payer(),
+            delegate,
+            account_delegate: get_account_delegate_address(astrolend_account_f.key, delegate),
+            fee_payer: test_f.payer(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountGrantDelegate {
+            permissions,
+            expiry,
+        }
+        .data(),
+    }
+}
+
+async fn make_delegate_deposit_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    delegate: Pubkey,
+    token_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let mut accounts = astrolend::accounts::LendingAccountDeposit {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astrolend_account_f.key,
+        signer: delegate,
+        bank: usdc_bank.key,
+        signer_token_account: token_account,
+        bank_liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+        token_program: usdc_bank.get_token_program(),
+    }
+    .to_account_metas(Some(true));
+    accounts.push(AccountMeta::new_readonly(
+        get_account_delegate_address(astrolend_account_f.key, delegate),
+        false,
+    ));
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountDeposit { amount }.data(),
+    }
+}
+
+async fn make_delegate_withdraw_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    delegate: Pubkey,
+    destination_token_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let mut accounts = astrolend::accounts::LendingAccountWithdraw {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astrolend_account_f.key,
+        signer: delegate,
+        bank: usdc_bank.key,
+        destination_token_account,
+        bank_liquidity_vault_authority: usdc_bank.get_vault_authority(BankVaultType::Liquidity).0,
+        bank_liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+        token_program: usdc_bank.get_token_program(),
+    }
+    .to_account_metas(Some(true));
+    accounts.push(AccountMeta::new_readonly(
+        get_account_delegate_address(astrolend_account_f.key, delegate),
+        false,
+    ));
+    accounts.extend(
+        astrolend_account_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountWithdraw {
+            amount,
+            withdraw_all: None,
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn delegate_deposit_and_withdraw() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let authority_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    astl_account_f
+        .try_bank_deposit(authority_token_account_f.key, usdc_bank, 1_000)
+        .await?;
+
+    let delegate = Keypair::new();
+    let delegate_token_account_f = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &test_f.usdc_mint,
+        &delegate.pubkey(),
+    )
+    .await;
+    test_f
+        .usdc_mint
+        .mint_to(&delegate_token_account_f.key, 100)
+        .await;
+
+    let now = {
+        let mut ctx = test_f.context.borrow_mut();
+        let clock: Clock = ctx.banks_client.get_sysvar().await?;
+        clock.unix_timestamp
+    };
+
+    let ix = make_grant_delegate_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        DELEGATE_DEPOSIT_REPAY_PERMISSION | DELEGATE_WITHDRAW_PERMISSION,
+        now + 3_600,
+    );
+    process_ix(&test_f, ix, None).await?;
+
+    let account_delegate: AstrolendAccountDelegate = test_f
+        .load_and_deserialize(&get_account_delegate_address(
+            astl_account_f.key,
+            delegate.pubkey(),
+        ))
+        .await;
+    assert_eq!(account_delegate.delegate, delegate.pubkey());
+    assert_eq!(account_delegate.expiry, now + 3_600);
+
+    // Delegate deposits from its own wallet
+    let ix = make_delegate_deposit_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        delegate_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    process_ix(&test_f, ix, Some(&delegate)).await?;
+    assert_eq!(delegate_token_account_f.balance().await, 0);
+
+    // Delegate withdraws to the authority
+    let ix = make_delegate_withdraw_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        authority_token_account_f.key,
+        native!(500, "USDC"),
+    )
+    .await;
+    process_ix(&test_f, ix, Some(&delegate)).await?;
+    assert_eq!(
+        authority_token_account_f.balance().await,
+        native!(500, "USDC")
+    );
+
+    // Delegate can't withdraw to itself
+    let ix = make_delegate_withdraw_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        delegate_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix, Some(&delegate)).await;
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    // Delegate can't act after expiry
+    test_f.advance_time(7_200).await;
+
+    let ix = make_delegate_withdraw_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        authority_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix, Some(&delegate)).await;
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delegate_failure_missing_permission_and_revoked() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let authority_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    astl_account_f
+        .try_bank_deposit(authority_token_account_f.key, usdc_bank, 1_000)
+        .await?;
+
+    let delegate = Keypair::new();
+    let delegate_token_account_f = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &test_f.usdc_mint,
+        &delegate.pubkey(),
+    )
+    .await;
+    test_f
+        .usdc_mint
+        .mint_to(&delegate_token_account_f.key, 100)
+        .await;
+
+    let ix = make_grant_delegate_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        DELEGATE_DEPOSIT_REPAY_PERMISSION,
+        0,
+    );
+    process_ix(&test_f, ix, None).await?;
+
+    // Deposit-only delegate can't withdraw
+    let ix = make_delegate_withdraw_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        authority_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix, Some(&delegate)).await;
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    // Revoke, the delegate record is closed and deposits are refused
+    let account_delegate = get_account_delegate_address(astl_account_f.key, delegate.pubkey());
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountRevokeDelegate {
+            astrolend_account: astl_account_f.key,
+            authority: test_f.payer(),
+            account_delegate,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountRevokeDelegate {}.data(),
+    };
+    process_ix(&test_f, ix, None).await?;
+
+    assert!(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(account_delegate)
+        .await?
+        .is_none());
+
+    let ix = make_delegate_deposit_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        delegate_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix, Some(&delegate)).await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delegate_failure_authority_transferred() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+
+    let delegate = Keypair::new();
+    let delegate_token_account_f = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &test_f.usdc_mint,
+        &delegate.pubkey(),
+    )
+    .await;
+    test_f
+        .usdc_mint
+        .mint_to(&delegate_token_account_f.key, 100)
+        .await;
+
+    let ix = make_grant_delegate_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        DELEGATE_DEPOSIT_REPAY_PERMISSION,
+        0,
+    );
+    process_ix(&test_f, ix, None).await?;
+
+    // Transfer the account to a new authority
+    let new_authority = Keypair::new();
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountOfferAuthorityTransfer {
+            astrolend_account: astl_account_f.key,
+            authority: test_f.payer(),
+            new_authority: new_authority.pubkey(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountOfferAuthorityTransfer {}.data(),
+    };
+    process_ix(&test_f, ix, None).await?;
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountAcceptAuthorityTransfer {
+            astrolend_account: astl_account_f.key,
+            new_authority: new_authority.pubkey(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountAcceptAuthorityTransfer {}.data(),
+    };
+    process_ix(&test_f, ix, Some(&new_authority)).await?;
+
+    // The delegate granted by the previous authority is refused
+    let ix = make_delegate_deposit_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        delegate_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix, Some(&delegate)).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    // The new authority grants the delegate again
+    let account_delegate = get_account_delegate_address(astl_account_f.key, delegate.pubkey());
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountUpdateDelegate {
+            astrolend_account: astl_account_f.key,
+            authority: new_authority.pubkey(),
+            account_delegate,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountUpdateDelegate {
+            permissions: DELEGATE_DEPOSIT_REPAY_PERMISSION,
+            expiry: 0,
+        }
+        .data(),
+    };
+    process_ix(&test_f, ix, Some(&new_authority)).await?;
+
+    let account_delegate: AstrolendAccountDelegate =
+        test_f.load_and_deserialize(&account_delegate).await;
+    assert_eq!(account_delegate.authority, new_authority.pubkey());
+
+    let ix = make_delegate_deposit_ix(
+        &test_f,
+        &astl_account_f,
+        delegate.pubkey(),
+        delegate_token_account_f.key,
+        native!(100, "USDC"),
+    )
+    .await;
+    process_ix(&test_f, ix, Some(&delegate)).await?;
+    assert_eq!(delegate_token_account_f.balance().await, 0);
+
+    Ok(())
+}
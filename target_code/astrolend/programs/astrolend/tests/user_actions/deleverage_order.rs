@@ -0,0 +1,336 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    constants::DELEVERAGE_ORDER_SEED, prelude::*, state::astrolend_group::BankVaultType,
+};
+use pretty_assertions::assert_eq;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_program};
+use solana_program_test::*;
+
+use super::swap_collateral::{allow_swap_program, process_ix, setup_usdc_sol_pool, MockPool};
+
+// Deleverage order tests, SOL collateral against a USDC liability
+// 1. Keeper executes a triggered order, earning the fee, and the order is closed
+// 2. Keeper can't execute an order whose trigger is not reached
+// 3. Keeper can't execute an order placed by a previous account authority
+
+fn get_deleverage_order_address(
+    astrolend_account: Pubkey,
+    collateral_bank: Pubkey,
+    liability_bank: Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            DELEVERAGE_ORDER_SEED.as_bytes(),
+            astrolend_account.as_ref(),
+            collateral_bank.as_ref(),
+            liability_bank.as_ref(),
+        ],
+        &astrolend::id(),
+    )
+    .0
+}
+
+fn make_place_deleverage_order_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    trigger_health: I80F48,
+    withdraw_amount: u64,
+    min_repay_amount: u64,
+    keeper_fee_bps: u16,
+) -> Instruction {
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingAccountPlaceDeleverageOrder {
+            astrolend_account: astrolend_account_f.key,
+            authority: test_f.payer(),
+            collateral_bank: sol_bank.key,
+            liability_bank: usdc_bank.key,
+            deleverage_order: get_deleverage_order_address(
+                astrolend_account_f.key,
+                sol_bank.key,
+                usdc_bank.key,
+            ),
+            fee_payer: test_f.payer(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingAccountPlaceDeleverageOrder {
+            trigger_health: trigger_health.into(),
+            withdraw_amount,
+            min_repay_amount,
+            keeper_fee_bps,
+        }
+        .data(),
+    }
+}
+
+async fn make_execute_deleverage_order_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    pool: &MockPool,
+    keeper_sol_token_account: Pubkey,
+    keeper_usdc_token_account: Pubkey,
+    swap_in: u64,
+    swap_out: u64,
+) -> Instruction {
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let mut accounts = astrolend::accounts::LendingAccountExecuteDeleverageOrder {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astrolend_account_f.key,
+        authority: astrolend_account_f.load().await.authority,
+        deleverage_order: get_deleverage_order_address(
+            astrolend_account_f.key,
+            sol_bank.key,
+            usdc_bank.key,
+        ),
+        keeper: test_f.payer(),
+        collateral_bank: sol_bank.key,
+        keeper_collateral_token_account: keeper_sol_token_account,
+        collateral_bank_liquidity_vault_authority: sol_bank
+            .get_vault_authority(BankVaultType::Liquidity)
+            .0,
+        collateral_bank_liquidity_vault: sol_bank.get_vault(BankVaultType::Liquidity).0,
+        collateral_token_program: sol_bank.get_token_program(),
+        liability_bank: usdc_bank.key,
+        keeper_liability_token_account: keeper_usdc_token_account,
+        liability_bank_liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+        liability_token_program: usdc_bank.get_token_program(),
+        swap_program: mocks::id(),
+    }
+    .to_account_metas(Some(true));
+
+    let swap_accounts = mocks::accounts::SwapLikeJupiter {
+        user_authority: test_f.payer(),
+        pool_auth: pool.pool_auth,
+        pool_a: pool.sol_pool,
+        pool_b: pool.usdc_pool,
+        source_a: keeper_sol_token_account,
+        destination_b: keeper_usdc_token_account,
+        token_program: anchor_spl::token::ID,
+    }
+    .to_account_metas(Some(true));
+    let swap_accounts_count = swap_accounts.len() as u8;
+    accounts.extend(swap_accounts);
+    accounts.extend(
+        astrolend_account_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountExecuteDeleverageOrder {
+            swap_accounts_count,
+            swap_data: mocks::instruction::SwapLikeJupiter {
+                amt_a: swap_in,
+                amt_b: swap_out,
+            }
+            .data(),
+        }
+        .data(),
+    }
+}
+
+/// Setup a user with 10 SOL of collateral against a 60 USDC liability.
+async fn setup_leveraged_user(test_f: &TestFixture) -> anyhow::Result<AstrolendAccountFixture> {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_astl_account_f
+        .try_bank_deposit(user_sol_token_account_f.key, sol_bank, 10)
+        .await?;
+    let user_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+    user_astl_account_f
+        .try_bank_borrow(user_usdc_token_account_f.key, usdc_bank, 60)
+        .await?;
+
+    Ok(user_astl_account_f)
+}
+
+#[tokio::test]
+async fn deleverage_order_execute_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let pool = setup_usdc_sol_pool(&test_f, 100., 0.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let user_astl_account_f = setup_leveraged_user(&test_f).await?;
+
+    // Sell 5 SOL for at least 50 USDC with a 1% keeper fee, at any health
+    let ix = make_place_deleverage_order_ix(
+        &test_f,
+        &user_astl_account_f,
+        I80F48!(1_000_000_000_000),
+        native!(5, "SOL"),
+        native!(50, "USDC"),
+        100,
+    );
+    process_ix(&test_f, ix).await?;
+
+    let keeper_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+    let keeper_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+
+    let ix = make_execute_deleverage_order_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        keeper_sol_token_account_f.key,
+        keeper_usdc_token_account_f.key,
+        native!(5, "SOL"),
+        native!(51, "USDC"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    // The keeper keeps the fee, the whole swap output went to the bank
+    let keeper_fee = native!(5, "SOL") / 100;
+    assert_eq!(keeper_sol_token_account_f.balance().await, keeper_fee);
+    assert_eq!(keeper_usdc_token_account_f.balance().await, 0);
+
+    let user_astl_account = user_astl_account_f.load().await;
+
+    let sol_balance = user_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    let sol_amount = sol_bank
+        .load()
+        .await
+        .get_asset_amount(sol_balance.asset_shares.into())?;
+    assert_eq!(sol_amount, I80F48::from(native!(5, "SOL") - keeper_fee));
+
+    let usdc_balance = user_astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    let usdc_amount = usdc_bank
+        .load()
+        .await
+        .get_liability_amount(usdc_balance.liability_shares.into())?;
+    assert_eq!(usdc_amount, I80F48::from(native!(9, "USDC")));
+
+    // The order is closed once executed
+    assert!(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(get_deleverage_order_address(
+            user_astl_account_f.key,
+            sol_bank.key,
+            usdc_bank.key,
+        ))
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deleverage_order_failure_not_triggered() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let pool = setup_usdc_sol_pool(&test_f, 100., 0.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let user_astl_account_f = setup_leveraged_user(&test_f).await?;
+
+    // Only trigger once the account is about to be liquidated
+    let ix = make_place_deleverage_order_ix(
+        &test_f,
+        &user_astl_account_f,
+        I80F48!(1),
+        native!(5, "SOL"),
+        native!(50, "USDC"),
+        100,
+    );
+    process_ix(&test_f, ix).await?;
+
+    let keeper_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+    let keeper_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+
+    let ix = make_execute_deleverage_order_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        keeper_sol_token_account_f.key,
+        keeper_usdc_token_account_f.key,
+        native!(5, "SOL"),
+        native!(51, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::DeleverageOrderNotTriggered
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn deleverage_order_failure_authority_transferred() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let pool = setup_usdc_sol_pool(&test_f, 100., 0.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let user_astl_account_f = setup_leveraged_user(&test_f).await?;
+
+    let ix = make_place_deleverage_order_ix(
+        &test_f,
+        &user_astl_account_f,
+        I80F48!(1_000_000_000_000),
+        native!(5, "SOL"),
+        native!(50, "USDC"),
+        100,
+    );
+    process_ix(&test_f, ix).await?;
+
+    // The account moves to a new authority
+    let mut user_astl_account = user_astl_account_f.load().await;
+    user_astl_account.authority = Pubkey::new_unique();
+    user_astl_account_f.set_account(&user_astl_account).await?;
+
+    let keeper_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+    let keeper_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+
+    let ix = make_execute_deleverage_order_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        keeper_sol_token_account_f.key,
+        keeper_usdc_token_account_f.key,
+        native!(5, "SOL"),
+        native!(51, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    Ok(())
+}
@@ -1,8 +1,18 @@
 use anchor_lang::{InstructionData, ToAccountMetas};
-use fixtures::{assert_custom_error, prelude::*};
-use astrolend::{prelude::*, state::astrolend_account::FLASHLOAN_ENABLED_FLAG};
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    prelude::*,
+    state::{
+        astrolend_account::FLASHLOAN_ENABLED_FLAG,
+        astrolend_group::{BankConfigOpt, BankVaultType},
+    },
+};
+use fixed::types::I80F48;
 use pretty_assertions::assert_eq;
-use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
 use solana_program_test::*;
 use solana_sdk::{
     compute_budget::ComputeBudgetInstruction, signer::Signer, transaction::Transaction,
@@ -18,6 +28,11 @@ use solana_sdk::{
 // 7. Flashloan fails because of invalid `end_flashloan` ix order
 // 8. Flashloan fails because `end_flashloan` ix is for another account
 // 9. Flashloan fails because account is already in a flashloan
+// 10. Flashloan fee is charged on repayment and credited to the group fees
+// 11. Flashloan fee is charged when a deposit repays the flashloan
+// 12. Flashloan fails because borrowing from the bank is disabled during flashloans
+// 13. Flashloan with callback success, fee charged
+// 14. Flashloan with callback fails because the callback underpays
 
 #[tokio::test]
 async fn flashloan_success_1op() -> anyhow::Result<()> {
@@ -535,3 +550,365 @@ async fn flashloan_fail_already_in_flashloan() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn flashloan_success_fee() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // 0.5% flashloan fee
+    sol_bank
+        .update_config(BankConfigOpt {
+            flashloan_fee_bps: Some(50),
+            ..BankConfigOpt::default()
+        })
+        .await?;
+
+    // Fund SOL lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Borrower only holds the fee
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(5).await;
+
+    let pre_group_fees = I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+
+    let borrow_ix = borrower_astl_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 1_000)
+        .await;
+
+    let repay_ix = borrower_astl_account_f
+        .make_bank_repay_ix(
+            borrower_token_account_f_sol.key,
+            sol_bank,
+            1_000,
+            Some(true),
+        )
+        .await;
+
+    borrower_astl_account_f
+        .try_flashloan(vec![borrow_ix, repay_ix], vec![], vec![])
+        .await?;
+
+    assert_eq!(borrower_token_account_f_sol.balance().await, 0);
+
+    let post_group_fees = I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+    assert_eq!(
+        post_group_fees - pre_group_fees,
+        I80F48::from(native!(5, "SOL"))
+    );
+
+    let borrower_astl_account = borrower_astl_account_f.load().await;
+    assert!(borrower_astl_account
+        .lending_account
+        .balances
+        .iter()
+        .all(|balance| balance.flashloan_borrowed == 0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_success_fee_deposit() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // 0.5% flashloan fee
+    sol_bank
+        .update_config(BankConfigOpt {
+            flashloan_fee_bps: Some(50),
+            ..BankConfigOpt::default()
+        })
+        .await?;
+
+    // Fund SOL lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Borrower only holds the fee
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(5).await;
+
+    let pre_group_fees = I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+
+    let borrow_ix = borrower_astl_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 1_000)
+        .await;
+
+    // Depositing the borrowed amount and the fee repays the flashloan
+    let deposit_ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingAccountDeposit {
+            astrolend_group: test_f.astrolend_group.key,
+            astrolend_account: borrower_astl_account_f.key,
+            signer: test_f.payer(),
+            bank: sol_bank.key,
+            signer_token_account: borrower_token_account_f_sol.key,
+            bank_liquidity_vault: sol_bank.get_vault(BankVaultType::Liquidity).0,
+            token_program: sol_bank.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingAccountDeposit {
+            amount: native!(1_005, "SOL"),
+        }
+        .data(),
+    };
+
+    borrower_astl_account_f
+        .try_flashloan(vec![borrow_ix, deposit_ix], vec![], vec![])
+        .await?;
+
+    assert_eq!(borrower_token_account_f_sol.balance().await, 0);
+
+    let post_group_fees = I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+    assert_eq!(
+        post_group_fees - pre_group_fees,
+        I80F48::from(native!(5, "SOL"))
+    );
+
+    // Nothing is left deposited or borrowed
+    let borrower_astl_account = borrower_astl_account_f.load().await;
+    let balance = borrower_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    assert_eq!(I80F48::from(balance.asset_shares), I80F48::ZERO);
+    assert_eq!(I80F48::from(balance.liability_shares), I80F48::ZERO);
+    assert_eq!(balance.flashloan_borrowed, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_fail_borrow_disabled() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            flashloan_borrow_disabled: Some(true),
+            ..BankConfigOpt::default()
+        })
+        .await?;
+
+    // Fund SOL lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_empty_token_account().await;
+
+    let borrow_ix = borrower_astl_account_f
+        .make_bank_borrow_ix(borrower_token_account_f_sol.key, sol_bank, 1_000)
+        .await;
+
+    let repay_ix = borrower_astl_account_f
+        .make_bank_repay_ix(
+            borrower_token_account_f_sol.key,
+            sol_bank,
+            1_000,
+            Some(true),
+        )
+        .await;
+
+    let flash_loan_result = borrower_astl_account_f
+        .try_flashloan(vec![borrow_ix, repay_ix], vec![], vec![])
+        .await;
+
+    assert_custom_error!(
+        flash_loan_result.unwrap_err(),
+        AstrolendError::FlashloanBorrowDisabled
+    );
+
+    Ok(())
+}
+
+async fn make_flashloan_with_callback_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    bank_f: &BankFixture,
+    token_account: Pubkey,
+    amount: u64,
+    callback_data: Vec<u8>,
+) -> Instruction {
+    let payer = test_f.payer();
+
+    let mut accounts = astrolend::accounts::LendingAccountFlashloanWithCallback {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astrolend_account_f.key,
+        signer: payer,
+        bank: bank_f.key,
+        destination_token_account: token_account,
+        bank_liquidity_vault_authority: bank_f.get_vault_authority(BankVaultType::Liquidity).0,
+        bank_liquidity_vault: bank_f.get_vault(BankVaultType::Liquidity).0,
+        token_program: bank_f.get_token_program(),
+        callback_program: mocks::id(),
+    }
+    .to_account_metas(Some(true));
+
+    // Accounts forwarded to the mock callback
+    let callback_accounts = vec![
+        AccountMeta::new_readonly(payer, true),
+        AccountMeta::new(token_account, false),
+        AccountMeta::new(bank_f.get_vault(BankVaultType::Liquidity).0, false),
+        AccountMeta::new_readonly(bank_f.get_token_program(), false),
+    ];
+    let callback_accounts_count = callback_accounts.len() as u8;
+    accounts.extend(callback_accounts);
+    accounts.extend(
+        astrolend_account_f
+            .load_observation_account_metas(vec![bank_f.key], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountFlashloanWithCallback {
+            amount,
+            callback_accounts_count,
+            callback_data,
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn flashloan_with_callback_success() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // 0.5% flashloan fee
+    sol_bank
+        .update_config(BankConfigOpt {
+            flashloan_fee_bps: Some(50),
+            ..BankConfigOpt::default()
+        })
+        .await?;
+
+    // Fund SOL lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Borrower only holds the fee
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(5).await;
+
+    let pre_group_fees = I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+
+    let ix = make_flashloan_with_callback_ix(
+        &test_f,
+        &borrower_astl_account_f,
+        sol_bank,
+        borrower_token_account_f_sol.key,
+        native!(1_000, "SOL"),
+        vec![],
+    )
+    .await;
+
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await?;
+    drop(ctx);
+
+    assert_eq!(borrower_token_account_f_sol.balance().await, 0);
+
+    let post_group_fees = I80F48::from(sol_bank.load().await.collected_group_fees_outstanding);
+    assert_eq!(
+        post_group_fees - pre_group_fees,
+        I80F48::from(native!(5, "SOL"))
+    );
+
+    let borrower_astl_account = borrower_astl_account_f.load().await;
+    assert!(borrower_astl_account
+        .lending_account
+        .balances
+        .iter()
+        .all(|balance| balance.flashloan_borrowed == 0
+            && I80F48::from(balance.liability_shares) == I80F48::ZERO));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flashloan_with_callback_fail_not_repaid() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // Fund SOL lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
+
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_empty_token_account().await;
+
+    // The mock callback repays the amount passed in `callback_data`
+    let ix = make_flashloan_with_callback_ix(
+        &test_f,
+        &borrower_astl_account_f,
+        sol_bank,
+        borrower_token_account_f_sol.key,
+        native!(1_000, "SOL"),
+        native!(999, "SOL").to_le_bytes().to_vec(),
+    )
+    .await;
+
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    let res = ctx.banks_client.process_transaction(tx).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::FlashloanNotRepaid);
+
+    Ok(())
+}
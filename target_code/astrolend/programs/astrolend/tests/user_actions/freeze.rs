@@ -0,0 +1,238 @@
+use anchor_lang::{error::ErrorCode, InstructionData, ToAccountMetas};
+use fixtures::{assert_anchor_error, assert_custom_error, prelude::*};
+use astrolend::{prelude::*, state::astrolend_account::FROZEN_FLAG};
+use pretty_assertions::assert_eq;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+// Account freeze tests
+// 1. A frozen account can deposit and repay but not withdraw or borrow, until unfrozen
+// 2. With a recovery authority, only it can unfreeze the account, and it can rotate the authority
+// 3. Transferring the account removes the recovery authority set by the previous authority
+
+async fn process_ix(
+    test_f: &TestFixture,
+    ix: Instruction,
+    signer: Option<&Keypair>,
+) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let mut signers = vec![&ctx.payer];
+    signers.extend(signer);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &signers,
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+fn make_freeze_ix(
+    astrolend_account_f: &AstrolendAccountFixture,
+    signer: Pubkey,
+    freeze: bool,
+) -> Instruction {
+    let accounts = astrolend::accounts::AstrolendAccountFreeze {
+        astrolend_account: astrolend_account_f.key,
+        signer,
+    }
+    .to_account_metas(Some(true));
+
+    let data = if freeze {
+        astrolend::instruction::AstrolendAccountFreeze {}.data()
+    } else {
+        astrolend::instruction::AstrolendAccountUnfreeze {}.data()
+    };
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data,
+    }
+}
+
+fn make_set_recovery_authority_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    recovery_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountSetRecoveryAuthority {
+            astrolend_account: astrolend_account_f.key,
+            authority: test_f.payer(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountSetRecoveryAuthority { recovery_authority }
+            .data(),
+    }
+}
+
+#[tokio::test]
+async fn freeze_blocks_withdraw_and_borrow() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let sol_depositor = test_f.create_astrolend_account().await;
+    let sol_token_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    sol_depositor
+        .try_bank_deposit(sol_token_account.key, sol_bank, 100)
+        .await?;
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let usdc_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let sol_token_account = test_f.sol_mint.create_empty_token_account().await;
+    astl_account_f
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 500)
+        .await?;
+    astl_account_f
+        .try_bank_borrow(sol_token_account.key, sol_bank, 1)
+        .await?;
+
+    let ix = make_freeze_ix(&astl_account_f, test_f.payer(), true);
+    process_ix(&test_f, ix, None).await?;
+    assert!(astl_account_f.load().await.get_flag(FROZEN_FLAG));
+
+    let res = astl_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 100, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountFrozen);
+
+    let res = astl_account_f
+        .try_bank_borrow(sol_token_account.key, sol_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountFrozen);
+
+    // Deposits and repayments are still allowed
+    astl_account_f
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 500)
+        .await?;
+    astl_account_f
+        .try_bank_repay(sol_token_account.key, sol_bank, 1, Some(true))
+        .await?;
+
+    // Without a recovery authority the authority can unfreeze the account
+    let ix = make_freeze_ix(&astl_account_f, test_f.payer(), false);
+    process_ix(&test_f, ix, None).await?;
+
+    astl_account_f
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 100, None)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn freeze_recovery_authority() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let astl_account_f = test_f.create_astrolend_account().await;
+
+    let recovery_authority = Keypair::new();
+    let ix = make_set_recovery_authority_ix(&test_f, &astl_account_f, recovery_authority.pubkey());
+    process_ix(&test_f, ix, None).await?;
+
+    let ix = make_freeze_ix(&astl_account_f, test_f.payer(), true);
+    process_ix(&test_f, ix, None).await?;
+
+    // The authority can neither unfreeze nor replace the recovery authority
+    let ix = make_freeze_ix(&astl_account_f, test_f.payer(), false);
+    let res = process_ix(&test_f, ix, None).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::Unauthorized);
+
+    let ix = make_set_recovery_authority_ix(&test_f, &astl_account_f, Pubkey::default());
+    let res = process_ix(&test_f, ix, None).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountFrozen);
+
+    // The recovery authority rotates the authority and unfreezes the account
+    let new_authority = Keypair::new().pubkey();
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountRecoverAuthority {
+            astrolend_account: astl_account_f.key,
+            recovery_authority: recovery_authority.pubkey(),
+            new_authority,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountRecoverAuthority {}.data(),
+    };
+    process_ix(&test_f, ix, Some(&recovery_authority)).await?;
+
+    let ix = make_freeze_ix(&astl_account_f, recovery_authority.pubkey(), false);
+    process_ix(&test_f, ix, Some(&recovery_authority)).await?;
+
+    let astrolend_account = astl_account_f.load().await;
+    assert_eq!(astrolend_account.authority, new_authority);
+    assert_eq!(
+        astrolend_account.recovery_authority,
+        recovery_authority.pubkey()
+    );
+    assert!(!astrolend_account.get_flag(FROZEN_FLAG));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn freeze_recovery_authority_removed_on_transfer() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let astl_account_f = test_f.create_astrolend_account().await;
+
+    let recovery_authority = Keypair::new();
+    let ix = make_set_recovery_authority_ix(&test_f, &astl_account_f, recovery_authority.pubkey());
+    process_ix(&test_f, ix, None).await?;
+
+    // Transfer the account to a new authority
+    let new_authority = Keypair::new();
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountOfferAuthorityTransfer {
+            astrolend_account: astl_account_f.key,
+            authority: test_f.payer(),
+            new_authority: new_authority.pubkey(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountOfferAuthorityTransfer {}.data(),
+    };
+    process_ix(&test_f, ix, None).await?;
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountAcceptAuthorityTransfer {
+            astrolend_account: astl_account_f.key,
+            new_authority: new_authority.pubkey(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountAcceptAuthorityTransfer {}.data(),
+    };
+    process_ix(&test_f, ix, Some(&new_authority)).await?;
+
+    let astrolend_account = astl_account_f.load().await;
+    assert_eq!(astrolend_account.recovery_authority, Pubkey::default());
+
+    // The previous recovery authority can't take the account back
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountRecoverAuthority {
+            astrolend_account: astl_account_f.key,
+            recovery_authority: recovery_authority.pubkey(),
+            new_authority: recovery_authority.pubkey(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountRecoverAuthority {}.data(),
+    };
+    let res = process_ix(&test_f, ix, Some(&recovery_authority)).await;
+    assert!(res.is_err());
+    assert_anchor_error!(res.unwrap_err(), ErrorCode::ConstraintAddress);
+
+    let astrolend_account = astl_account_f.load().await;
+    assert_eq!(astrolend_account.authority, new_authority.pubkey());
+
+    Ok(())
+}
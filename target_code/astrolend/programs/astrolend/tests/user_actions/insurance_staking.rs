@@ -0,0 +1,419 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use astrolend::{
+    constants::{
+        INSURANCE_SHARE_MINT_SEED, INSURANCE_UNSTAKE_ESCROW_SEED, INSURANCE_UNSTAKE_REQUEST_SEED,
+    },
+    prelude::*,
+    state::astrolend_group::{BankConfig, BankVaultType, InterestRateConfig},
+};
+use pretty_assertions::assert_eq;
+use solana_program::{
+    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, system_instruction,
+    system_program,
+};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+// Insurance staking tests
+// 1. Requesting an unstake escrows the shares, which are burned once the cooldown has passed
+// 2. Unstakes can't complete after the window, cancelling returns the escrowed shares
+// 3. Outstanding insurance fees are collected into the vault before the unstake is priced
+
+const UNSTAKE_COOLDOWN: u64 = 3_600;
+const UNSTAKE_WINDOW: u64 = 3_600;
+
+async fn process_ix(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+struct InsuranceStakingAccounts {
+    share_mint: Pubkey,
+    unstake_request: Pubkey,
+    unstake_escrow: Pubkey,
+    staker_share_token_account: Pubkey,
+}
+
+/// Enable staking on `bank_f` and stake `amount` USDC from a fresh payer token account.
+async fn setup_staked_insurance(
+    test_f: &TestFixture,
+    bank_f: &BankFixture,
+    amount: f64,
+) -> anyhow::Result<InsuranceStakingAccounts> {
+    let share_mint = Pubkey::find_program_address(
+        &[INSURANCE_SHARE_MINT_SEED.as_bytes(), bank_f.key.as_ref()],
+        &astrolend::id(),
+    )
+    .0;
+    let unstake_request = Pubkey::find_program_address(
+        &[
+            INSURANCE_UNSTAKE_REQUEST_SEED.as_bytes(),
+            bank_f.key.as_ref(),
+            test_f.payer().as_ref(),
+        ],
+        &astrolend::id(),
+    )
+    .0;
+    let unstake_escrow = Pubkey::find_program_address(
+        &[
+            INSURANCE_UNSTAKE_ESCROW_SEED.as_bytes(),
+            unstake_request.as_ref(),
+        ],
+        &astrolend::id(),
+    )
+    .0;
+
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolSetupInsuranceStaking {
+            astrolend_group: test_f.astrolend_group.key,
+            admin: test_f.payer(),
+            bank: bank_f.key,
+            insurance_vault: bank_f.get_vault(BankVaultType::Insurance).0,
+            insurance_vault_authority: bank_f.get_vault_authority(BankVaultType::Insurance).0,
+            insurance_share_mint: share_mint,
+            token_program: bank_f.get_token_program(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolSetupInsuranceStaking {
+            unstake_cooldown: UNSTAKE_COOLDOWN,
+            unstake_window: UNSTAKE_WINDOW,
+        }
+        .data(),
+    };
+    process_ix(test_f, ix).await?;
+
+    let staker_share_token_account = Keypair::new();
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let rent = ctx.banks_client.get_rent().await?;
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::create_account(
+                    &ctx.payer.pubkey(),
+                    &staker_share_token_account.pubkey(),
+                    rent.minimum_balance(spl_token::state::Account::LEN),
+                    spl_token::state::Account::LEN as u64,
+                    &spl_token::ID,
+                ),
+                spl_token::instruction::initialize_account3(
+                    &spl_token::ID,
+                    &staker_share_token_account.pubkey(),
+                    &share_mint,
+                    &ctx.payer.pubkey(),
+                )?,
+            ],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, &staker_share_token_account],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
+    }
+
+    let staker_token_account_f = bank_f.mint.create_token_account_and_mint_to(amount).await;
+
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolStakeInsurance {
+            astrolend_group: test_f.astrolend_group.key,
+            bank: bank_f.key,
+            staker: test_f.payer(),
+            staker_token_account: staker_token_account_f.key,
+            liquidity_vault_authority: bank_f.get_vault_authority(BankVaultType::Liquidity).0,
+            liquidity_vault: bank_f.get_vault(BankVaultType::Liquidity).0,
+            insurance_vault: bank_f.get_vault(BankVaultType::Insurance).0,
+            insurance_vault_authority: bank_f.get_vault_authority(BankVaultType::Insurance).0,
+            insurance_share_mint: share_mint,
+            staker_share_token_account: staker_share_token_account.pubkey(),
+            token_program: bank_f.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolStakeInsurance {
+            amount: native!(amount, "USDC", f64),
+        }
+        .data(),
+    };
+    process_ix(test_f, ix).await?;
+
+    Ok(InsuranceStakingAccounts {
+        share_mint,
+        unstake_request,
+        unstake_escrow,
+        staker_share_token_account: staker_share_token_account.pubkey(),
+    })
+}
+
+fn make_request_unstake_ix(
+    test_f: &TestFixture,
+    bank_f: &BankFixture,
+    staking: &InsuranceStakingAccounts,
+    shares: u64,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolRequestInsuranceUnstake {
+            astrolend_group: test_f.astrolend_group.key,
+            bank: bank_f.key,
+            staker: test_f.payer(),
+            staker_share_token_account: staking.staker_share_token_account,
+            insurance_vault_authority: bank_f.get_vault_authority(BankVaultType::Insurance).0,
+            insurance_share_mint: staking.share_mint,
+            unstake_request: staking.unstake_request,
+            unstake_escrow: staking.unstake_escrow,
+            token_program: bank_f.get_token_program(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolRequestInsuranceUnstake { shares }.data(),
+    }
+}
+
+fn make_unstake_ix(
+    test_f: &TestFixture,
+    bank_f: &BankFixture,
+    staking: &InsuranceStakingAccounts,
+    destination_token_account: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolUnstakeInsurance {
+            astrolend_group: test_f.astrolend_group.key,
+            bank: bank_f.key,
+            staker: test_f.payer(),
+            liquidity_vault_authority: bank_f.get_vault_authority(BankVaultType::Liquidity).0,
+            liquidity_vault: bank_f.get_vault(BankVaultType::Liquidity).0,
+            insurance_vault: bank_f.get_vault(BankVaultType::Insurance).0,
+            insurance_vault_authority: bank_f.get_vault_authority(BankVaultType::Insurance).0,
+            insurance_share_mint: staking.share_mint,
+            destination_token_account,
+            unstake_request: staking.unstake_request,
+            unstake_escrow: staking.unstake_escrow,
+            token_program: bank_f.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolUnstakeInsurance {}.data(),
+    }
+}
+
+async fn token_balance(test_f: &TestFixture, key: Pubkey) -> u64 {
+    TokenAccountFixture::fetch(test_f.context.clone(), key)
+        .await
+        .balance()
+        .await
+}
+
+async fn account_exists(test_f: &TestFixture, key: Pubkey) -> anyhow::Result<bool> {
+    Ok(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(key)
+        .await?
+        .is_some())
+}
+
+#[tokio::test]
+async fn insurance_unstake_after_cooldown() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let staking = setup_staked_insurance(&test_f, usdc_bank, 100.).await?;
+    let shares = token_balance(&test_f, staking.staker_share_token_account).await;
+    assert_eq!(shares, native!(100, "USDC"));
+
+    // Request: the shares leave the staker's wallet for the escrow
+    let ix = make_request_unstake_ix(&test_f, usdc_bank, &staking, shares);
+    process_ix(&test_f, ix).await?;
+
+    assert_eq!(
+        token_balance(&test_f, staking.staker_share_token_account).await,
+        0
+    );
+    assert_eq!(token_balance(&test_f, staking.unstake_escrow).await, shares);
+
+    // Cooldown: the unstake can't complete yet
+    let destination_f = test_f.usdc_mint.create_empty_token_account().await;
+    let ix = make_unstake_ix(&test_f, usdc_bank, &staking, destination_f.key);
+    let res = process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::InsuranceUnstakeCooldownActive
+    );
+
+    // Complete: the escrowed shares are burned for the staked tokens
+    test_f.advance_time(UNSTAKE_COOLDOWN as i64).await;
+
+    let ix = make_unstake_ix(&test_f, usdc_bank, &staking, destination_f.key);
+    process_ix(&test_f, ix).await?;
+
+    assert_eq!(destination_f.balance().await, native!(100, "USDC"));
+    assert_eq!(
+        token_balance(&test_f, usdc_bank.get_vault(BankVaultType::Insurance).0).await,
+        0
+    );
+    assert!(!account_exists(&test_f, staking.unstake_escrow).await?);
+    assert!(!account_exists(&test_f, staking.unstake_request).await?);
+
+    let share_mint_account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(staking.share_mint)
+        .await?
+        .unwrap();
+    assert_eq!(
+        spl_token::state::Mint::unpack(&share_mint_account.data)?.supply,
+        0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insurance_unstake_expired_and_cancelled() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let staking = setup_staked_insurance(&test_f, usdc_bank, 100.).await?;
+    let shares = token_balance(&test_f, staking.staker_share_token_account).await;
+
+    let ix = make_request_unstake_ix(&test_f, usdc_bank, &staking, shares / 2);
+    process_ix(&test_f, ix).await?;
+
+    assert_eq!(
+        token_balance(&test_f, staking.staker_share_token_account).await,
+        shares - shares / 2
+    );
+
+    // Window expired: the unstake can no longer complete
+    test_f
+        .advance_time((UNSTAKE_COOLDOWN + UNSTAKE_WINDOW + 1) as i64)
+        .await;
+
+    let destination_f = test_f.usdc_mint.create_empty_token_account().await;
+    let ix = make_unstake_ix(&test_f, usdc_bank, &staking, destination_f.key);
+    let res = process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::InsuranceUnstakeWindowExpired
+    );
+
+    // Cancel: the escrowed shares go back to the staker
+    let ix = Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolCancelInsuranceUnstake {
+            bank: usdc_bank.key,
+            staker: test_f.payer(),
+            staker_share_token_account: staking.staker_share_token_account,
+            insurance_vault_authority: usdc_bank.get_vault_authority(BankVaultType::Insurance).0,
+            insurance_share_mint: staking.share_mint,
+            unstake_request: staking.unstake_request,
+            unstake_escrow: staking.unstake_escrow,
+            token_program: usdc_bank.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolCancelInsuranceUnstake {}.data(),
+    };
+    process_ix(&test_f, ix).await?;
+
+    assert_eq!(
+        token_balance(&test_f, staking.staker_share_token_account).await,
+        shares
+    );
+    assert_eq!(destination_f.balance().await, 0);
+    assert!(!account_exists(&test_f, staking.unstake_escrow).await?);
+    assert!(!account_exists(&test_f, staking.unstake_request).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn insurance_unstake_includes_outstanding_fees() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::Usdc,
+                config: Some(BankConfig {
+                    interest_rate_config: InterestRateConfig {
+                        insurance_fee_fixed_apr: I80F48!(0.1).into(),
+                        ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                    },
+                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
+                }),
+            },
+            TestBankSetting {
+                mint: BankMint::Sol,
+                config: None,
+            },
+        ],
+    }))
+    .await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let staking = setup_staked_insurance(&test_f, usdc_bank, 100.).await?;
+    let shares = token_balance(&test_f, staking.staker_share_token_account).await;
+
+    let lender_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_account_f
+        .try_bank_deposit(lender_token_account.key, usdc_bank, 1_000)
+        .await?;
+
+    let borrower_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(200).await;
+    borrower_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank, 200)
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank, 500)
+        .await?;
+
+    // A year of 10% insurance fees on the 500 USDC borrowed, never collected
+    test_f.advance_time(31_536_000).await;
+
+    let ix = make_request_unstake_ix(&test_f, usdc_bank, &staking, shares);
+    process_ix(&test_f, ix).await?;
+    test_f.advance_time(UNSTAKE_COOLDOWN as i64).await;
+
+    let destination_f = test_f.usdc_mint.create_empty_token_account().await;
+    let ix = make_unstake_ix(&test_f, usdc_bank, &staking, destination_f.key);
+    process_ix(&test_f, ix).await?;
+
+    // The staker is paid the stake and the fees earned while staked
+    assert_eq_noise!(
+        destination_f.balance().await as i64,
+        native!(150, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.load().await.collected_insurance_fees_outstanding),
+        I80F48::ZERO,
+        I80F48!(1)
+    );
+
+    Ok(())
+}
@@ -1,15 +1,34 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
 use anchor_spl::token_2022::spl_token_2022::extension::{
     transfer_fee::TransferFeeConfig, BaseStateWithExtensions,
 };
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
-use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*, test::PYTH_SOL_FEED};
 use astrolend::{
     prelude::*,
-    state::astrolend_group::{Bank, BankConfig, BankConfigOpt, BankVaultType},
+    state::{
+        astrolend_account::MAX_LENDING_ACCOUNT_BALANCES,
+        astrolend_group::{Bank, BankConfig, BankConfigOpt, BankVaultType},
+    },
 };
 use pretty_assertions::assert_eq;
+use solana_program::{
+    address_lookup_table::{
+        instruction::{create_lookup_table as create_lookup_table_ix, extend_lookup_table},
+        AddressLookupTableAccount,
+    },
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
 use solana_program_test::*;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    message::{v0, VersionedMessage},
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
 use test_case::test_case;
 
 #[test_case(100., 9.9, 1., BankMint::Usdc, BankMint::Sol)]
@@ -384,6 +403,211 @@ async fn astrolend_account_liquidation_success_many_balances() -> anyhow::Result
     Ok(())
 }
 
+/// Create a lookup table of `addresses`, usable once this returns.
+async fn create_lookup_table(
+    test_f: &TestFixture,
+    addresses: Vec<Pubkey>,
+) -> anyhow::Result<AddressLookupTableAccount> {
+    let clock: Clock = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_sysvar()
+        .await?;
+
+    let key = {
+        let mut ctx = test_f.context.borrow_mut();
+        let payer = ctx.payer.pubkey();
+
+        // The table is derived from a recent slot
+        ctx.warp_to_slot(clock.slot + 1)?;
+
+        let (create_ix, key) = create_lookup_table_ix(payer, payer, clock.slot);
+        let ixs = [create_ix].into_iter().chain(
+            addresses
+                .chunks(20)
+                .map(|chunk| extend_lookup_table(key, payer, Some(payer), chunk.to_vec())),
+        );
+        for ix in ixs {
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&payer),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            );
+            ctx.banks_client.process_transaction(tx).await?;
+        }
+
+        // Extended addresses can be looked up from the next slot
+        ctx.warp_to_slot(clock.slot + 2)?;
+
+        key
+    };
+    test_f.set_time(clock.unix_timestamp);
+
+    Ok(AddressLookupTableAccount { key, addresses })
+}
+
+#[tokio::test]
+async fn astrolend_account_liquidation_success_full_account() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    // Borrower deposits 100 SOL worth of $1000 and borrows $999
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_astl_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_astl_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
+
+    // The other balances are filled in banks each with its own oracle, the most accounts a
+    // liquidation of a full account can take
+    let sol_feed = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(PYTH_SOL_FEED)
+        .await?
+        .unwrap();
+    let mut lookup_addresses = vec![];
+    for _ in 2..MAX_LENDING_ACCOUNT_BALANCES {
+        let oracle = Pubkey::new_unique();
+        test_f
+            .context
+            .borrow_mut()
+            .set_account(&oracle, &sol_feed.clone().into());
+
+        let mint_f = MintFixture::new(test_f.context.clone(), None, None).await;
+        let bank_f = test_f
+            .astrolend_group
+            .try_lending_pool_add_bank(
+                &mint_f,
+                BankConfig {
+                    oracle_keys: create_oracle_key_array(oracle),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                },
+            )
+            .await?;
+        let token_account_f = mint_f.create_empty_token_account().await;
+        borrower_astl_account_f
+            .try_bank_deposit(token_account_f.key, &bank_f, 0)
+            .await?;
+
+        lookup_addresses.extend([bank_f.key, oracle]);
+    }
+
+    let borrower_ma = borrower_astl_account_f.load().await;
+    assert!(borrower_ma
+        .lending_account
+        .balances
+        .iter()
+        .all(|balance| balance.active));
+
+    // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let mut accounts = astrolend::accounts::LendingAccountLiquidate {
+        astrolend_group: test_f.astrolend_group.key,
+        asset_bank: sol_bank_f.key,
+        liab_bank: usdc_bank_f.key,
+        liquidator_astrolend_account: lender_astl_account_f.key,
+        signer: test_f.payer(),
+        liquidatee_astrolend_account: borrower_astl_account_f.key,
+        bank_liquidity_vault_authority: usdc_bank_f.get_vault_authority(BankVaultType::Liquidity).0,
+        bank_liquidity_vault: usdc_bank_f.get_vault(BankVaultType::Liquidity).0,
+        bank_insurance_vault: usdc_bank_f.get_vault(BankVaultType::Insurance).0,
+        token_program: usdc_bank_f.get_token_program(),
+    }
+    .to_account_metas(Some(true));
+    for bank_f in [sol_bank_f, usdc_bank_f] {
+        accounts.push(AccountMeta::new_readonly(
+            bank_f.load().await.config.oracle_keys[0],
+            false,
+        ));
+    }
+    accounts.extend(
+        lender_astl_account_f
+            .load_observation_account_metas(vec![sol_bank_f.key], vec![])
+            .await,
+    );
+    accounts.extend(
+        borrower_astl_account_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+    let liquidate_ix = Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountLiquidate {
+            asset_amount: native!(1, "SOL"),
+        }
+        .data(),
+    };
+
+    // The liquidatee accounts don't fit a legacy transaction, they are looked up
+    let lookup_table = create_lookup_table(&test_f, lookup_addresses).await?;
+    let message = v0::Message::try_compile(
+        &test_f.payer(),
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+            liquidate_ix,
+        ],
+        &[lookup_table],
+        test_f.get_latest_blockhash().await,
+    )?;
+    let tx =
+        VersionedTransaction::try_new(VersionedMessage::V0(message), &[&test_f.payer_keypair()])?;
+
+    let simulation = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .simulate_transaction(tx.clone())
+        .await?;
+    assert!(matches!(simulation.result, Some(Ok(()))));
+    assert!(simulation.simulation_details.unwrap().units_consumed < 1_400_000);
+
+    test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .process_transaction(tx)
+        .await?;
+
+    // Borrower should have 99 SOL
+    let sol_bank: Bank = sol_bank_f.load().await;
+    let borrower_ma = borrower_astl_account_f.load().await;
+    assert_eq!(
+        sol_bank
+            .get_asset_amount(borrower_ma.lending_account.balances[0].asset_shares.into())
+            .unwrap(),
+        I80F48::from(native!(99, "SOL"))
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn astrolend_account_liquidation_success_swb() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
@@ -401,7 +625,12 @@ async fn astrolend_account_liquidation_success_swb() -> anyhow::Result<()> {
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -523,7 +752,12 @@ async fn astrolend_account_liquidation_failure_liquidatee_not_unhealthy() -> any
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -626,7 +860,12 @@ async fn astrolend_account_liquidation_failure_liquidator_no_collateral() -> any
                 config: None,
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: None,
+        }),
     }))
     .await;
 
@@ -733,3 +972,204 @@ async fn astrolend_account_liquidation_failure_bank_not_liquidatable() -> anyhow
 
     Ok(())
 }
+
+async fn process_ix(test_f: &TestFixture, ix: Instruction) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+async fn make_soft_liquidate_ix(
+    test_f: &TestFixture,
+    liquidator_f: &AstrolendAccountFixture,
+    liquidatee_f: &AstrolendAccountFixture,
+    asset_bank_f: &BankFixture,
+    asset_amount: u64,
+    liab_bank_f: &BankFixture,
+) -> Instruction {
+    let mut accounts = astrolend::accounts::LendingAccountLiquidate {
+        astrolend_group: test_f.astrolend_group.key,
+        asset_bank: asset_bank_f.key,
+        liab_bank: liab_bank_f.key,
+        liquidator_astrolend_account: liquidator_f.key,
+        signer: test_f.payer(),
+        liquidatee_astrolend_account: liquidatee_f.key,
+        bank_liquidity_vault_authority: liab_bank_f.get_vault_authority(BankVaultType::Liquidity).0,
+        bank_liquidity_vault: liab_bank_f.get_vault(BankVaultType::Liquidity).0,
+        bank_insurance_vault: liab_bank_f.get_vault(BankVaultType::Insurance).0,
+        token_program: liab_bank_f.get_token_program(),
+    }
+    .to_account_metas(Some(true));
+
+    for bank_f in [asset_bank_f, liab_bank_f] {
+        accounts.push(AccountMeta::new_readonly(
+            bank_f.load().await.config.oracle_keys[0],
+            false,
+        ));
+    }
+    accounts.extend(
+        liquidator_f
+            .load_observation_account_metas(vec![asset_bank_f.key], vec![])
+            .await,
+    );
+    accounts.extend(
+        liquidatee_f
+            .load_observation_account_metas(vec![], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountSoftLiquidate { asset_amount }.data(),
+    }
+}
+
+#[tokio::test]
+async fn astrolend_account_soft_liquidation_success_capped_per_window() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_astl_account_f = test_f.create_astrolend_account().await;
+    let liquidator_token_account_usdc =
+        test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    liquidator_astl_account_f
+        .try_bank_deposit(liquidator_token_account_usdc.key, usdc_bank_f, 200)
+        .await?;
+
+    // 50 USDC of debt against 10 SOL
+    let liquidatee_astl_account_f = test_f.create_astrolend_account().await;
+    let liquidatee_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let liquidatee_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    liquidatee_astl_account_f
+        .try_bank_deposit(liquidatee_token_account_sol.key, sol_bank_f, 10)
+        .await?;
+    liquidatee_astl_account_f
+        .try_bank_borrow(liquidatee_token_account_usdc.key, usdc_bank_f, 50)
+        .await?;
+
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            liability_weight_init: Some(I80F48!(1).into()),
+            liability_weight_maint: Some(I80F48!(1).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Maintenance health: 100 * 0.6 - 50 = 10
+    // Soft liquidation health: 100 * (0.6 - 0.35 * 0.5) - 50 = -7.5
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.6).into()),
+            soft_liquidation_threshold: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Regular liquidations are not allowed above the maintenance requirement
+    let res = liquidator_astl_account_f
+        .try_liquidate(&liquidatee_astl_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    let ix = make_soft_liquidate_ix(
+        &test_f,
+        &liquidator_astl_account_f,
+        &liquidatee_astl_account_f,
+        sol_bank_f,
+        native!(0.5, "SOL", f64),
+        usdc_bank_f,
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    // Liquidatee liability is paid down by the collateral value, net of the reduced fees
+    let liquidatee_astl_account = liquidatee_astl_account_f.load().await;
+    let usdc_balance = liquidatee_astl_account
+        .lending_account
+        .get_balance(&usdc_bank_f.key)
+        .unwrap();
+    let usdc_amount = usdc_bank_f
+        .load()
+        .await
+        .get_liability_amount(usdc_balance.liability_shares.into())?;
+    assert_eq_noise!(
+        usdc_amount,
+        I80F48::from(native!(50. - 5. * 0.985, "USDC", f64)),
+        native!(0.0001, "USDC", f64)
+    );
+
+    // A further $6 of collateral exceeds the $10 soft liquidated per window
+    let ix = make_soft_liquidate_ix(
+        &test_f,
+        &liquidator_astl_account_f,
+        &liquidatee_astl_account_f,
+        sol_bank_f,
+        native!(0.6, "SOL", f64),
+        usdc_bank_f,
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SoftLiquidationCapExceeded);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn astrolend_account_soft_liquidation_failure_zone_disabled() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    let liquidator_astl_account_f = test_f.create_astrolend_account().await;
+    let liquidator_token_account_usdc =
+        test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    liquidator_astl_account_f
+        .try_bank_deposit(liquidator_token_account_usdc.key, usdc_bank_f, 200)
+        .await?;
+
+    let liquidatee_astl_account_f = test_f.create_astrolend_account().await;
+    let liquidatee_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let liquidatee_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    liquidatee_astl_account_f
+        .try_bank_deposit(liquidatee_token_account_sol.key, sol_bank_f, 10)
+        .await?;
+    liquidatee_astl_account_f
+        .try_bank_borrow(liquidatee_token_account_usdc.key, usdc_bank_f, 50)
+        .await?;
+
+    // Below the initial requirement, but without a soft liquidation threshold
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.6).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let ix = make_soft_liquidate_ix(
+        &test_f,
+        &liquidator_astl_account_f,
+        &liquidatee_astl_account_f,
+        sol_bank_f,
+        native!(0.5, "SOL", f64),
+        usdc_bank_f,
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::IllegalLiquidation);
+
+    Ok(())
+}
@@ -0,0 +1,557 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    prelude::*,
+    state::astrolend_group::{BankConfigOpt, BankVaultType},
+};
+use pretty_assertions::assert_eq;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::*;
+
+use super::swap_collateral::{allow_swap_program, process_ix, setup_usdc_sol_pool, MockPool};
+
+// Leveraged looping tests, SOL collateral against a USDC liability
+// 1. Loop into SOL then close the position
+// 2. Loop fails because the position exceeds the initial weights
+// 3. Loop to a target leverage sized off-chain by the caller
+// 4. Account below the initial weights can self-deleverage but not deleverage
+// 5. Self-deleverage fails when the swap output is below the oracle value of the collateral
+
+enum LoopAction {
+    Loop {
+        borrow_amount: u64,
+        min_deposit_amount: u64,
+    },
+    Deleverage {
+        withdraw_amount: u64,
+        min_repay_amount: u64,
+        close_position: bool,
+    },
+    SelfDeleverage {
+        withdraw_amount: u64,
+        min_repay_amount: u64,
+    },
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn make_loop_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    pool: &MockPool,
+    sol_token_account: Pubkey,
+    usdc_token_account: Pubkey,
+    action: LoopAction,
+    swap_in: u64,
+    swap_out: u64,
+) -> Instruction {
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let mut accounts = astrolend::accounts::LendingAccountLoopPosition {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astrolend_account_f.key,
+        signer: test_f.payer(),
+        collateral_bank: sol_bank.key,
+        collateral_token_account: sol_token_account,
+        collateral_bank_liquidity_vault_authority: sol_bank
+            .get_vault_authority(BankVaultType::Liquidity)
+            .0,
+        collateral_bank_liquidity_vault: sol_bank.get_vault(BankVaultType::Liquidity).0,
+        collateral_token_program: sol_bank.get_token_program(),
+        liability_bank: usdc_bank.key,
+        liability_token_account: usdc_token_account,
+        liability_bank_liquidity_vault_authority: usdc_bank
+            .get_vault_authority(BankVaultType::Liquidity)
+            .0,
+        liability_bank_liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+        liability_token_program: usdc_bank.get_token_program(),
+        swap_program: mocks::id(),
+    }
+    .to_account_metas(Some(true));
+
+    // Looping swaps USDC into SOL, deleveraging swaps SOL back into USDC
+    let (source_a, pool_a, pool_b, destination_b) = match action {
+        LoopAction::Loop { .. } => (
+            usdc_token_account,
+            pool.usdc_pool,
+            pool.sol_pool,
+            sol_token_account,
+        ),
+        LoopAction::Deleverage { .. } | LoopAction::SelfDeleverage { .. } => (
+            sol_token_account,
+            pool.sol_pool,
+            pool.usdc_pool,
+            usdc_token_account,
+        ),
+    };
+    let swap_accounts = mocks::accounts::SwapLikeJupiter {
+        user_authority: test_f.payer(),
+        pool_auth: pool.pool_auth,
+        pool_a,
+        pool_b,
+        source_a,
+        destination_b,
+        token_program: anchor_spl::token::ID,
+    }
+    .to_account_metas(Some(true));
+    let swap_accounts_count = swap_accounts.len() as u8;
+    accounts.extend(swap_accounts);
+    accounts.extend(
+        astrolend_account_f
+            .load_observation_account_metas(vec![sol_bank.key, usdc_bank.key], vec![])
+            .await,
+    );
+
+    let swap_data = mocks::instruction::SwapLikeJupiter {
+        amt_a: swap_in,
+        amt_b: swap_out,
+    }
+    .data();
+
+    let data = match action {
+        LoopAction::Loop {
+            borrow_amount,
+            min_deposit_amount,
+        } => astrolend::instruction::LendingAccountLoop {
+            borrow_amount,
+            min_deposit_amount,
+            swap_accounts_count,
+            swap_data,
+        }
+        .data(),
+        LoopAction::Deleverage {
+            withdraw_amount,
+            min_repay_amount,
+            close_position,
+        } => astrolend::instruction::LendingAccountDeleverage {
+            withdraw_amount,
+            min_repay_amount,
+            close_position,
+            swap_accounts_count,
+            swap_data,
+        }
+        .data(),
+        LoopAction::SelfDeleverage {
+            withdraw_amount,
+            min_repay_amount,
+        } => astrolend::instruction::LendingAccountSelfDeleverage {
+            withdraw_amount,
+            min_repay_amount,
+            close_position: false,
+            swap_accounts_count,
+            swap_data,
+        }
+        .data(),
+    };
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data,
+    }
+}
+
+#[tokio::test]
+async fn loop_and_close_position_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let pool = setup_usdc_sol_pool(&test_f, 1_000., 100.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    // Fund USDC lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // User starts with 10 SOL of collateral
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_astl_account_f
+        .try_bank_deposit(user_sol_token_account_f.key, sol_bank, 10)
+        .await?;
+    let user_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+
+    // Borrow 100 USDC and loop it into 10 more SOL
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::Loop {
+            borrow_amount: native!(100, "USDC"),
+            min_deposit_amount: native!(10, "SOL"),
+        },
+        native!(100, "USDC"),
+        native!(10, "SOL"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    let user_astl_account = user_astl_account_f.load().await;
+    let sol_balance = user_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    let sol_amount = sol_bank
+        .load()
+        .await
+        .get_asset_amount(sol_balance.asset_shares.into())?;
+    assert_eq!(sol_amount, I80F48::from(native!(20, "SOL")));
+
+    let usdc_balance = user_astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    let usdc_amount = usdc_bank
+        .load()
+        .await
+        .get_liability_amount(usdc_balance.liability_shares.into())?;
+    assert_eq!(usdc_amount, I80F48::from(native!(100, "USDC")));
+
+    // Close the position, swapping 11 of the 20 SOL into enough USDC to cover the liability
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::Deleverage {
+            withdraw_amount: 0,
+            min_repay_amount: native!(101, "USDC"),
+            close_position: true,
+        },
+        native!(11, "SOL"),
+        native!(101, "USDC"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    let user_astl_account = user_astl_account_f.load().await;
+    assert!(user_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .is_none());
+    assert!(user_astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .is_none());
+
+    // Leftovers stay in the wallet
+    assert_eq!(user_sol_token_account_f.balance().await, native!(9, "SOL"));
+    assert!(user_usdc_token_account_f.balance().await > 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn loop_failure_exceeds_init_weights() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let pool = setup_usdc_sol_pool(&test_f, 0., 100.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    // Fund USDC lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_astl_account_f
+        .try_bank_deposit(user_sol_token_account_f.key, sol_bank, 10)
+        .await?;
+    let user_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+
+    // 500 USDC of debt against 15 SOL
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::Loop {
+            borrow_amount: native!(500, "USDC"),
+            min_deposit_amount: native!(5, "SOL"),
+        },
+        native!(500, "USDC"),
+        native!(5, "SOL"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    Ok(())
+}
+
+/// Value to borrow for `lending_account_loop` to bring a position to `leverage`, before slippage.
+/// The instruction takes the borrow amount, sizing it for a target leverage is up to the caller.
+fn loop_borrow_value_for_leverage(assets_value: f64, liabilities_value: f64, leverage: f64) -> f64 {
+    leverage * (assets_value - liabilities_value) - assets_value
+}
+
+#[tokio::test]
+async fn loop_to_target_leverage_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let pool = setup_usdc_sol_pool(&test_f, 0., 100.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    // Fund USDC lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // User starts with 10 SOL of collateral, worth 100 USDC
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_astl_account_f
+        .try_bank_deposit(user_sol_token_account_f.key, sol_bank, 10)
+        .await?;
+    let user_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+
+    // 1.5x leverage takes a 50 USDC borrow, swapped into 5 SOL at the oracle price
+    let borrow_value = loop_borrow_value_for_leverage(100., 0., 1.5);
+    assert_eq!(borrow_value, 50.);
+
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::Loop {
+            borrow_amount: native!(borrow_value, "USDC", f64),
+            min_deposit_amount: native!(5, "SOL"),
+        },
+        native!(borrow_value, "USDC", f64),
+        native!(5, "SOL"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    let user_astl_account = user_astl_account_f.load().await;
+    let sol_balance = user_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    let sol_amount = sol_bank
+        .load()
+        .await
+        .get_asset_amount(sol_balance.asset_shares.into())?;
+    let usdc_balance = user_astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    let usdc_amount = usdc_bank
+        .load()
+        .await
+        .get_liability_amount(usdc_balance.liability_shares.into())?;
+
+    // 150 USDC of assets over 100 USDC of equity
+    assert_eq!(sol_amount, I80F48::from(native!(15, "SOL")));
+    assert_eq!(usdc_amount, I80F48::from(native!(50, "USDC")));
+
+    // The instruction does not shrink the borrow to the initial weights, overshooting fails
+    let borrow_value = loop_borrow_value_for_leverage(150., 50., 5.);
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::Loop {
+            borrow_amount: native!(borrow_value, "USDC", f64),
+            min_deposit_amount: native!(35, "SOL"),
+        },
+        native!(borrow_value, "USDC", f64),
+        native!(35, "SOL"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_deleverage_success_below_init_weights() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let pool = setup_usdc_sol_pool(&test_f, 100., 0.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // Fund USDC lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // 60 USDC of debt against 10 SOL
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_astl_account_f
+        .try_bank_deposit(user_sol_token_account_f.key, sol_bank, 10)
+        .await?;
+    let user_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+    user_astl_account_f
+        .try_bank_borrow(user_usdc_token_account_f.key, usdc_bank, 60)
+        .await?;
+
+    // Bring the account below the initial requirement, but not the maintenance one
+    test_f
+        .get_bank_mut(&BankMint::Sol)
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(1).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // Sell 1 SOL for 11 USDC
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::Deleverage {
+            withdraw_amount: native!(1, "SOL"),
+            min_repay_amount: native!(11, "USDC"),
+            close_position: false,
+        },
+        native!(1, "SOL"),
+        native!(11, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::SelfDeleverage {
+            withdraw_amount: native!(1, "SOL"),
+            min_repay_amount: native!(11, "USDC"),
+        },
+        native!(1, "SOL"),
+        native!(11, "USDC"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    let user_astl_account = user_astl_account_f.load().await;
+    let usdc_balance = user_astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    let usdc_amount = usdc_bank
+        .load()
+        .await
+        .get_liability_amount(usdc_balance.liability_shares.into())?;
+    assert_eq!(usdc_amount, I80F48::from(native!(49, "USDC")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_deleverage_failure_swap_below_oracle_value() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let pool = setup_usdc_sol_pool(&test_f, 100., 0.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    // Fund USDC lender
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // 40 USDC of debt against 10 SOL
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    user_astl_account_f
+        .try_bank_deposit(user_sol_token_account_f.key, sol_bank, 10)
+        .await?;
+    let user_usdc_token_account_f = test_f.usdc_mint.create_empty_token_account().await;
+    user_astl_account_f
+        .try_bank_borrow(user_usdc_token_account_f.key, usdc_bank, 40)
+        .await?;
+
+    // With a maintenance weight of 0.5, any sale of 1 SOL above 5 USDC improves the health
+    test_f
+        .get_bank_mut(&BankMint::Sol)
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Sell 1 SOL, worth 10 USDC at the oracle price, for 6 USDC
+    let ix = make_loop_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_sol_token_account_f.key,
+        user_usdc_token_account_f.key,
+        LoopAction::SelfDeleverage {
+            withdraw_amount: native!(1, "SOL"),
+            min_repay_amount: native!(6, "USDC"),
+        },
+        native!(1, "SOL"),
+        native!(6, "USDC"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SwapOutputBelowMinimum);
+
+    Ok(())
+}
@@ -2,26 +2,42 @@ mod borrow;
 mod close_account;
 mod close_balance;
 mod create_account;
+mod delegate;
+mod deleverage_order;
 mod deposit;
 mod flash_loan;
+mod freeze;
+mod insurance_staking;
 mod liquidate;
+mod loop_position;
 mod repay;
+mod swap_collateral;
+mod transfer_authority;
+mod transfer_balance;
 mod withdraw;
 
-use anchor_lang::prelude::Clock;
+use anchor_lang::{prelude::Clock, InstructionData, ToAccountMetas};
 use fixed::types::I80F48;
 use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*};
 use astrolend::{
     assert_eq_with_tolerance,
     constants::{
-        EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, MIN_EMISSIONS_START_TIME,
+        EMISSIONS_AUTH_SEED, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
+        EMISSIONS_TOKEN_ACCOUNT_SEED, MIN_EMISSIONS_START_TIME,
     },
     prelude::*,
-    state::astrolend_account::{
-        BankAccountWrapper, DISABLED_FLAG, FLASHLOAN_ENABLED_FLAG, IN_FLASHLOAN_FLAG,
+    state::{
+        astrolend_account::{
+            BankAccountWrapper, DISABLED_FLAG, FLASHLOAN_ENABLED_FLAG, IN_FLASHLOAN_FLAG,
+        },
+        astrolend_group::BankVaultType,
     },
 };
 use pretty_assertions::assert_eq;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
 use solana_program_test::*;
 use solana_sdk::timing::SECONDS_PER_YEAR;
 
@@ -141,7 +157,7 @@ async fn astrolend_account_correct_balance_selection_after_closing_position() ->
     let bank_account = BankAccountWrapper::find(
         &usdc_bank_f.key,
         &mut usdc_bank,
-        &mut astrolend_account.lending_account,
+        &mut astrolend_account,
     );
 
     assert!(bank_account.is_ok());
@@ -208,7 +224,7 @@ async fn emissions_test() -> anyhow::Result<()> {
         .create_token_account_and_mint_to(200)
         .await;
 
-    let res = sol_bank
+    sol_bank
         .try_setup_emissions(
             EMISSIONS_FLAG_BORROW_ACTIVE,
             1_000_000,
@@ -217,6 +233,25 @@ async fn emissions_test() -> anyhow::Result<()> {
             funding_account.key,
             sol_emissions_mint_2.token_program,
         )
+        .await?;
+
+    // Every stream of the bank is taken
+    let sol_emissions_mint_3 =
+        MintFixture::new_token_22(test_f.context.clone(), None, Some(6), &[]).await;
+
+    let funding_account = sol_emissions_mint_3
+        .create_token_account_and_mint_to(200)
+        .await;
+
+    let res = sol_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_BORROW_ACTIVE,
+            1_000_000,
+            native!(50, 6),
+            sol_emissions_mint_3.key,
+            funding_account.key,
+            sol_emissions_mint_3.token_program,
+        )
         .await;
 
     assert_custom_error!(res.unwrap_err(), AstrolendError::EmissionsAlreadySetup);
@@ -471,6 +506,437 @@ async fn emissions_setup_t22_with_fee() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Remaining accounts claiming the `emissions_mint` emissions of `bank_f`, followed by the
+/// destination token account or the deposit bank and its liquidity vault.
+fn claim_all_emissions_metas(
+    bank_f: &BankFixture,
+    emissions_mint: Pubkey,
+    destination: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let emissions_auth = Pubkey::find_program_address(
+        &[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            bank_f.key.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        &astrolend::id(),
+    )
+    .0;
+
+    let mut metas = vec![
+        AccountMeta::new(bank_f.key, false),
+        AccountMeta::new_readonly(emissions_mint, false),
+        AccountMeta::new_readonly(emissions_auth, false),
+        AccountMeta::new(
+            get_emissions_token_account_address(bank_f.key, emissions_mint).0,
+            false,
+        ),
+    ];
+    metas.extend(destination.iter().map(|key| AccountMeta::new(*key, false)));
+
+    metas
+}
+
+fn make_claim_all_emissions_ix(
+    test_f: &TestFixture,
+    astl_account_f: &AstrolendAccountFixture,
+    remaining_accounts: Vec<AccountMeta>,
+    deposit: bool,
+) -> Instruction {
+    let mut accounts = astrolend::accounts::LendingAccountClaimAllEmissions {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astl_account_f.key,
+        signer: test_f.payer(),
+        token_program: test_f.get_bank(&BankMint::Usdc).get_token_program(),
+    }
+    .to_account_metas(Some(true));
+
+    // Bumps of the emissions auth and vault of each group
+    let group_len = if deposit { 6 } else { 5 };
+    let emissions_bumps = remaining_accounts
+        .chunks(group_len)
+        .map(|group| {
+            let bump = |seed: &str| {
+                Pubkey::find_program_address(
+                    &[
+                        seed.as_bytes(),
+                        group[0].pubkey.as_ref(),
+                        group[1].pubkey.as_ref(),
+                    ],
+                    &astrolend::id(),
+                )
+                .1
+            };
+            [
+                bump(EMISSIONS_AUTH_SEED),
+                bump(EMISSIONS_TOKEN_ACCOUNT_SEED),
+            ]
+        })
+        .collect();
+    accounts.extend(remaining_accounts);
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountClaimAllEmissions {
+            deposit,
+            emissions_bumps,
+        }
+        .data(),
+    }
+}
+
+/// USDC lending emissions on the USDC bank and USDC borrow emissions on the SOL bank, with an
+/// account lending 50 USDC and borrowing 2 SOL for half a year.
+async fn setup_claim_all_emissions() -> anyhow::Result<(TestFixture, AstrolendAccountFixture)> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let funding_account = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(50, "USDC"),
+            usdc_bank.mint.key,
+            funding_account.key,
+            usdc_bank.get_token_program(),
+        )
+        .await?;
+    sol_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_BORROW_ACTIVE,
+            1_000_000,
+            native!(100, "USDC"),
+            usdc_bank.mint.key,
+            funding_account.key,
+            usdc_bank.get_token_program(),
+        )
+        .await?;
+
+    let sol_lender_account = test_f.create_astrolend_account().await;
+    let sol_lender_token_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    sol_lender_account
+        .try_bank_deposit(sol_lender_token_account.key, sol_bank, 100)
+        .await?;
+
+    test_f.set_time(MIN_EMISSIONS_START_TIME as i64);
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_USDC_FEED, MIN_EMISSIONS_START_TIME as i64)
+        .await;
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_SOL_FEED, MIN_EMISSIONS_START_TIME as i64)
+        .await;
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(50).await;
+    astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank, 50)
+        .await?;
+
+    let sol_account = test_f.sol_mint.create_empty_token_account().await;
+    astl_account_f
+        .try_bank_borrow(sol_account.key, sol_bank, 2)
+        .await?;
+
+    test_f.advance_time((SECONDS_PER_YEAR / 2.0) as i64).await;
+
+    Ok((test_f, astl_account_f))
+}
+
+#[tokio::test]
+async fn emissions_claim_all() -> anyhow::Result<()> {
+    let (test_f, astl_account_f) = setup_claim_all_emissions().await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_mint = usdc_bank.mint.key;
+
+    let destination = test_f.usdc_mint.create_empty_token_account().await;
+
+    // A partial group is rejected
+    let mut remaining_accounts =
+        claim_all_emissions_metas(usdc_bank, usdc_mint, &[destination.key]);
+    remaining_accounts.pop();
+    let ix = make_claim_all_emissions_ix(&test_f, &astl_account_f, remaining_accounts, false);
+    let res = swap_collateral::process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidBankAccount);
+
+    // A wrong bump doesn't derive the emissions auth
+    let remaining_accounts = claim_all_emissions_metas(usdc_bank, usdc_mint, &[destination.key]);
+    let mut ix = make_claim_all_emissions_ix(&test_f, &astl_account_f, remaining_accounts, false);
+    let emissions_auth_bump = Pubkey::find_program_address(
+        &[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            usdc_bank.key.as_ref(),
+            usdc_mint.as_ref(),
+        ],
+        &astrolend::id(),
+    )
+    .1;
+    let emissions_vault_bump = get_emissions_token_account_address(usdc_bank.key, usdc_mint).1;
+    ix.data = astrolend::instruction::LendingAccountClaimAllEmissions {
+        deposit: false,
+        emissions_bumps: vec![[emissions_auth_bump.wrapping_sub(1), emissions_vault_bump]],
+    }
+    .data();
+    let res = swap_collateral::process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidEmissionsAccount);
+
+    // Groups of 5 accounts claim the lending and the borrowing emissions at once
+    let mut remaining_accounts =
+        claim_all_emissions_metas(usdc_bank, usdc_mint, &[destination.key]);
+    remaining_accounts.extend(claim_all_emissions_metas(
+        sol_bank,
+        usdc_mint,
+        &[destination.key],
+    ));
+    let ix = make_claim_all_emissions_ix(&test_f, &astl_account_f, remaining_accounts, false);
+    swap_collateral::process_ix(&test_f, ix).await?;
+
+    // 25 USDC for lending 50 USDC and 1 USDC for borrowing 2 SOL
+    assert_eq_with_tolerance!(
+        destination.balance().await as i64,
+        native!(26, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    let astl_account = astl_account_f.load().await;
+    for bank_f in [usdc_bank, sol_bank] {
+        let balance = astl_account
+            .lending_account
+            .get_balance(&bank_f.key)
+            .unwrap();
+        assert!(I80F48::from(balance.emissions_outstanding) < I80F48::ONE);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emissions_claim_all_deposit() -> anyhow::Result<()> {
+    let (test_f, astl_account_f) = setup_claim_all_emissions().await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+    let usdc_mint = usdc_bank.mint.key;
+    let usdc_liquidity_vault = usdc_bank.get_vault(BankVaultType::Liquidity).0;
+
+    // The deposit bank must have the emissions mint as its mint
+    let remaining_accounts = claim_all_emissions_metas(
+        usdc_bank,
+        usdc_mint,
+        &[sol_bank.key, sol_bank.get_vault(BankVaultType::Liquidity).0],
+    );
+    let ix = make_claim_all_emissions_ix(&test_f, &astl_account_f, remaining_accounts, true);
+    let res = swap_collateral::process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::InvalidBankAccount);
+
+    // Groups of 6 accounts deposit both emissions into the USDC bank
+    let pre_vault_balance = usdc_bank
+        .get_vault_token_account(BankVaultType::Liquidity)
+        .await
+        .balance()
+        .await;
+
+    let mut remaining_accounts =
+        claim_all_emissions_metas(usdc_bank, usdc_mint, &[usdc_bank.key, usdc_liquidity_vault]);
+    remaining_accounts.extend(claim_all_emissions_metas(
+        sol_bank,
+        usdc_mint,
+        &[usdc_bank.key, usdc_liquidity_vault],
+    ));
+    let ix = make_claim_all_emissions_ix(&test_f, &astl_account_f, remaining_accounts, true);
+    swap_collateral::process_ix(&test_f, ix).await?;
+
+    let post_vault_balance = usdc_bank
+        .get_vault_token_account(BankVaultType::Liquidity)
+        .await
+        .balance()
+        .await;
+    assert_eq_with_tolerance!(
+        (post_vault_balance - pre_vault_balance) as i64,
+        native!(26, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    // The claimed emissions are added to the 50 USDC deposit
+    let astl_account = astl_account_f.load().await;
+    let balance = astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    assert_eq_noise!(
+        usdc_bank
+            .load()
+            .await
+            .get_asset_amount(balance.asset_shares.into())?,
+        I80F48::from(native!(76, "USDC")),
+        I80F48::from(native!(1, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emissions_stream_added_later_accrues_from_setup() -> anyhow::Result<()> {
+    let (test_f, astl_account_f) = setup_claim_all_emissions().await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    // A second lending stream on the USDC bank, half a year after the deposit
+    let emissions_mint = MintFixture::new(test_f.context.clone(), None, Some(6)).await;
+    let funding_account = emissions_mint.create_token_account_and_mint_to(100).await;
+
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(100, 6),
+            emissions_mint.key,
+            funding_account.key,
+            emissions_mint.token_program,
+        )
+        .await?;
+
+    test_f.advance_time((SECONDS_PER_YEAR / 2.0) as i64).await;
+
+    // The deposit earns the second stream for the half year since its setup only
+    let destination = emissions_mint.create_empty_token_account().await;
+    let remaining_accounts =
+        claim_all_emissions_metas(usdc_bank, emissions_mint.key, &[destination.key]);
+    let ix = make_claim_all_emissions_ix(&test_f, &astl_account_f, remaining_accounts, false);
+    swap_collateral::process_ix(&test_f, ix).await?;
+
+    assert_eq_with_tolerance!(
+        destination.balance().await as i64,
+        native!(25, 6) as i64,
+        native!(1, 6) as i64
+    );
+
+    Ok(())
+}
+
+fn make_clawback_emissions_ix(
+    test_f: &TestFixture,
+    bank_f: &BankFixture,
+    emissions_mint: Pubkey,
+    destination: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::LendingPoolClawbackEmissions {
+            astrolend_group: test_f.astrolend_group.key,
+            admin: test_f.payer(),
+            bank: bank_f.key,
+            emissions_mint,
+            emissions_auth: Pubkey::find_program_address(
+                &[
+                    EMISSIONS_AUTH_SEED.as_bytes(),
+                    bank_f.key.as_ref(),
+                    emissions_mint.as_ref(),
+                ],
+                &astrolend::id(),
+            )
+            .0,
+            emissions_vault: get_emissions_token_account_address(bank_f.key, emissions_mint).0,
+            destination_account: destination,
+            token_program: bank_f.get_token_program(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::LendingPoolClawbackEmissions {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn emissions_clawback_keeps_unsettled() -> anyhow::Result<()> {
+    let (test_f, astl_account_f) = setup_claim_all_emissions().await?;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let usdc_mint = usdc_bank.mint.key;
+
+    // Half of the 50 USDC budget accrued to the unsettled deposit, the other half is returned
+    let destination = test_f.usdc_mint.create_empty_token_account().await;
+    let ix = make_clawback_emissions_ix(&test_f, usdc_bank, usdc_mint, destination.key);
+    swap_collateral::process_ix(&test_f, ix).await?;
+
+    assert_eq_with_tolerance!(
+        destination.balance().await as i64,
+        native!(25, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    // The deposit still claims what it accrued before the clawback, and nothing after
+    test_f.advance_time((SECONDS_PER_YEAR / 2.0) as i64).await;
+
+    let emissions_token_account = test_f.usdc_mint.create_empty_token_account().await;
+    astl_account_f
+        .try_withdraw_emissions(usdc_bank, &emissions_token_account)
+        .await?;
+
+    assert_eq_with_tolerance!(
+        emissions_token_account.balance().await as i64,
+        native!(25, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emissions_clawback_reserves_since_setup() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let usdc_mint = usdc_bank.mint.key;
+
+    // A stream without schedule, set up a year after the emissions cutoff
+    let setup_time = MIN_EMISSIONS_START_TIME as i64 + SECONDS_PER_YEAR as i64;
+    test_f.set_time(setup_time);
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_USDC_FEED, setup_time)
+        .await;
+
+    let funding_account = test_f.usdc_mint.create_token_account_and_mint_to(50).await;
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(50, "USDC"),
+            usdc_mint,
+            funding_account.key,
+            usdc_bank.get_token_program(),
+        )
+        .await?;
+
+    let astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(50).await;
+    astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank, 50)
+        .await?;
+
+    test_f.advance_time((SECONDS_PER_YEAR / 2.0) as i64).await;
+
+    // Only the half year since the setup is reserved, the other half of the budget is returned
+    let destination = test_f.usdc_mint.create_empty_token_account().await;
+    let ix = make_clawback_emissions_ix(&test_f, usdc_bank, usdc_mint, destination.key);
+    swap_collateral::process_ix(&test_f, ix).await?;
+
+    assert_eq_with_tolerance!(
+        destination.balance().await as i64,
+        native!(25, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn account_flags() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
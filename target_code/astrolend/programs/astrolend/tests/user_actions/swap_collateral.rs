@@ -0,0 +1,405 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{
+    constants::MAX_SWAP_PROGRAMS,
+    prelude::*,
+    state::astrolend_group::{BankVaultType, GroupConfig},
+};
+use pretty_assertions::assert_eq;
+use solana_program::{instruction::Instruction, pubkey::Pubkey, system_program, sysvar};
+use solana_program_test::*;
+use solana_sdk::{signer::Signer, transaction::Transaction};
+
+// Collateral swap tests
+// 1. USDC collateral swapped into SOL collateral
+// 2. USDC collateral swapped into SOL repays the SOL liability before depositing
+// 3. Swap fails because the output is below the minimum
+// 4. Swap fails because the swap program is not allowed by the group
+
+pub(super) struct MockPool {
+    pub pool_auth: Pubkey,
+    pub usdc_pool: Pubkey,
+    pub sol_pool: Pubkey,
+}
+
+/// Setup `swap_like_jupiter` pools between USDC and SOL, funded with `usdc_liquidity` and
+/// `sol_liquidity`.
+pub(super) async fn setup_usdc_sol_pool(
+    test_f: &TestFixture,
+    usdc_liquidity: f64,
+    sol_liquidity: f64,
+) -> MockPool {
+    let nonce: u16 = 0;
+    let (pool_auth, _) =
+        Pubkey::find_program_address(&[&nonce.to_le_bytes(), b"pool_auth"], &mocks::id());
+    let (usdc_pool, _) = Pubkey::find_program_address(
+        &[test_f.usdc_mint.key.as_ref(), pool_auth.as_ref(), b"pools"],
+        &mocks::id(),
+    );
+    let (sol_pool, _) = Pubkey::find_program_address(
+        &[test_f.sol_mint.key.as_ref(), pool_auth.as_ref(), b"pools"],
+        &mocks::id(),
+    );
+
+    let init_ix = Instruction {
+        program_id: mocks::id(),
+        accounts: mocks::accounts::InitPoolAuth {
+            payer: test_f.payer(),
+            pool_auth,
+            mint_a: test_f.usdc_mint.key,
+            mint_b: test_f.sol_mint.key,
+            pool_a: usdc_pool,
+            pool_b: sol_pool,
+            token_program: anchor_spl::token::ID,
+            rent: sysvar::rent::id(),
+            system_program: system_program::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: mocks::instruction::InitPoolAuth { nonce }.data(),
+    };
+
+    let usdc_liquidity_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(usdc_liquidity)
+        .await;
+    let sol_liquidity_token_account_f = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(sol_liquidity)
+        .await;
+    let fund_ixs: Vec<Instruction> = [
+        (
+            usdc_liquidity_token_account_f.key,
+            usdc_pool,
+            native!(usdc_liquidity, "USDC", f64),
+        ),
+        (
+            sol_liquidity_token_account_f.key,
+            sol_pool,
+            native!(sol_liquidity, "SOL", f64),
+        ),
+    ]
+    .map(|(source, pool, amount)| {
+        anchor_spl::token::spl_token::instruction::transfer(
+            &anchor_spl::token::ID,
+            &source,
+            &pool,
+            &test_f.payer(),
+            &[],
+            amount,
+        )
+        .unwrap()
+    })
+    .into();
+
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[vec![init_ix], fund_ixs].concat(),
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    MockPool {
+        pool_auth,
+        usdc_pool,
+        sol_pool,
+    }
+}
+
+pub(super) async fn allow_swap_program(
+    test_f: &TestFixture,
+    program: Pubkey,
+) -> anyhow::Result<()> {
+    let mut swap_programs = [Pubkey::default(); MAX_SWAP_PROGRAMS];
+    swap_programs[0] = program;
+
+    test_f
+        .astrolend_group
+        .try_update(GroupConfig {
+            admin: None,
+            bank_config_delay: None,
+            reserve_haircut: None,
+            swap_programs: Some(swap_programs),
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn make_swap_collateral_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    pool: &MockPool,
+    usdc_token_account: Pubkey,
+    sol_token_account: Pubkey,
+    usdc_amount: u64,
+    sol_amount: u64,
+    min_sol_amount: u64,
+) -> Instruction {
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let mut accounts = astrolend::accounts::LendingAccountSwapCollateral {
+        astrolend_group: test_f.astrolend_group.key,
+        astrolend_account: astrolend_account_f.key,
+        signer: test_f.payer(),
+        withdraw_bank: usdc_bank.key,
+        withdraw_token_account: usdc_token_account,
+        withdraw_bank_liquidity_vault_authority: usdc_bank
+            .get_vault_authority(BankVaultType::Liquidity)
+            .0,
+        withdraw_bank_liquidity_vault: usdc_bank.get_vault(BankVaultType::Liquidity).0,
+        withdraw_token_program: usdc_bank.get_token_program(),
+        deposit_bank: sol_bank.key,
+        deposit_token_account: sol_token_account,
+        deposit_bank_liquidity_vault: sol_bank.get_vault(BankVaultType::Liquidity).0,
+        deposit_token_program: sol_bank.get_token_program(),
+        swap_program: mocks::id(),
+    }
+    .to_account_metas(Some(true));
+
+    let swap_accounts = mocks::accounts::SwapLikeJupiter {
+        user_authority: test_f.payer(),
+        pool_auth: pool.pool_auth,
+        pool_a: pool.usdc_pool,
+        pool_b: pool.sol_pool,
+        source_a: usdc_token_account,
+        destination_b: sol_token_account,
+        token_program: anchor_spl::token::ID,
+    }
+    .to_account_metas(Some(true));
+    let swap_accounts_count = swap_accounts.len() as u8;
+    accounts.extend(swap_accounts);
+    accounts.extend(
+        astrolend_account_f
+            .load_observation_account_metas(vec![sol_bank.key], vec![])
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountSwapCollateral {
+            withdraw_amount: usdc_amount,
+            min_deposit_amount: min_sol_amount,
+            swap_accounts_count,
+            swap_data: mocks::instruction::SwapLikeJupiter {
+                amt_a: usdc_amount,
+                amt_b: sol_amount,
+            }
+            .data(),
+        }
+        .data(),
+    }
+}
+
+pub(super) async fn process_ix(
+    test_f: &TestFixture,
+    ix: Instruction,
+) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+#[tokio::test]
+async fn swap_collateral_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let pool = setup_usdc_sol_pool(&test_f, 0., 100.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_usdc_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    user_astl_account_f
+        .try_bank_deposit(user_usdc_token_account_f.key, usdc_bank, 1_000)
+        .await?;
+    let user_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+
+    // Swap 500 USDC for 5 SOL
+    let ix = make_swap_collateral_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_usdc_token_account_f.key,
+        user_sol_token_account_f.key,
+        native!(500, "USDC"),
+        native!(5, "SOL"),
+        native!(5, "SOL"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    // Everything went through the wallet and back into the account
+    assert_eq!(user_usdc_token_account_f.balance().await, 0);
+    assert_eq!(user_sol_token_account_f.balance().await, 0);
+
+    let user_astl_account = user_astl_account_f.load().await;
+
+    let usdc_balance = user_astl_account
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    let usdc_amount = usdc_bank
+        .load()
+        .await
+        .get_asset_amount(usdc_balance.asset_shares.into())?;
+    assert_eq!(usdc_amount, I80F48::from(native!(500, "USDC")));
+
+    let sol_balance = user_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    let sol_amount = sol_bank
+        .load()
+        .await
+        .get_asset_amount(sol_balance.asset_shares.into())?;
+    assert_eq!(sol_amount, I80F48::from(native!(5, "SOL")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn swap_collateral_repays_liability() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let pool = setup_usdc_sol_pool(&test_f, 0., 100.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_sol_token_account_f = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_sol_token_account_f.key, sol_bank, 100)
+        .await?;
+
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_usdc_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    user_astl_account_f
+        .try_bank_deposit(user_usdc_token_account_f.key, usdc_bank, 1_000)
+        .await?;
+    let user_sol_borrow_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+    user_astl_account_f
+        .try_bank_borrow(user_sol_borrow_token_account_f.key, sol_bank, 2)
+        .await?;
+    let user_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+
+    // Swap 500 USDC for 5 SOL, 2 SOL repay the liability and 3 SOL are deposited
+    let ix = make_swap_collateral_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_usdc_token_account_f.key,
+        user_sol_token_account_f.key,
+        native!(500, "USDC"),
+        native!(5, "SOL"),
+        native!(5, "SOL"),
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    assert_eq!(user_sol_token_account_f.balance().await, 0);
+
+    let user_astl_account = user_astl_account_f.load().await;
+    let sol_balance = user_astl_account
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    assert_eq!(I80F48::from(sol_balance.liability_shares), I80F48::ZERO);
+    let sol_amount = sol_bank
+        .load()
+        .await
+        .get_asset_amount(sol_balance.asset_shares.into())?;
+    assert_eq!(sol_amount, I80F48::from(native!(3, "SOL")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn swap_collateral_failure_below_min_output() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let pool = setup_usdc_sol_pool(&test_f, 0., 100.).await;
+    allow_swap_program(&test_f, mocks::id()).await?;
+
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_usdc_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    user_astl_account_f
+        .try_bank_deposit(user_usdc_token_account_f.key, usdc_bank, 1_000)
+        .await?;
+    let user_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+
+    // Swap 500 USDC for 4 SOL, expecting at least 5 SOL
+    let ix = make_swap_collateral_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_usdc_token_account_f.key,
+        user_sol_token_account_f.key,
+        native!(500, "USDC"),
+        native!(4, "SOL"),
+        native!(5, "SOL"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SwapOutputBelowMinimum);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn swap_collateral_failure_swap_program_not_allowed() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let pool = setup_usdc_sol_pool(&test_f, 0., 100.).await;
+
+    let user_astl_account_f = test_f.create_astrolend_account().await;
+    let user_usdc_token_account_f = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    user_astl_account_f
+        .try_bank_deposit(user_usdc_token_account_f.key, usdc_bank, 1_000)
+        .await?;
+    let user_sol_token_account_f = test_f.sol_mint.create_empty_token_account().await;
+
+    let ix = make_swap_collateral_ix(
+        &test_f,
+        &user_astl_account_f,
+        &pool,
+        user_usdc_token_account_f.key,
+        user_sol_token_account_f.key,
+        native!(500, "USDC"),
+        native!(5, "SOL"),
+        native!(5, "SOL"),
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+
+    assert_custom_error!(res.unwrap_err(), AstrolendError::SwapProgramNotAllowed);
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use anchor_lang::{error::ErrorCode, InstructionData, ToAccountMetas};
+use fixtures::{assert_anchor_error, assert_custom_error, prelude::*};
+use astrolend::prelude::*;
+use pretty_assertions::assert_eq;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+
+// Account authority transfer tests
+// 1. Authority offers the account, only the offered authority can accept it, no admin flag needed
+// 2. A cancelled offer can't be accepted
+
+async fn process_ix(
+    test_f: &TestFixture,
+    ix: Instruction,
+    signer: Option<&Keypair>,
+) -> Result<(), BanksClientError> {
+    let mut ctx = test_f.context.borrow_mut();
+    let mut signers = vec![&ctx.payer];
+    signers.extend(signer);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&ctx.payer.pubkey().clone()),
+        &signers,
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+fn make_offer_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+    new_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountOfferAuthorityTransfer {
+            astrolend_account: astrolend_account_f.key,
+            authority: test_f.payer(),
+            new_authority,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountOfferAuthorityTransfer {}.data(),
+    }
+}
+
+fn make_cancel_ix(
+    test_f: &TestFixture,
+    astrolend_account_f: &AstrolendAccountFixture,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountCancelAuthorityTransfer {
+            astrolend_account: astrolend_account_f.key,
+            authority: test_f.payer(),
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountCancelAuthorityTransfer {}.data(),
+    }
+}
+
+fn make_accept_ix(
+    astrolend_account_f: &AstrolendAccountFixture,
+    new_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: astrolend::id(),
+        accounts: astrolend::accounts::AstrolendAccountAcceptAuthorityTransfer {
+            astrolend_account: astrolend_account_f.key,
+            new_authority,
+        }
+        .to_account_metas(Some(true)),
+        data: astrolend::instruction::AstrolendAccountAcceptAuthorityTransfer {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn authority_transfer_offer_accept() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let astl_account_f = test_f.create_astrolend_account().await;
+
+    let new_authority = Keypair::new();
+    let ix = make_offer_ix(&test_f, &astl_account_f, new_authority.pubkey());
+    process_ix(&test_f, ix, None).await?;
+
+    let astrolend_account = astl_account_f.load().await;
+    assert_eq!(astrolend_account.authority, test_f.payer());
+    assert_eq!(astrolend_account.pending_authority, new_authority.pubkey());
+
+    // Someone else can't accept the offer
+    let other = Keypair::new();
+    let ix = make_accept_ix(&astl_account_f, other.pubkey());
+    let res = process_ix(&test_f, ix, Some(&other)).await;
+    assert!(res.is_err());
+    assert_anchor_error!(res.unwrap_err(), ErrorCode::ConstraintAddress);
+
+    let ix = make_accept_ix(&astl_account_f, new_authority.pubkey());
+    process_ix(&test_f, ix, Some(&new_authority)).await?;
+
+    let astrolend_account = astl_account_f.load().await;
+    assert_eq!(astrolend_account.authority, new_authority.pubkey());
+    assert_eq!(astrolend_account.pending_authority, Pubkey::default());
+
+    // The previous authority can no longer offer the account
+    let ix = make_offer_ix(&test_f, &astl_account_f, other.pubkey());
+    let res = process_ix(&test_f, ix, None).await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn authority_transfer_cancelled_offer() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let astl_account_f = test_f.create_astrolend_account().await;
+
+    // The account can't be offered to its own authority
+    let ix = make_offer_ix(&test_f, &astl_account_f, test_f.payer());
+    let res = process_ix(&test_f, ix, None).await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        AstrolendError::IllegalAccountAuthorityTransfer
+    );
+
+    let new_authority = Keypair::new();
+    let ix = make_offer_ix(&test_f, &astl_account_f, new_authority.pubkey());
+    process_ix(&test_f, ix, None).await?;
+
+    let ix = make_cancel_ix(&test_f, &astl_account_f);
+    process_ix(&test_f, ix, None).await?;
+
+    let ix = make_accept_ix(&astl_account_f, new_authority.pubkey());
+    let res = process_ix(&test_f, ix, Some(&new_authority)).await;
+    assert!(res.is_err());
+
+    let astrolend_account = astl_account_f.load().await;
+    assert_eq!(astrolend_account.authority, test_f.payer());
+    assert_eq!(astrolend_account.pending_authority, Pubkey::default());
+
+    Ok(())
+}
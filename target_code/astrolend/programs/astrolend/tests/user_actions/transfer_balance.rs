@@ -0,0 +1,256 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use fixed::types::I80F48;
+use fixtures::{assert_custom_error, native, prelude::*};
+use astrolend::{prelude::*, state::astrolend_group::BankVaultType};
+use pretty_assertions::assert_eq;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_program_test::*;
+
+use super::swap_collateral::process_ix;
+
+// Balance transfer tests
+// 1. Assets and liabilities move between two accounts of the same authority, without tokens
+// 2. The destination can't take over a liability it can't cover
+// 3. Balances can't be transferred during a flashloan
+
+async fn make_transfer_balance_ix(
+    test_f: &TestFixture,
+    source: &AstrolendAccountFixture,
+    destination: &AstrolendAccountFixture,
+    bank: &BankFixture,
+    amount: u64,
+    transfer_all: Option<bool>,
+    health_banks: [(Vec<Pubkey>, Vec<Pubkey>); 2],
+) -> Instruction {
+    let [source_health, destination_health] = health_banks;
+    let mut accounts = astrolend::accounts::LendingAccountTransferBalance {
+        astrolend_group: test_f.astrolend_group.key,
+        source_astrolend_account: source.key,
+        destination_astrolend_account: destination.key,
+        signer: test_f.payer(),
+        bank: bank.key,
+    }
+    .to_account_metas(Some(true));
+    accounts.extend(
+        source
+            .load_observation_account_metas(source_health.0, source_health.1)
+            .await,
+    );
+    accounts.extend(
+        destination
+            .load_observation_account_metas(destination_health.0, destination_health.1)
+            .await,
+    );
+
+    Instruction {
+        program_id: astrolend::id(),
+        accounts,
+        data: astrolend::instruction::LendingAccountTransferBalance {
+            amount,
+            transfer_all,
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn transfer_balance_assets_and_liabilities() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let sol_depositor = test_f.create_astrolend_account().await;
+    let sol_token_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    sol_depositor
+        .try_bank_deposit(sol_token_account.key, sol_bank, 100)
+        .await?;
+
+    let source = test_f.create_astrolend_account().await;
+    let destination = test_f.create_astrolend_account().await;
+    let usdc_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    source
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 1_000)
+        .await?;
+    let sol_token_account = test_f.sol_mint.create_empty_token_account().await;
+    source
+        .try_bank_borrow(sol_token_account.key, sol_bank, 10)
+        .await?;
+    let usdc_vault_balance = usdc_bank
+        .get_vault_token_account(BankVaultType::Liquidity)
+        .await
+        .balance()
+        .await;
+
+    // Move part of the collateral
+    let ix = make_transfer_balance_ix(
+        &test_f,
+        &source,
+        &destination,
+        usdc_bank,
+        native!(400, "USDC"),
+        None,
+        [(vec![], vec![]), (vec![usdc_bank.key], vec![])],
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    let usdc_bank_data = usdc_bank.load().await;
+    let source_balance = *source
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    let destination_balance = *destination
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank.key)
+        .unwrap();
+    assert_eq!(
+        usdc_bank_data.get_asset_amount(source_balance.asset_shares.into())?,
+        I80F48::from(native!(600, "USDC"))
+    );
+    assert_eq!(
+        usdc_bank_data.get_asset_amount(destination_balance.asset_shares.into())?,
+        I80F48::from(native!(400, "USDC"))
+    );
+    // No tokens moved
+    assert_eq!(
+        usdc_bank
+            .get_vault_token_account(BankVaultType::Liquidity)
+            .await
+            .balance()
+            .await,
+        usdc_vault_balance
+    );
+
+    // Move the whole liability, the source balance is closed
+    let ix = make_transfer_balance_ix(
+        &test_f,
+        &source,
+        &destination,
+        sol_bank,
+        0,
+        Some(true),
+        [(vec![], vec![sol_bank.key]), (vec![sol_bank.key], vec![])],
+    )
+    .await;
+    process_ix(&test_f, ix).await?;
+
+    assert!(source
+        .load()
+        .await
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .is_none());
+    let destination_balance = *destination
+        .load()
+        .await
+        .lending_account
+        .get_balance(&sol_bank.key)
+        .unwrap();
+    assert_eq!(
+        sol_bank
+            .load()
+            .await
+            .get_liability_amount(destination_balance.liability_shares.into())?,
+        I80F48::from(native!(10, "SOL"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transfer_balance_failure_destination_unhealthy() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank = test_f.get_bank(&BankMint::Sol);
+
+    let sol_depositor = test_f.create_astrolend_account().await;
+    let sol_token_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    sol_depositor
+        .try_bank_deposit(sol_token_account.key, sol_bank, 100)
+        .await?;
+
+    let source = test_f.create_astrolend_account().await;
+    let destination = test_f.create_astrolend_account().await;
+    let usdc_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    source
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 1_000)
+        .await?;
+    let sol_token_account = test_f.sol_mint.create_empty_token_account().await;
+    source
+        .try_bank_borrow(sol_token_account.key, sol_bank, 10)
+        .await?;
+
+    // The destination has no collateral for the liability
+    let ix = make_transfer_balance_ix(
+        &test_f,
+        &source,
+        &destination,
+        sol_bank,
+        native!(5, "SOL"),
+        None,
+        [(vec![], vec![]), (vec![sol_bank.key], vec![])],
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::RiskEngineInitRejected);
+
+    // A balance can't be moved to the same account
+    let ix = make_transfer_balance_ix(
+        &test_f,
+        &source,
+        &source,
+        usdc_bank,
+        native!(100, "USDC"),
+        None,
+        [(vec![], vec![]), (vec![], vec![])],
+    )
+    .await;
+    let res = process_ix(&test_f, ix).await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transfer_balance_failure_in_flashloan() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank = test_f.get_bank(&BankMint::Usdc);
+
+    let source = test_f.create_astrolend_account().await;
+    let destination = test_f.create_astrolend_account().await;
+    let usdc_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    source
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 1_000)
+        .await?;
+
+    let ix = make_transfer_balance_ix(
+        &test_f,
+        &source,
+        &destination,
+        usdc_bank,
+        native!(400, "USDC"),
+        None,
+        [(vec![], vec![]), (vec![usdc_bank.key], vec![])],
+    )
+    .await;
+    let res = source.try_flashloan(vec![ix], vec![], vec![]).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::AccountInFlashloan);
+
+    Ok(())
+}
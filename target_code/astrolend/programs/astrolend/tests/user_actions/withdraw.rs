@@ -2,8 +2,13 @@ use anchor_spl::token_2022::spl_token_2022::extension::{
     transfer_fee::TransferFeeConfig, BaseStateWithExtensions,
 };
 use fixed::types::I80F48;
-use fixtures::{assert_custom_error, prelude::*, ui_to_native};
-use astrolend::{assert_eq_with_tolerance, prelude::*, state::astrolend_group::BankVaultType};
+use fixed_macro::types::I80F48;
+use fixtures::{assert_custom_error, assert_eq_noise, native, prelude::*, ui_to_native};
+use astrolend::{
+    assert_eq_with_tolerance,
+    prelude::*,
+    state::astrolend_group::{BankConfigOpt, BankVaultType},
+};
 use pretty_assertions::assert_eq;
 use solana_program_test::*;
 use test_case::test_case;
@@ -300,3 +305,141 @@ async fn astrolend_account_withdraw_failure_withdrawing_too_much(
 
     Ok(())
 }
+
+#[tokio::test]
+async fn astrolend_account_withdraw_discounted_by_deficit() -> anyhow::Result<()> {
+    // -------------------------------------------------------------------------
+    // Setup
+    // -------------------------------------------------------------------------
+
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+    let usdc_bank_f = test_f.get_bank(&BankMint::Usdc);
+    let sol_bank_f = test_f.get_bank(&BankMint::Sol);
+
+    usdc_bank_f
+        .update_config(BankConfigOpt {
+            deficit_interest_share: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Lender
+
+    let lender_astl_account_f = test_f.create_astrolend_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+    lender_astl_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000)
+        .await?;
+
+    // Borrower, made bankrupt by nullifying its collateral
+
+    let borrower_astl_account_f = test_f.create_astrolend_account().await;
+    let sufficient_collateral_amount = test_f
+        .get_sufficient_collateral_for_outflow(10_000., &BankMint::Sol, &BankMint::Usdc)
+        .await;
+    let borrower_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(sufficient_collateral_amount)
+        .await;
+    borrower_astl_account_f
+        .try_bank_deposit(
+            borrower_token_account_sol.key,
+            sol_bank_f,
+            sufficient_collateral_amount,
+        )
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    borrower_astl_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 10_000)
+        .await?;
+
+    let mut borrower_astl_account = borrower_astl_account_f.load().await;
+    borrower_astl_account.lending_account.balances[0]
+        .asset_shares
+        .value = 0_i128.to_le_bytes();
+    borrower_astl_account_f
+        .set_account(&borrower_astl_account)
+        .await?;
+
+    // The 10k of bad debt is recorded as a deficit, deposits are only 90% backed
+
+    test_f
+        .astrolend_group
+        .try_handle_bankruptcy(usdc_bank_f, &borrower_astl_account_f)
+        .await?;
+
+    assert_eq_noise!(
+        I80F48::from(usdc_bank_f.load().await.deficit),
+        I80F48::from(native!(10_000, "USDC")),
+        I80F48::ONE
+    );
+
+    // -------------------------------------------------------------------------
+    // Test
+    // -------------------------------------------------------------------------
+
+    // Withdrawing beyond the backed deposits fails
+    let destination_token_account_usdc = test_f.usdc_mint.create_empty_token_account().await;
+    let res = lender_astl_account_f
+        .try_bank_withdraw(
+            destination_token_account_usdc.key,
+            usdc_bank_f,
+            95_000,
+            None,
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), AstrolendError::OperationWithdrawOnly);
+
+    // Withdrawing 9k burns 10k of deposits, the forfeited 1k repays the deficit
+    lender_astl_account_f
+        .try_bank_withdraw(destination_token_account_usdc.key, usdc_bank_f, 9_000, None)
+        .await?;
+
+    assert_eq!(
+        destination_token_account_usdc.balance().await,
+        native!(9_000, "USDC")
+    );
+    let usdc_bank = usdc_bank_f.load().await;
+    let lender_astl_account = lender_astl_account_f.load().await;
+    assert_eq_noise!(
+        usdc_bank.get_asset_amount(
+            lender_astl_account.lending_account.balances[0]
+                .asset_shares
+                .into()
+        )?,
+        I80F48::from(native!(90_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.deficit),
+        I80F48::from(native!(9_000, "USDC")),
+        I80F48::ONE
+    );
+
+    // Withdrawing all pays out the backed 81k and leaves no deficit
+    lender_astl_account_f
+        .try_bank_withdraw(
+            destination_token_account_usdc.key,
+            usdc_bank_f,
+            0,
+            Some(true),
+        )
+        .await?;
+
+    assert_eq_noise!(
+        I80F48::from(destination_token_account_usdc.balance().await),
+        I80F48::from(native!(90_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq_noise!(
+        I80F48::from(usdc_bank_f.load().await.deficit),
+        I80F48::ZERO,
+        I80F48::ONE
+    );
+
+    Ok(())
+}
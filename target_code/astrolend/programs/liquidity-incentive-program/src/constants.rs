@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+#[constant]
+pub const CAMPAIGN_SEED: &str = "campaign";
+#[constant]
+pub const CAMPAIGN_AUTH_SEED: &str = "campaign_auth";
+#[constant]
+pub const DEPOSIT_ASTROLEND_AUTH_SIGNER_SEED: &str = "deposit_astrolend_auth";
+#[constant]
+pub const TEMP_TOKEN_ACCOUNT_AUTH_SEED: &str = "ephemeral_token_account_auth";
+#[constant]
+pub const ASTROLEND_ACCOUNT_SEED: &str = "astrolend_account";
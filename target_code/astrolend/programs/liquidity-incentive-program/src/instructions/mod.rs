@@ -0,0 +1,7 @@
+pub mod create_campaign;
+pub mod create_deposit;
+pub mod end_deposit;
+
+pub use create_campaign::*;
+pub use create_deposit::*;
+pub use end_deposit::*;
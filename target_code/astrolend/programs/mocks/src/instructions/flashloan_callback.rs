@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+
+#[derive(Accounts)]
+pub struct AstrolendFlashloanCallback<'info> {
+    pub user_authority: Signer<'info>,
+
+    #[account(mut)]
+    pub source: Account<'info, TokenAccount>,
+
+    /// The bank liquidity vault
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Repay a flashloan with callback, `data` is read as the amount to repay if set, so tests can
+/// under or over repay.
+pub fn astrolend_flashloan_callback(
+    ctx: Context<AstrolendFlashloanCallback>,
+    repay_amount: u64,
+    data: Vec<u8>,
+) -> Result<()> {
+    let amount = match data.get(..8) {
+        Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+        None => repay_amount,
+    };
+    msg!(
+        "Flashloan callback, repay {:?} transfer {:?}",
+        repay_amount,
+        amount
+    );
+
+    token::transfer(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.source.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: ctx.accounts.user_authority.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
@@ -1,7 +1,9 @@
 pub mod do_nothing;
+pub mod flashloan_callback;
 pub mod init_pool_auth;
 pub mod swap_like_jupiter;
 
 pub use do_nothing::*;
+pub use flashloan_callback::*;
 pub use init_pool_auth::*;
 pub use swap_like_jupiter::*;
@@ -36,6 +36,18 @@ pub mod mocks {
         instructions::swap_like_jupiter::SwapLikeJupiter::swap_like_jup(ctx, amt_a, amt_b)
     }
 
+    /// Callback of astrolend's `lending_account_flashloan_with_callback`, repays the flashloan.
+    pub fn astrolend_flashloan_callback(
+        ctx: Context<AstrolendFlashloanCallback>,
+        _astrolend_account: Pubkey,
+        _bank: Pubkey,
+        _amount: u64,
+        repay_amount: u64,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::flashloan_callback::astrolend_flashloan_callback(ctx, repay_amount, data)
+    }
+
     #[derive(Accounts)]
     pub struct Write<'info> {
         #[account(mut)]